@@ -1,60 +1,74 @@
-use std::{
-    cell::RefCell,
-    collections::{BTreeMap, LinkedList},
-};
+use std::cell::RefCell;
 
 use candid::{Nat, Principal};
 use ic_cdk::{
-    api::time,
-    caller, export_candid, init, post_upgrade, pre_upgrade, query,
-    storage::{stable_restore, stable_save},
-    trap, update,
+    api::{call::ManualReply, time},
+    caller, export_candid, init, post_upgrade, query, trap, update,
+};
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager},
+    Cell, DefaultMemoryImpl, StableBTreeMap,
 };
+use icrc_ledger_types::icrc1::transfer::TransferError;
 use shared::{
     bank::{
         api::{
             GetExchangeRatesRequest, GetExchangeRatesResponse, GetFmjStatsRequest,
-            GetFmjStatsResponse, SetExchangeRateRequest, SetExchangeRateResponse,
-            SwapRewardsRequest, SwapRewardsResponse,
+            GetFmjStatsResponse, GetTreasuryCapsRequest, GetTreasuryCapsResponse,
+            GetTreasuryTransfersRequest, GetTreasuryTransfersResponse, SetExchangeRateRequest,
+            SetExchangeRateResponse, SetTreasuryCapsRequest, SetTreasuryCapsResponse,
+            SwapRewardsRequest, SwapRewardsResponse, TreasuryTransferRequest,
+            TreasuryTransferResponse,
         },
-        state::BankState,
-        types::{SwapFrom, SwapInto},
+        state::{BankState, LegacyBankState},
+        types::{SwapFrom, SwapInto, TreasuryCaps},
     },
-    btreemap,
     e8s::E8s,
     humans::{api::RefundRewardsRequest, client::HumansCanisterClient},
+    stable_memory::restore_legacy_state,
     Guard, ENV_VARS,
 };
 
 #[init]
 fn init_hook() {
     with_state_mut(|s| {
-        s.fmj_canister_id = ENV_VARS.fmj_canister_id;
-        s.icp_canister_id = ENV_VARS.icp_canister_id;
-
-        let now = time();
-
-        let exchange_rates = btreemap! {
-            (SwapFrom::Storypoint, SwapInto::FMJ) => LinkedList::from([(now, E8s(Nat::from(10000_0000_0000u64)))]),
-            (SwapFrom::Storypoint, SwapInto::ICP) => LinkedList::from([(now, E8s(Nat::from(1_0000_0000u64)))]),
-            (SwapFrom::Hour, SwapInto::FMJ) => LinkedList::from([(now, E8s(Nat::from(10000_0000_0000u64)))]),
-            (SwapFrom::Hour, SwapInto::ICP) => LinkedList::from([(now, E8s(Nat::from(1_0000_0000u64)))]),
-        };
+        let exchange_rates = vec![
+            (
+                SwapFrom::Storypoint,
+                SwapInto::FMJ,
+                E8s(Nat::from(1_0000_0000_0000u64)),
+            ),
+            (
+                SwapFrom::Storypoint,
+                SwapInto::ICP,
+                E8s(Nat::from(1_0000_0000u64)),
+            ),
+            (
+                SwapFrom::Hour,
+                SwapInto::FMJ,
+                E8s(Nat::from(1_0000_0000_0000u64)),
+            ),
+            (
+                SwapFrom::Hour,
+                SwapInto::ICP,
+                E8s(Nat::from(1_0000_0000u64)),
+            ),
+        ];
 
-        s.exchange_rates = exchange_rates;
+        s.init(
+            ENV_VARS.fmj_canister_id,
+            ENV_VARS.icp_canister_id,
+            exchange_rates,
+            time(),
+        );
     });
 }
 
-#[pre_upgrade]
-fn pre_upgrade_hook() {
-    with_state(|s| stable_save((s,)).expect("Unable to stable save"));
-}
-
 #[post_upgrade]
 fn post_upgrade_hook() {
-    let (bank_state,): (BankState,) = stable_restore().expect("Unable to stable restore");
-
-    with_state_mut(|s| *s = bank_state);
+    if let Some(legacy_state) = restore_legacy_state::<LegacyBankState>() {
+        with_state_mut(|s| s.migrate_legacy(legacy_state));
+    }
 }
 
 #[update]
@@ -124,6 +138,88 @@ async fn bank__swap_rewards(mut req: SwapRewardsRequest) -> SwapRewardsResponse
     trap(&err);
 }
 
+// rejects instead of trapping, so the released spending is kept
+#[update(manual_reply = true)]
+#[allow(non_snake_case)]
+async fn bank__treasury_transfer(
+    mut req: TreasuryTransferRequest,
+) -> ManualReply<TreasuryTransferResponse> {
+    let reserved = with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to make a treasury transfer");
+
+        s.reserve_treasury_spending(&req, time())
+    });
+
+    let (icrc1_client, transfer_arg) = match reserved {
+        Ok(it) => it,
+        Err(e) => return ManualReply::reject(e),
+    };
+
+    let err = match icrc1_client.icrc1_transfer(transfer_arg).await {
+        Err((code, msg)) => format!("Bad treasury transfer: [{:?}] {}", code, msg),
+        Ok((res,)) => match res {
+            // an earlier attempt of the same voting went through
+            Err(TransferError::Duplicate { duplicate_of }) => {
+                let id = with_state_mut(|s| {
+                    s.record_treasury_transfer(req, duplicate_of.clone(), time())
+                });
+
+                return ManualReply::one(TreasuryTransferResponse {
+                    id,
+                    block_idx: duplicate_of,
+                });
+            }
+            Err(e) => format!("Bad treasury transfer: {}", e),
+            Ok(block_idx) => {
+                let id =
+                    with_state_mut(|s| s.record_treasury_transfer(req, block_idx.clone(), time()));
+
+                return ManualReply::one(TreasuryTransferResponse { id, block_idx });
+            }
+        },
+    };
+
+    with_state_mut(|s| s.release_treasury_spending(&req));
+
+    ManualReply::reject(err)
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn bank__set_treasury_caps(mut req: SetTreasuryCapsRequest) -> SetTreasuryCapsResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to set treasury caps");
+
+        s.set_treasury_caps(req)
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn bank__get_treasury_caps(mut req: GetTreasuryCapsRequest) -> GetTreasuryCapsResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get treasury caps");
+
+        s.get_treasury_caps(req, time())
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn bank__get_treasury_transfers(
+    mut req: GetTreasuryTransfersRequest,
+) -> GetTreasuryTransfersResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get treasury transfers");
+
+        s.get_treasury_transfers(req)
+    })
+}
+
 #[query]
 #[allow(non_snake_case)]
 fn bank__get_exchange_rates(mut req: GetExchangeRatesRequest) -> GetExchangeRatesResponse {
@@ -147,7 +243,22 @@ fn bank__get_fmj_stats(mut req: GetFmjStatsRequest) -> GetFmjStatsResponse {
 }
 
 thread_local! {
-    static BANK_STATE: RefCell<BankState> = RefCell::new(BankState::new(Principal::management_canister(), Principal::management_canister()));
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    static BANK_STATE: RefCell<BankState> = RefCell::new(
+        BankState {
+            fmj_canister_id: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))), Principal::management_canister()).expect("Unable to create FMJ canister id cell"),
+            icp_canister_id: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))), Principal::management_canister()).expect("Unable to create ICP canister id cell"),
+            exchange_rates: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2)))),
+            monthly_minted_fmj: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3)))),
+            fmj_total_supply: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))), E8s::zero()).expect("Unable to create FMJ total supply cell"),
+            treasury_caps: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))), TreasuryCaps::default()).expect("Unable to create treasury caps cell"),
+            treasury_spending: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6)))),
+            treasury_transfers: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7)))),
+            treasury_transfer_attempts: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8)))),
+        }
+    );
 }
 
 fn with_state<R, F: FnOnce(&BankState) -> R>(f: F) -> R {
@@ -167,3 +278,35 @@ fn with_state_mut<R, F: FnOnce(&mut BankState) -> R>(f: F) -> R {
 }
 
 export_candid!();
+
+#[cfg(test)]
+mod tests {
+    // without the flag the macro replies on its own and traps, since a ManualReply can't be encoded
+    #[test]
+    fn manual_replies_are_flagged() {
+        let src = include_str!("lib.rs");
+        let lines: Vec<_> = src.lines().collect();
+
+        for (idx, line) in lines.iter().enumerate() {
+            let is_signature = line.starts_with("async fn ")
+                || line.starts_with("fn ")
+                || line.starts_with(") -> ");
+
+            if !is_signature || !line.contains("-> ManualReply<") {
+                continue;
+            }
+
+            let attr = lines[..idx]
+                .iter()
+                .rev()
+                .find(|it| it.starts_with("#[update") || it.starts_with("#[query"))
+                .expect("No endpoint attribute");
+
+            assert!(
+                attr.contains("manual_reply = true"),
+                "Endpoint at line {} lacks manual_reply",
+                idx + 1
+            );
+        }
+    }
+}
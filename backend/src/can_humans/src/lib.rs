@@ -1,12 +1,12 @@
 use std::cell::RefCell;
 
-use ic_cdk::{
-    api::time,
-    caller, export_candid, post_upgrade, pre_upgrade, query,
-    storage::{stable_restore, stable_save},
-    update,
+use ic_cdk::{api::time, caller, export_candid, post_upgrade, query, update};
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager},
+    Cell, DefaultMemoryImpl, StableBTreeMap,
 };
 use shared::{
+    e8s::E8s,
     humans::{
         api::{
             EditProfileRequest, EditProfileResponse, EmployRequest, EmployResponse,
@@ -16,21 +16,17 @@ use shared::{
             RefundRewardsResponse, RegisterRequest, RegisterResponse, SpendRewardsRequest,
             SpendRewardsResponse, UnemployRequest, UnemployResponse,
         },
-        state::HumansState,
+        state::{HumansState, LegacyHumansState},
     },
+    stable_memory::restore_legacy_state,
     Guard,
 };
 
-#[pre_upgrade]
-fn pre_upgrade_hook() {
-    with_state(|s| stable_save((s,)).expect("Unable to stable save"));
-}
-
 #[post_upgrade]
 fn post_upgrade_hook() {
-    let (humans_state,): (HumansState,) = stable_restore().expect("Unable to stable restore");
-
-    with_state_mut(|s| *s = humans_state);
+    if let Some(legacy_state) = restore_legacy_state::<LegacyHumansState>() {
+        with_state_mut(|s| s.migrate_legacy(legacy_state));
+    }
 }
 
 #[update]
@@ -161,7 +157,17 @@ fn humans__init_once() {
 }
 
 thread_local! {
-    static HUMANS_STATE: RefCell<HumansState> = RefCell::default();
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    static HUMANS_STATE: RefCell<HumansState> = RefCell::new(
+        HumansState {
+            profiles: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0)))),
+            total_hours_minted: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))), E8s::zero()).expect("Unable to create total hours minted cell"),
+            total_storypoints_minted: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))), E8s::zero()).expect("Unable to create total storypoints minted cell"),
+            inited: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))), false).expect("Unable to create the inited flag cell"),
+        }
+    );
 }
 
 fn with_state<R, F: FnOnce(&HumansState) -> R>(f: F) -> R {
@@ -1,24 +1,32 @@
-use std::cell::RefCell;
+use std::{cell::RefCell, time::Duration};
 
-use ic_cdk::{
-    api::time,
-    caller, export_candid, post_upgrade, pre_upgrade, query,
-    storage::{stable_restore, stable_save},
-    update,
+use ic_cdk::{api::time, caller, export_candid, init, post_upgrade, query, update};
+use ic_cdk_timers::set_timer;
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager},
+    Cell, DefaultMemoryImpl, StableBTreeMap,
 };
 use shared::{
     liquid_democracy::{
         api::{
+            CreateDecisionTopicRequest, CreateDecisionTopicResponse, DeprecateDecisionTopicRequest,
+            DeprecateDecisionTopicResponse, EditDecisionTopicRequest, EditDecisionTopicResponse,
             FollowRequest, FollowResponse, GetDecisionTopicsRequest, GetDecisionTopicsResponse,
             GetFolloweesOfRequest, GetFolloweesOfResponse, GetFollowersOfRequest,
             GetFollowersOfResponse, GetLiquidDemocracyProofRequest,
             GetLiquidDemocracyProofResponse,
         },
-        state::LiquidDemocracyState,
+        state::{LegacyLiquidDemocracyState, LiquidDemocracyState},
     },
+    stable_memory::restore_legacy_state,
     Guard,
 };
 
+#[init]
+fn init_hook() {
+    with_state_mut(|s| s.init());
+}
+
 #[update]
 #[allow(non_snake_case)]
 fn liquid_democracy__follow(mut req: FollowRequest) -> FollowResponse {
@@ -65,6 +73,48 @@ fn liquid_democracy__get_decision_topics(
     })
 }
 
+#[update]
+#[allow(non_snake_case)]
+fn liquid_democracy__create_decision_topic(
+    mut req: CreateDecisionTopicRequest,
+) -> CreateDecisionTopicResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to create decision topic");
+
+        s.create_decision_topic(req)
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn liquid_democracy__edit_decision_topic(
+    mut req: EditDecisionTopicRequest,
+) -> EditDecisionTopicResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to edit decision topic");
+
+        s.edit_decision_topic(req)
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn liquid_democracy__deprecate_decision_topic(
+    mut req: DeprecateDecisionTopicRequest,
+) -> DeprecateDecisionTopicResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to deprecate decision topic");
+
+        let response = s.deprecate_decision_topic(req, time());
+        start_migration_timer();
+
+        response
+    })
+}
+
 #[update]
 #[allow(non_snake_case)]
 fn liquid_democracy__get_liquid_democracy_proof(
@@ -78,20 +128,44 @@ fn liquid_democracy__get_liquid_democracy_proof(
     })
 }
 
-#[pre_upgrade]
-fn pre_upgrade_hook() {
-    with_state(|s| stable_save((s,)).expect("Unable to stable save"));
-}
-
 #[post_upgrade]
 fn post_upgrade_hook() {
-    let (state,): (LiquidDemocracyState,) = stable_restore().expect("Unable to stable restore");
+    if let Some(legacy_state) = restore_legacy_state::<LegacyLiquidDemocracyState>() {
+        with_state_mut(|s| s.migrate_legacy(legacy_state));
+    }
+
+    with_state_mut(|s| s.fix_decision_topic_id_counter());
+
+    start_migration_timer();
+}
+
+fn start_migration_timer() {
+    set_timer(Duration::from_millis(0), run_migration_round);
+}
+
+fn run_migration_round() {
+    // one round is 100 entries (to not hit the instruction limit)
+    let should_reschedule_immediately = with_state_mut(|s| s.migrate_delegations(100));
 
-    with_state_mut(|s| *s = state);
+    // the state knows where to continue from
+    if should_reschedule_immediately {
+        start_migration_timer();
+    }
 }
 
 thread_local! {
-    static STATE: RefCell<LiquidDemocracyState> = RefCell::new(LiquidDemocracyState::new());
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    static STATE: RefCell<LiquidDemocracyState> = RefCell::new(
+        LiquidDemocracyState {
+            decision_topic_id_counter: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))), 0).expect("Unable to create decision topic id counter cell"),
+            decision_topics: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1)))),
+            followees_of: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2)))),
+            followers_of: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3)))),
+            topic_migrations: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4)))),
+        }
+    );
 }
 
 fn with_state<R, F: FnOnce(&LiquidDemocracyState) -> R>(f: F) -> R {
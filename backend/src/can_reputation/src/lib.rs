@@ -1,21 +1,28 @@
 use ic_cdk::api::time;
 use ic_cdk::{caller, export_candid, init, post_upgrade, query, update};
-use ic_cdk_timers::set_timer;
+use ic_cdk_timers::{clear_timer, set_timer, TimerId};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
 use ic_stable_structures::{Cell, DefaultMemoryImpl, StableBTreeMap};
 use shared::e8s::E8s;
 use shared::reputation::api::{
-    GetBalanceRequest, GetBalanceResponse, GetRepProofRequest, GetRepProofResponse,
-    GetTotalSupplyRequest, GetTotalSupplyResponse, MintRepRequest, MintRepResponse,
+    CreateSnapshotRequest, CreateSnapshotResponse, GetBalanceRequest, GetBalanceResponse,
+    GetRepProofRequest, GetRepProofResponse, GetReputationHistoryRequest,
+    GetReputationHistoryResponse, GetTotalSupplyRequest, GetTotalSupplyResponse, MintRepRequest,
+    MintRepResponse, ProjectDecayRequest, ProjectDecayResponse, ReleaseSnapshotsRequest,
+    ReleaseSnapshotsResponse, SetDecayParamsRequest, SetDecayParamsResponse, SlashRepRequest,
+    SlashRepResponse,
 };
 use shared::reputation::state::ReputationState;
-use shared::votings::types::ONE_MONTH_NS;
+use shared::reputation::types::DecayParams;
 use shared::Guard;
 use std::cell::RefCell;
 
 use std::time::Duration;
 
 thread_local! {
+    // the pending decay round, so it can be rescheduled when the params change
+    static DECAY_TIMER: RefCell<Option<TimerId>> = const { RefCell::new(None) };
+
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
 
@@ -27,6 +34,12 @@ thread_local! {
             total_supply: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))), E8s::zero()).expect("Unable to create total supply cell"),
             decay_start_key: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))), None).expect("Unable to create decay start key cell"),
             initialized: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))), false).expect("Unable to store the initialized flag"),
+            quadratic_total_supply: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))), E8s::zero()).expect("Unable to create quadratic total supply cell"),
+            snapshots: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5)))),
+            snapshot_balances: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6)))),
+            snapshot_id_generator: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))), 0).expect("Unable to create snapshot id generator cell"),
+            history: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8)))),
+            decay_params: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))), DecayParams::default()).expect("Unable to create decay params cell"),
         }
     )
 }
@@ -42,6 +55,55 @@ fn reputation__mint(mut req: MintRepRequest) -> MintRepResponse {
     })
 }
 
+#[update]
+#[allow(non_snake_case)]
+fn reputation__slash(mut req: SlashRepRequest) -> SlashRepResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to slash rep");
+
+        s.slash(req, time())
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn reputation__get_history(mut req: GetReputationHistoryRequest) -> GetReputationHistoryResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get rep history");
+
+        s.get_history(req)
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn reputation__set_decay_params(mut req: SetDecayParamsRequest) -> SetDecayParamsResponse {
+    let response = with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to set decay params");
+
+        s.set_decay_params(req)
+    });
+
+    // the new period should apply right away, not after the already scheduled round
+    restart_decay_timer();
+
+    response
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn reputation__project_decay(mut req: ProjectDecayRequest) -> ProjectDecayResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to project decay");
+
+        s.project_decay(req)
+    })
+}
+
 #[query]
 #[allow(non_snake_case)]
 fn reputation__get_balance(mut req: GetBalanceRequest) -> GetBalanceResponse {
@@ -75,6 +137,28 @@ fn reputation__get_reputation_proof(mut req: GetRepProofRequest) -> GetRepProofR
     })
 }
 
+#[update]
+#[allow(non_snake_case)]
+fn reputation__create_snapshot(mut req: CreateSnapshotRequest) -> CreateSnapshotResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to create a snapshot");
+
+        s.create_snapshot(req, time())
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn reputation__release_snapshots(mut req: ReleaseSnapshotsRequest) -> ReleaseSnapshotsResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to release snapshots");
+
+        s.release_snapshots(req)
+    })
+}
+
 #[update]
 #[allow(non_snake_case)]
 fn reputation__init_once() {
@@ -83,12 +167,40 @@ fn reputation__init_once() {
 
 #[init]
 fn init_hook() {
-    set_timer(Duration::from_nanos(ONE_MONTH_NS), run_decay_round);
+    start_decay_timer();
 }
 
 #[post_upgrade]
 fn post_upgrade_hook() {
-    set_timer(Duration::from_nanos(ONE_MONTH_NS), run_decay_round);
+    with_state_mut(|s| s.recalculate_quadratic_total_supply());
+
+    start_decay_timer();
+}
+
+// the period is read every time, so governance can change it without an upgrade
+fn start_decay_timer() {
+    let period_ns = with_state(|s| s.decay_params.get().period_ns);
+
+    schedule_decay_round(Duration::from_nanos(period_ns));
+}
+
+fn restart_decay_timer() {
+    if let Some(timer_id) = DECAY_TIMER.with(|t| t.borrow_mut().take()) {
+        clear_timer(timer_id);
+    }
+
+    // an unfinished decay continues from where it stopped
+    if with_state(|s| s.decay_start_key.get().is_some()) {
+        schedule_decay_round(Duration::from_millis(0));
+    } else {
+        start_decay_timer();
+    }
+}
+
+fn schedule_decay_round(delay: Duration) {
+    let timer_id = set_timer(delay, run_decay_round);
+
+    DECAY_TIMER.with(|t| *t.borrow_mut() = Some(timer_id));
 }
 
 fn run_decay_round() {
@@ -97,10 +209,10 @@ fn run_decay_round() {
 
     if should_reschedule_immediately {
         // reschedule immediately, if after the round there are still unprocessed entries (the state knows where to continue from)
-        set_timer(Duration::from_millis(0), run_decay_round);
+        schedule_decay_round(Duration::from_millis(0));
     } else {
-        // reschedule next week, if the decay is complete
-        set_timer(Duration::from_nanos(ONE_MONTH_NS), run_decay_round);
+        // reschedule next period, if the decay is complete
+        start_decay_timer();
     }
 }
 
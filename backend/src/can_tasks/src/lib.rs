@@ -10,6 +10,11 @@ use ic_cdk::{
 use serde::Deserialize;
 use shared::{
     humans::{api::MintRewardsRequest, client::HumansCanisterClient},
+    liquid_democracy::{
+        api::GetDecisionTopicsRequest,
+        client::LiquidDemocracyCanisterClient,
+        types::{assert_decision_topics_are_active, DecisionTopic},
+    },
     proof::{last_reputation_reliant_action_at, start_cleanup_interval_for_rep_reliant_actions},
     reputation::{api::MintRepRequest, client::ReputationCanisterClient},
     task_archive::api::{
@@ -60,7 +65,14 @@ fn post_upgrade_hook() {
 
 #[update]
 #[allow(non_snake_case)]
-fn tasks__create_task(mut req: CreateTaskRequest) -> CreateTaskResponse {
+async fn tasks__create_task(mut req: CreateTaskRequest) -> CreateTaskResponse {
+    let topics = fetch_decision_topics()
+        .await
+        .expect("Unable to create task");
+
+    assert_decision_topics_are_active(&topics, &req.decision_topics)
+        .expect("Unable to create task");
+
     with_state_mut(|s| {
         req.validate_and_escape(s, caller(), time())
             .expect("Unable to create task");
@@ -71,7 +83,29 @@ fn tasks__create_task(mut req: CreateTaskRequest) -> CreateTaskResponse {
 
 #[update]
 #[allow(non_snake_case)]
-fn tasks__edit_task(mut req: EditTaskRequest) -> EditTaskResponse {
+async fn tasks__edit_task(mut req: EditTaskRequest) -> EditTaskResponse {
+    // deprecated topics, which are already attached to the task, can stay there
+    let added_topics: Vec<_> = with_state(|s| {
+        let current = s
+            .tasks
+            .get(&req.id)
+            .map(|task| task.decision_topics.clone())
+            .unwrap_or_default();
+
+        req.new_decision_topics_opt
+            .iter()
+            .flatten()
+            .filter(|it| !current.contains(it))
+            .copied()
+            .collect()
+    });
+
+    if !added_topics.is_empty() {
+        let topics = fetch_decision_topics().await.expect("Unable to edit task");
+
+        assert_decision_topics_are_active(&topics, &added_topics).expect("Unable to edit task");
+    }
+
     with_state_mut(|s| {
         req.validate_and_escape(s, caller(), time())
             .expect("Unable to edit task");
@@ -162,9 +196,7 @@ async fn tasks__evaluate_task(mut req: EvaluateRequest) -> EvaluateResponse {
     let mint_rep_req = MintRepRequest {
         entries: rewards
             .iter()
-            .map(|it| it.as_reputation_mint_entry())
-            .filter(|it| it.is_some())
-            .map(|it| it.unwrap())
+            .filter_map(|it| it.as_reputation_mint_entry())
             .collect(),
     };
 
@@ -294,6 +326,17 @@ fn start_archiving_timer() {
     });
 }
 
+async fn fetch_decision_topics() -> Result<Vec<DecisionTopic>, String> {
+    let liquid_democracy_canister =
+        LiquidDemocracyCanisterClient::new(ENV_VARS.liquid_democracy_canister_id);
+
+    liquid_democracy_canister
+        .liquid_democracy__get_decision_topics(GetDecisionTopicsRequest {})
+        .await
+        .map(|it| it.entries)
+        .map_err(|(c, m)| format!("Unable to fetch decision topics - [{:?}]: {}", c, m))
+}
+
 thread_local! {
     static TASKS_STATE: RefCell<TasksState> = RefCell::new(TasksState::new(Principal::management_canister()));
 }
@@ -1,71 +1,189 @@
 use std::{cell::RefCell, time::Duration, vec};
 
 use ic_cdk::{
-    api::time,
-    caller, export_candid, post_upgrade, pre_upgrade, query, spawn,
-    storage::{stable_restore, stable_save},
-    update,
+    api::{
+        call::ManualReply,
+        management_canister::main::{
+            clear_chunk_store, install_chunked_code, install_code, upload_chunk,
+            CanisterInstallMode, ClearChunkStoreArgument, InstallChunkedCodeArgument,
+            InstallCodeArgument, UploadChunkArgument,
+        },
+        time,
+    },
+    caller, export_candid, init, post_upgrade, query, spawn, update,
+};
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager},
+    Cell, DefaultMemoryImpl, StableBTreeMap,
 };
 use shared::{
-    liquid_democracy::{state::GENERAL_TOPIC_ID, types::DecisionTopicId},
+    e8s::E8s,
+    humans::{
+        api::{MintRewardsRequest, RefundRewardsRequest, SpendRewardsRequest},
+        client::HumansCanisterClient,
+    },
+    liquid_democracy::{
+        api::GetDecisionTopicsRequest,
+        client::LiquidDemocracyCanisterClient,
+        state::GENERAL_TOPIC_ID,
+        types::{assert_decision_topics_are_active, DecisionTopic, DecisionTopicId},
+    },
     proof::{last_reputation_reliant_action_at, start_cleanup_interval_for_rep_reliant_actions},
+    reputation::{
+        api::{CreateSnapshotRequest, ReleaseSnapshotsRequest},
+        client::ReputationCanisterClient,
+    },
+    stable_memory::restore_legacy_state,
     tasks::{
         api::{FinishEditTaskRequest, FinishSolveRequest, GetTasksByIdRequest},
         client::TasksCanisterClient,
+        types::RewardEntry,
     },
     votings::{
         api::{
-            CastVoteRequest, CastVoteResponse, GetActionableVotingsRequest,
-            GetActionableVotingsResponse, GetVotingEventsRequest, GetVotingEventsResponse,
-            GetVotingsRequest, GetVotingsResponse, StartVotingRequest, StartVotingResponse,
+            CancelQueuedVotingRequest, CancelQueuedVotingResponse, CastSpamVoteRequest,
+            CastSpamVoteResponse, CastVoteRequest, CastVoteResponse,
+            CreateConvictionProposalRequest, CreateConvictionProposalResponse,
+            DeleteStagedWasmRequest, DeleteStagedWasmResponse, ExecuteBatchRequest,
+            ExecuteBatchResponse, ExecuteUpgradeRequest, ExecuteUpgradeResponse,
+            GetActionableVotingsRequest, GetActionableVotingsResponse, GetArchivedMotionsRequest,
+            GetArchivedMotionsResponse, GetArchivedVotingsRequest, GetArchivedVotingsResponse,
+            GetConvictionParamsRequest, GetConvictionParamsResponse, GetConvictionProposalsRequest,
+            GetConvictionProposalsResponse, GetDelegationBreakdownRequest,
+            GetDelegationBreakdownResponse, GetGenericCallAllowListRequest,
+            GetGenericCallAllowListResponse, GetGuardianCouncilRequest, GetGuardianCouncilResponse,
+            GetParticipationRequest, GetParticipationResponse, GetQueuedVotingsRequest,
+            GetQueuedVotingsResponse, GetStagedWasmsRequest, GetStagedWasmsResponse,
+            GetVotingEventsRequest, GetVotingEventsResponse, GetVotingParamsRequest,
+            GetVotingParamsResponse, GetVotingProjectionRequest, GetVotingProjectionResponse,
+            GetVotingsByTargetRequest, GetVotingsByTargetResponse, GetVotingsRequest,
+            GetVotingsResponse, GuardianVetoRequest, GuardianVetoResponse,
+            RecordParticipationRequest, RecordParticipationResponse, RetryExecutionRequest,
+            RetryExecutionResponse, RevealVoteRequest, RevealVoteResponse,
+            SetConvictionParamsRequest, SetConvictionParamsResponse, SetConvictionSupportRequest,
+            SetConvictionSupportResponse, SetGuardianCouncilRequest, SetGuardianCouncilResponse,
+            SetParticipationParamsRequest, SetParticipationParamsResponse, SetVotingParamsRequest,
+            SetVotingParamsResponse, StartVotingRequest, StartVotingResponse,
+            UpdateGenericCallAllowListRequest, UpdateGenericCallAllowListResponse,
+            UploadWasmChunkRequest, UploadWasmChunkResponse, VerifyStagedWasmRequest,
+            VerifyStagedWasmResponse,
+        },
+        state::{LegacyVotingsState, VotingsState},
+        types::{
+            CallToExecute, ClosingParticipationEpoch, ConvictionParams, GuardianCouncil,
+            ParticipationEpoch, ParticipationParams, PreparedUpgrade, VotingEvent, VotingEventV1,
+            VotingId, VotingKind, VotingTimer, ONE_HOUR_NS, ONE_MINUTE_NS,
         },
-        state::VotingsState,
-        types::{CallToExecute, VotingEvent, VotingEventV1, VotingId, VotingKind, VotingTimer},
     },
     Guard, TimestampNs, ENV_VARS,
 };
 
-#[pre_upgrade]
-fn pre_upgrade_hook() {
-    with_state(|s| stable_save((s,)).expect("Unable to stable save"));
+#[init]
+fn init_hook() {
+    with_state_mut(|s| s.init_participation_epoch(time()));
+
+    start_conviction_timer();
+    start_deposit_refund_interval();
+    start_participation_interval();
+    start_snapshot_release_interval();
 }
 
 #[post_upgrade]
 fn post_upgrade_hook() {
-    let (votings_state,): (VotingsState,) = stable_restore().expect("Unable to stable restore");
+    if let Some(legacy_state) = restore_legacy_state::<LegacyVotingsState>() {
+        with_state_mut(|s| s.migrate_legacy(legacy_state));
+    }
 
-    with_state_mut(|s| *s = votings_state);
+    // calls in flight were dropped by the upgrade, so their votings are scheduled for a retry
+    with_state_mut(|s| s.resume_interrupted_executions(time()));
 
-    let timers = with_state(|s| s.timers.values().copied().collect::<Vec<_>>());
+    let timers = with_state(|s| s.timers.iter().map(|(_, it)| it).collect::<Vec<_>>());
     let now = time();
 
     for timer in timers {
         start_voting_timer(timer, now);
     }
 
+    with_state_mut(|s| s.init_participation_epoch(time()));
+
     start_cleanup_interval_for_rep_reliant_actions();
+    start_conviction_timer();
+    start_deposit_refund_interval();
+    start_participation_interval();
+    start_snapshot_release_interval();
 }
 
-#[update]
+// rejects instead of trapping, so the deposit refund and the snapshot release are kept
+#[update(manual_reply = true)]
 #[allow(non_snake_case)]
-async fn votings__start_voting(mut req: StartVotingRequest) -> StartVotingResponse {
+async fn votings__start_voting(mut req: StartVotingRequest) -> ManualReply<StartVotingResponse> {
     // validate the request
-    with_state(|s| {
+    let deposit = with_state(|s| {
         req.validate_and_escape(s, caller(), time())
             .expect("Unable to start a voting");
+
+        s.get_deposit_for(&req)
     });
 
-    // validate the entity a voting is trying to mutate
-    let topics = validate_voting_related_entity(&mut req.kind)
+    // lock the deposit of a non-team member, nothing is held elsewhere yet, so it is fine to trap here
+    if let Some(storypoints) = &deposit {
+        let humans_canister = HumansCanisterClient::new(ENV_VARS.humans_canister_id);
+
+        humans_canister
+            .humans__spend_rewards(SpendRewardsRequest {
+                spender: caller(),
+                hours: E8s::zero(),
+                storypoints: storypoints.clone(),
+            })
+            .await
+            .expect("Unable to lock the deposit");
+    }
+
+    // every vote is weighed against the reputation as it was at the start of the voting
+    let reputation_canister = ReputationCanisterClient::new(ENV_VARS.reputation_canister_id);
+    let snapshot = match reputation_canister
+        .reputation__create_snapshot(CreateSnapshotRequest {})
         .await
-        .expect("Invalid voting");
+    {
+        Ok(it) => it.snapshot,
+        Err((code, msg)) => {
+            with_state_mut(|s| {
+                if let Some(storypoints) = deposit {
+                    s.refund_failed_start(caller(), storypoints);
+                }
+            });
+
+            return ManualReply::reject(format!(
+                "Unable to snapshot reputation: [{:?}] {}",
+                code, msg
+            ));
+        }
+    };
+
+    // the entity a voting is trying to mutate is validated last, since it may change it (e.g. finish a task edit)
+    let topics = match validate_voting_entities(&mut req.kind).await {
+        Ok(it) => it,
+        Err(e) => {
+            // rejecting keeps the state, so the locked deposit is given back by the refund interval
+            with_state_mut(|s| {
+                if let Some(storypoints) = deposit {
+                    s.refund_failed_start(caller(), storypoints);
+                }
+
+                s.release_snapshot(snapshot.id, 1);
+            });
+
+            return ManualReply::reject(format!("Invalid voting: {}", e));
+        }
+    };
 
     // start a voting
-    let (response, timer) = with_state_mut(|s| s.start_voting(req, topics, caller(), time()));
+    let (response, timer) =
+        with_state_mut(|s| s.start_voting(req, topics, deposit, snapshot, caller(), time()));
 
     start_voting_timer(timer, time());
 
-    response
+    ManualReply::one(response)
 }
 
 #[update]
@@ -74,22 +192,257 @@ async fn votings__cast_vote(mut req: CastVoteRequest) -> CastVoteResponse {
     let id = req.id;
 
     // validate the request
-    let (resp, call_to_exec_opt) = with_state_mut(|s| {
+    let (resp, call_to_exec_opt, timer_opt) = with_state_mut(|s| {
         req.validate_and_escape(s, caller(), time())
             .expect("Unable to cast a vote");
 
-        s.cast_vote(req, caller())
+        s.cast_vote(req, caller(), time())
     });
 
     if !resp.decision_made {
         return resp;
     }
 
+    // the decision is queued, if the kind has a timelock
+    if let Some(timer) = timer_opt {
+        start_voting_timer(timer, time());
+    }
+
     process_voting_result(id, call_to_exec_opt);
 
     resp
 }
 
+#[update]
+#[allow(non_snake_case)]
+fn votings__reveal_vote(mut req: RevealVoteRequest) -> RevealVoteResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to reveal a vote");
+
+        s.reveal_vote(req, caller())
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__retry_execution(mut req: RetryExecutionRequest) -> RetryExecutionResponse {
+    let call_to_exec_opt = with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to retry the execution");
+
+        s.start_retry(req.id, true, time())
+    });
+
+    process_voting_result(req.id, call_to_exec_opt);
+
+    RetryExecutionResponse {}
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__cancel_queued_voting(mut req: CancelQueuedVotingRequest) -> CancelQueuedVotingResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to cancel a queued voting");
+
+        s.cancel_queued_voting(req, time())
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__cast_spam_vote(mut req: CastSpamVoteRequest) -> CastSpamVoteResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to cast a spam vote");
+
+        s.cast_spam_vote(req)
+    })
+}
+
+// rejects instead of trapping, so the outcomes of the executed steps are kept
+#[update(manual_reply = true)]
+#[allow(non_snake_case)]
+async fn votings__execute_batch(mut req: ExecuteBatchRequest) -> ManualReply<ExecuteBatchResponse> {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to execute a batch");
+    });
+
+    loop {
+        let step_opt = with_state(|s| s.next_batch_step(req.id));
+
+        let (step, call_opt) = match step_opt {
+            Some(it) => it,
+            None => return ManualReply::one(ExecuteBatchResponse {}),
+        };
+
+        let error = match call_opt {
+            Some(call) => call.execute().await.err(),
+            None => Some("Unable to generate the call".to_string()),
+        };
+
+        if let Err(e) = with_state_mut(|s| s.record_batch_step(req.id, step, error, time())) {
+            return ManualReply::reject(e);
+        }
+    }
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__upload_wasm_chunk(mut req: UploadWasmChunkRequest) -> UploadWasmChunkResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to upload a wasm chunk");
+
+        s.upload_wasm_chunk(req, caller(), time())
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__verify_staged_wasm(mut req: VerifyStagedWasmRequest) -> VerifyStagedWasmResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to verify a staged wasm");
+
+        s.verify_staged_wasm(req)
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__delete_staged_wasm(mut req: DeleteStagedWasmRequest) -> DeleteStagedWasmResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to delete a staged wasm");
+
+        s.delete_staged_wasm(req)
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_staged_wasms(mut req: GetStagedWasmsRequest) -> GetStagedWasmsResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get staged wasms");
+
+        s.get_staged_wasms(req)
+    })
+}
+
+// rejects instead of trapping, so the error is recorded as an execution failure and could be retried
+#[update(manual_reply = true)]
+#[allow(non_snake_case)]
+async fn votings__execute_upgrade(
+    mut req: ExecuteUpgradeRequest,
+) -> ManualReply<ExecuteUpgradeResponse> {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to execute an upgrade");
+    });
+
+    let upgrade = match with_state(|s| s.prepare_upgrade(req.id)) {
+        Ok(it) => it,
+        Err(e) => return ManualReply::reject(e),
+    };
+
+    let (canister_id, wasm_hash) = (upgrade.canister_id, upgrade.wasm_hash);
+
+    if let Err(e) = upgrade_canister(upgrade).await {
+        return ManualReply::reject(e);
+    }
+
+    with_state_mut(|s| s.finish_upgrade(req.id, canister_id, wasm_hash));
+
+    ManualReply::one(ExecuteUpgradeResponse {})
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__guardian_veto(mut req: GuardianVetoRequest) -> GuardianVetoResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to veto a voting");
+
+        s.guardian_veto(req, caller(), time())
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__set_guardian_council(mut req: SetGuardianCouncilRequest) -> SetGuardianCouncilResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to set guardian council");
+
+        s.set_guardian_council(req)
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_guardian_council(mut req: GetGuardianCouncilRequest) -> GetGuardianCouncilResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get guardian council");
+
+        s.get_guardian_council(req)
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_queued_votings(mut req: GetQueuedVotingsRequest) -> GetQueuedVotingsResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get queued votings");
+
+        s.get_queued_votings(req, caller())
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_votings_by_target(
+    mut req: GetVotingsByTargetRequest,
+) -> GetVotingsByTargetResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get votings by target");
+
+        s.get_votings_by_target(req, caller())
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_delegation_breakdown(
+    mut req: GetDelegationBreakdownRequest,
+) -> GetDelegationBreakdownResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get delegation breakdown");
+
+        s.get_delegation_breakdown(req)
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_voting_projection(
+    mut req: GetVotingProjectionRequest,
+) -> GetVotingProjectionResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get voting projection");
+
+        s.get_voting_projection(req)
+    })
+}
+
 #[query]
 #[allow(non_snake_case)]
 fn votings__get_votings(mut req: GetVotingsRequest) -> GetVotingsResponse {
@@ -125,6 +478,178 @@ fn votings__get_actionable_votings(
     })
 }
 
+#[update]
+#[allow(non_snake_case)]
+fn votings__update_generic_call_allow_list(
+    mut req: UpdateGenericCallAllowListRequest,
+) -> UpdateGenericCallAllowListResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to update the generic call allow-list");
+
+        s.update_generic_call_allow_list(req)
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_generic_call_allow_list(
+    mut req: GetGenericCallAllowListRequest,
+) -> GetGenericCallAllowListResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get the generic call allow-list");
+
+        s.get_generic_call_allow_list(req)
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__set_voting_params(mut req: SetVotingParamsRequest) -> SetVotingParamsResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to set voting params");
+
+        s.set_voting_params(req)
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_voting_params(mut req: GetVotingParamsRequest) -> GetVotingParamsResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get voting params");
+
+        s.get_voting_params(req)
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_archived_motions(mut req: GetArchivedMotionsRequest) -> GetArchivedMotionsResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get archived motions");
+
+        s.get_archived_motions(req)
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__create_conviction_proposal(
+    mut req: CreateConvictionProposalRequest,
+) -> CreateConvictionProposalResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to create a conviction proposal");
+
+        s.create_conviction_proposal(req, caller(), time())
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__set_conviction_support(
+    mut req: SetConvictionSupportRequest,
+) -> SetConvictionSupportResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to set conviction support");
+
+        s.set_conviction_support(req, caller())
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_conviction_proposals(
+    mut req: GetConvictionProposalsRequest,
+) -> GetConvictionProposalsResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get conviction proposals");
+
+        s.get_conviction_proposals(req, caller())
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__set_conviction_params(
+    mut req: SetConvictionParamsRequest,
+) -> SetConvictionParamsResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to set conviction params");
+
+        s.set_conviction_params(req)
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__set_participation_params(
+    mut req: SetParticipationParamsRequest,
+) -> SetParticipationParamsResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to set participation params");
+
+        s.set_participation_params(req)
+    })
+}
+
+#[update]
+#[allow(non_snake_case)]
+fn votings__record_participation(
+    mut req: RecordParticipationRequest,
+) -> RecordParticipationResponse {
+    with_state_mut(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to record participation");
+
+        s.record_participation(req)
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_participation(mut req: GetParticipationRequest) -> GetParticipationResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get participation");
+
+        s.get_participation(req, caller())
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_conviction_params(
+    mut req: GetConvictionParamsRequest,
+) -> GetConvictionParamsResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get conviction params");
+
+        s.get_conviction_params(req)
+    })
+}
+
+#[query]
+#[allow(non_snake_case)]
+fn votings__get_archived_votings(mut req: GetArchivedVotingsRequest) -> GetArchivedVotingsResponse {
+    with_state(|s| {
+        req.validate_and_escape(s, caller(), time())
+            .expect("Unable to get archived votings");
+
+        s.get_archived_votings(req)
+    })
+}
+
 #[query]
 #[allow(non_snake_case)]
 fn _votings__get_my_create_voting_timestamp() -> u64 {
@@ -143,17 +668,64 @@ fn start_voting_timer(timer: VotingTimer, now: TimestampNs) {
                 with_state_mut(|s| s.save_timer(voting_id, timer));
 
                 ic_cdk_timers::set_timer(Duration::from_nanos(timestamp - now), move || {
+                    // removed before resolving, since resolving may start another timer for the same voting
+                    with_state_mut(|s| s.remove_timer(&voting_id, &timer));
                     resolve_voting_on_timer(voting_id);
-                    with_state_mut(|s| s.remove_timer(&voting_id));
                 });
             }
         }
+        VotingTimer::RetryExecution {
+            voting_id,
+            timestamp,
+        } => {
+            with_state_mut(|s| s.save_timer(voting_id, timer));
+
+            ic_cdk_timers::set_timer(
+                Duration::from_nanos(timestamp.saturating_sub(now)),
+                move || {
+                    with_state_mut(|s| s.remove_timer(&voting_id, &timer));
+
+                    let call_to_exec_opt =
+                        with_state_mut(|s| s.start_retry(voting_id, false, time()));
+                    process_voting_result(voting_id, call_to_exec_opt);
+                },
+            );
+        }
+        VotingTimer::ExecuteQueued {
+            voting_id,
+            timestamp,
+        } => {
+            with_state_mut(|s| s.save_timer(voting_id, timer));
+
+            ic_cdk_timers::set_timer(
+                Duration::from_nanos(timestamp.saturating_sub(now)),
+                move || {
+                    with_state_mut(|s| s.remove_timer(&voting_id, &timer));
+
+                    let (call_to_exec_opt, next_timer_opt) =
+                        with_state_mut(|s| s.execute_queued(voting_id, time()));
+
+                    // postponed while a veto voting against the decision is open
+                    if let Some(next_timer) = next_timer_opt {
+                        start_voting_timer(next_timer, time());
+                    }
+
+                    process_voting_result(voting_id, call_to_exec_opt);
+                },
+            );
+        }
     }
 }
 
 fn resolve_voting_on_timer(voting_id: VotingId) {
     // resolve on timer
-    let call_to_exec_opt = with_state_mut(|s| s.resolve_on_timer(voting_id));
+    let (call_to_exec_opt, next_timer_opt) =
+        with_state_mut(|s| s.resolve_on_timer(voting_id, time()));
+
+    // secret votings continue with the reveal phase, timelocked ones are queued
+    if let Some(next_timer) = next_timer_opt {
+        start_voting_timer(next_timer, time());
+    }
 
     // if the quorum is reached
     process_voting_result(voting_id, call_to_exec_opt)
@@ -163,21 +735,225 @@ fn process_voting_result(voting_id: VotingId, call_to_exec_opt: Option<CallToExe
     if let Some(call_to_exec) = call_to_exec_opt {
         spawn(async move {
             // execute the call
-            let event = match call_to_exec.execute().await {
-                Ok(_) => VotingEvent::V0001(VotingEventV1::VotingSuccess { voting_id }),
-                Err(e) => VotingEvent::V0001(VotingEventV1::VotingFail {
-                    voting_id,
-                    reason: e,
-                }),
-            };
-
-            // save the produced events and delete the voting
-            with_state_mut(|s| {
-                s.save_event(event);
-                s.delete_voting(voting_id);
+            match call_to_exec.execute().await {
+                Ok(_) => {
+                    let event = VotingEvent::V0001(VotingEventV1::VotingSuccess { voting_id });
+
+                    // save the produced event and archive the voting
+                    with_state_mut(|s| s.finish_voting(voting_id, event, time()));
+                }
+                Err(e) => {
+                    // the decision is kept, so the call could be retried later
+                    let timer_opt = with_state_mut(|s| s.fail_execution(voting_id, e, time()));
+
+                    if let Some(timer) = timer_opt {
+                        start_voting_timer(timer, time());
+                    }
+                }
+            }
+        });
+    }
+}
+
+// the period is read every time, so governance can change it without an upgrade
+fn start_conviction_timer() {
+    let period_ns = with_state(|s| s.conviction_params.get().period_ns);
+
+    ic_cdk_timers::set_timer(Duration::from_nanos(period_ns), || {
+        run_conviction_round();
+        start_conviction_timer();
+    });
+}
+
+fn run_conviction_round() {
+    let calls = with_state_mut(|s| s.run_conviction_round());
+
+    for (proposal_id, call_to_exec) in calls {
+        spawn(async move {
+            let result = call_to_exec.execute().await;
+
+            with_state_mut(|s| s.finish_conviction_proposal(proposal_id, result));
+        });
+    }
+}
+
+// small wasms are installed directly, bigger ones go through the chunk store of the target canister
+async fn upgrade_canister(upgrade: PreparedUpgrade) -> Result<(), String> {
+    let PreparedUpgrade {
+        canister_id,
+        wasm_hash,
+        mut chunks,
+        arg,
+    } = upgrade;
+    let mode = CanisterInstallMode::Upgrade(None);
+
+    if chunks.len() == 1 {
+        return install_code(InstallCodeArgument {
+            mode,
+            canister_id,
+            wasm_module: chunks.remove(0),
+            arg,
+        })
+        .await
+        .map_err(|(c, m)| format!("Unable to install the code - [{:?}]: {}", c, m));
+    }
+
+    // leftovers of a previous failed attempt would not match the hash
+    clear_chunk_store(ClearChunkStoreArgument { canister_id })
+        .await
+        .map_err(|(c, m)| format!("Unable to clear the chunk store - [{:?}]: {}", c, m))?;
+
+    let mut chunk_hashes_list = Vec::new();
+    for chunk in chunks {
+        let (chunk_hash,) = upload_chunk(UploadChunkArgument { canister_id, chunk })
+            .await
+            .map_err(|(c, m)| format!("Unable to upload a chunk - [{:?}]: {}", c, m))?;
+
+        chunk_hashes_list.push(chunk_hash);
+    }
+
+    install_chunked_code(InstallChunkedCodeArgument {
+        mode,
+        target_canister: canister_id,
+        store_canister: None,
+        chunk_hashes_list,
+        wasm_module_hash: wasm_hash.to_vec(),
+        arg,
+    })
+    .await
+    .map_err(|(c, m)| format!("Unable to install the code - [{:?}]: {}", c, m))?;
+
+    // the upgrade itself has already succeeded at this point
+    let _ = clear_chunk_store(ClearChunkStoreArgument { canister_id }).await;
+
+    Ok(())
+}
+
+// failed refunds are put back and retried on the next tick
+fn start_deposit_refund_interval() {
+    ic_cdk_timers::set_timer_interval(Duration::from_nanos(ONE_MINUTE_NS), || {
+        let refunds = with_state_mut(|s| s.take_deposit_refunds());
+
+        for (id, refund) in refunds {
+            spawn(async move {
+                let humans_canister = HumansCanisterClient::new(ENV_VARS.humans_canister_id);
+                let result = humans_canister
+                    .humans__refund_rewards(RefundRewardsRequest {
+                        spender: refund.depositor,
+                        hours: E8s::zero(),
+                        storypoints: refund.storypoints.clone(),
+                    })
+                    .await;
+
+                if result.is_err() {
+                    with_state_mut(|s| s.deposit_refunds.insert(id, refund));
+                }
             });
+        }
+    });
+}
+
+// snapshots of finished votings are released, so the reputation canister stops tracking balance changes for them
+fn start_snapshot_release_interval() {
+    ic_cdk_timers::set_timer_interval(Duration::from_nanos(ONE_HOUR_NS), || {
+        let entries = with_state_mut(|s| s.take_snapshots_to_release());
+
+        if entries.is_empty() {
+            return;
+        }
+
+        spawn(async move {
+            let reputation_canister =
+                ReputationCanisterClient::new(ENV_VARS.reputation_canister_id);
+            let result = reputation_canister
+                .reputation__release_snapshots(ReleaseSnapshotsRequest {
+                    entries: entries.clone(),
+                })
+                .await;
+
+            // failed releases are put back and retried on the next tick
+            if result.is_err() {
+                with_state_mut(|s| {
+                    for (id, times) in entries {
+                        s.release_snapshot(id, times);
+                    }
+                });
+            }
         });
+    });
+}
+
+// failed mints are put back and retried on the next tick
+fn start_participation_interval() {
+    ic_cdk_timers::set_timer_interval(Duration::from_nanos(ONE_HOUR_NS), || {
+        run_participation_epoch_round();
+
+        let rewards = with_state_mut(|s| s.take_participation_rewards());
+
+        for chunk in rewards.chunks(100) {
+            let chunk = chunk.to_vec();
+
+            spawn(async move {
+                let humans_canister = HumansCanisterClient::new(ENV_VARS.humans_canister_id);
+                let result = humans_canister
+                    .humans__mint_rewards(MintRewardsRequest {
+                        rewards: chunk
+                            .iter()
+                            .map(|(participant, storypoints)| RewardEntry {
+                                solver: *participant,
+                                reward_hours: E8s::zero(),
+                                reward_storypoints: storypoints.clone(),
+                                want_rep: false,
+                            })
+                            .collect(),
+                    })
+                    .await;
+
+                if result.is_err() {
+                    with_state_mut(|s| {
+                        for (participant, storypoints) in chunk {
+                            s.add_participation_reward(participant, storypoints);
+                        }
+                    });
+                }
+            });
+        }
+    });
+}
+
+fn run_participation_epoch_round() {
+    // one round is 500 participants (to not hit the instruction limit)
+    let should_reschedule_immediately =
+        with_state_mut(|s| s.finish_participation_epoch(500, time()));
+
+    // the state knows where to continue from, the rewards of later rounds are minted on the next tick
+    if should_reschedule_immediately {
+        ic_cdk_timers::set_timer(Duration::from_millis(0), run_participation_epoch_round);
+    }
+}
+
+// batched actions are validated one by one and the batch covers all of their topics
+async fn validate_voting_entities(kind: &mut VotingKind) -> Result<Vec<DecisionTopicId>, String> {
+    let actions = match kind {
+        VotingKind::Batch {
+            description: _,
+            actions,
+            on_failure: _,
+        } => actions,
+        _ => return validate_voting_related_entity(kind).await,
+    };
+
+    let mut topics = Vec::new();
+
+    for action in actions.iter_mut() {
+        for topic in validate_voting_related_entity(action).await? {
+            if !topics.contains(&topic) {
+                topics.push(topic);
+            }
+        }
     }
+
+    Ok(topics)
 }
 
 async fn validate_voting_related_entity(
@@ -194,7 +970,7 @@ async fn validate_voting_related_entity(
             let task = response.task;
 
             if !task.can_approve_to_solve() {
-                return Err(format!("The task is in invalid state"));
+                return Err("The task is in invalid state".to_string());
             }
 
             Ok(task.decision_topics.clone().into_iter().collect())
@@ -210,10 +986,9 @@ async fn validate_voting_related_entity(
 
             let task = resp
                 .entries
-                .get(0)
-                .expect("Task not found")
-                .as_ref()
-                .expect("Task not found");
+                .first()
+                .and_then(|it| it.as_ref())
+                .ok_or(String::from("Task not found"))?;
 
             Ok(task.decision_topics.clone().into_iter().collect())
         }
@@ -227,7 +1002,7 @@ async fn validate_voting_related_entity(
             let task = response.task;
 
             if !task.can_evaluate() {
-                return Err(format!("The task is in invalid state"));
+                return Err("The task is in invalid state".to_string());
             }
 
             // setting the solutions to what's inside the actual task, instead of relying on what the user has provided
@@ -245,11 +1020,139 @@ async fn validate_voting_related_entity(
             into: _,
             new_rate: _,
         } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::GenericCall {
+            canister_id: _,
+            method_name: _,
+            args_raw: _,
+            description: _,
+        } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::TreasuryTransfer {
+            asset: _,
+            from_subaccount: _,
+            to: _,
+            amount: _,
+            memo: _,
+        } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::SetTreasuryCaps { caps: _ } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::SlashReputation {
+            target: _,
+            amount_or_fraction: _,
+            reason: _,
+        } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::SetDecayParams { params: _ } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::UpdateGenericCallAllowList { add: _, remove: _ } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::SetVotingParams { kind: _, params: _ } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::Motion {
+            title: _,
+            body: _,
+            topics,
+        } => Ok(topics.clone()),
+        VotingKind::MultiChoice {
+            title: _,
+            body: _,
+            topics,
+            options: _,
+            mode: _,
+        } => Ok(topics.clone()),
+        VotingKind::SetConvictionParams { params: _ } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::Veto {
+            voting_id: _,
+            reason: _,
+        } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::SetGuardianCouncil { council: _ } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::SetParticipationParams { params: _ } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::UpgradeCanister {
+            canister_id: _,
+            wasm_hash: _,
+            arg: _,
+        } => Ok(vec![GENERAL_TOPIC_ID]),
+        VotingKind::Batch {
+            description: _,
+            actions: _,
+            on_failure: _,
+        } => Err("Nested batches are not allowed".to_string()),
+        VotingKind::CreateDecisionTopic {
+            name,
+            description: _,
+        } => {
+            let topics = fetch_decision_topics().await?;
+
+            if topics
+                .iter()
+                .any(|it| it.name.to_lowercase() == name.to_lowercase())
+            {
+                return Err(format!("Decision topic {} already exists", name));
+            }
+
+            Ok(vec![GENERAL_TOPIC_ID])
+        }
+        VotingKind::EditDecisionTopic {
+            id,
+            new_name_opt: _,
+            new_description_opt: _,
+        } => {
+            let topics = fetch_decision_topics().await?;
+            assert_decision_topics_are_active(&topics, &[*id])?;
+
+            // those who follow someone on the edited topic should have a say
+            Ok(vec![GENERAL_TOPIC_ID, *id])
+        }
+        VotingKind::DeprecateDecisionTopic { id, successors } => {
+            let topics = fetch_decision_topics().await?;
+            assert_decision_topics_are_active(&topics, &[*id])?;
+            assert_decision_topics_are_active(&topics, successors)?;
+
+            let mut result = vec![GENERAL_TOPIC_ID, *id];
+            result.extend(successors.iter().filter(|it| **it != GENERAL_TOPIC_ID));
+
+            Ok(result)
+        }
     }
 }
 
+async fn fetch_decision_topics() -> Result<Vec<DecisionTopic>, String> {
+    let liquid_democracy_canister =
+        LiquidDemocracyCanisterClient::new(ENV_VARS.liquid_democracy_canister_id);
+
+    liquid_democracy_canister
+        .liquid_democracy__get_decision_topics(GetDecisionTopicsRequest {})
+        .await
+        .map(|it| it.entries)
+        .map_err(|(c, m)| format!("Unable to fetch decision topics - [{:?}]: {}", c, m))
+}
+
 thread_local! {
-    static VOTINGS_STATE: RefCell<VotingsState> = RefCell::default();
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    static VOTINGS_STATE: RefCell<VotingsState> = RefCell::new(
+        VotingsState {
+            votings: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0)))),
+            events: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1)))),
+            timers: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2)))),
+            generic_call_allow_list: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3)))),
+            voting_params: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4)))),
+            motions_archive: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5)))),
+            conviction_proposals: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6)))),
+            conviction_proposal_id_generator: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))), 0).expect("Unable to create conviction proposal id generator cell"),
+            conviction_params: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))), ConvictionParams::default()).expect("Unable to create conviction params cell"),
+            conviction_budget: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))), E8s::zero()).expect("Unable to create conviction budget cell"),
+            votings_archive: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10)))),
+            guardian_council: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))), GuardianCouncil::default()).expect("Unable to create guardian council cell"),
+            guardian_vetoes: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12)))),
+            deposit_refunds: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13)))),
+            voting_id_generator: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14))), 0).expect("Unable to create voting id generator cell"),
+            votings_by_target: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15)))),
+            participation_params: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16))), ParticipationParams::default()).expect("Unable to create participation params cell"),
+            participation_epoch: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17))), ParticipationEpoch::default()).expect("Unable to create participation epoch cell"),
+            participation: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18)))),
+            participation_rewards: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19)))),
+            staged_wasms: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20)))),
+            wasm_chunks: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21)))),
+            snapshots_to_release: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22)))),
+            closing_participation_epoch: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23))), ClosingParticipationEpoch::default()).expect("Unable to create closing participation epoch cell"),
+        }
+    );
 }
 
 fn with_state<R, F: FnOnce(&VotingsState) -> R>(f: F) -> R {
@@ -269,3 +1172,35 @@ fn with_state_mut<R, F: FnOnce(&mut VotingsState) -> R>(f: F) -> R {
 }
 
 export_candid!();
+
+#[cfg(test)]
+mod tests {
+    // without the flag the macro replies on its own and traps, since a ManualReply can't be encoded
+    #[test]
+    fn manual_replies_are_flagged() {
+        let src = include_str!("lib.rs");
+        let lines: Vec<_> = src.lines().collect();
+
+        for (idx, line) in lines.iter().enumerate() {
+            let is_signature = line.starts_with("async fn ")
+                || line.starts_with("fn ")
+                || line.starts_with(") -> ");
+
+            if !is_signature || !line.contains("-> ManualReply<") {
+                continue;
+            }
+
+            let attr = lines[..idx]
+                .iter()
+                .rev()
+                .find(|it| it.starts_with("#[update") || it.starts_with("#[query"))
+                .expect("No endpoint attribute");
+
+            assert!(
+                attr.contains("manual_reply = true"),
+                "Endpoint at line {} lacks manual_reply",
+                idx + 1
+            );
+        }
+    }
+}
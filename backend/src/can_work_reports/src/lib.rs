@@ -1,23 +1,24 @@
 use std::cell::RefCell;
 
-use ic_cdk::{
-    api::time,
-    caller, export_candid, post_upgrade, pre_upgrade, query,
-    storage::{stable_restore, stable_save},
-    trap, update,
+use ic_cdk::{api::time, caller, export_candid, post_upgrade, query, trap, update};
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager},
+    Cell, DefaultMemoryImpl, StableBTreeMap,
 };
 use shared::{
     e8s::E8s,
     humans::{api::MintRewardsRequest, client::HumansCanisterClient},
     reputation::{api::MintRepRequest, client::ReputationCanisterClient},
+    stable_memory::restore_legacy_state,
     tasks::types::RewardEntry,
+    votings::{api::RecordParticipationRequest, client::VotingsCanisterClient},
     work_reports::{
         api::{
             CreateWorkReportRequest, CreateWorkReportResponse, EvaluateWorkReportRequest,
             EvaluateWorkReportResponse, GetWorkReportIdsRequest, GetWorkReportIdsResponse,
             GetWorkReportsByIdRequest, GetWorkReportsByIdResponse,
         },
-        state::WorkReportState,
+        state::{LegacyWorkReportState, WorkReportState},
     },
     Guard, ENV_VARS,
 };
@@ -47,7 +48,15 @@ async fn work_reports__evaluate(mut req: EvaluateWorkReportRequest) -> EvaluateW
         return EvaluateWorkReportResponse { result: None };
     }
 
-    let res = res_opt.unwrap();
+    let (res, evaluators) = res_opt.unwrap();
+
+    // participation is only used for rewards, so it is fine to lose it if the call fails
+    let votings_canister = VotingsCanisterClient::new(ENV_VARS.votings_canister_id);
+    let _ = votings_canister
+        .votings__record_participation(RecordParticipationRequest {
+            participants: evaluators,
+        })
+        .await;
 
     // if was marked as spam - simply return
     if res.is_none() {
@@ -131,18 +140,23 @@ fn work_reports__get_by_id(mut req: GetWorkReportsByIdRequest) -> GetWorkReports
 }
 
 thread_local! {
-    static STATE: RefCell<WorkReportState> = RefCell::new(WorkReportState::default());
-}
-
-#[pre_upgrade]
-fn pre_upgrade_hook() {
-    STATE.with_borrow(|s| stable_save((s,)).expect("Unable to stable save"))
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+    static STATE: RefCell<WorkReportState> = RefCell::new(
+        WorkReportState {
+            work_report_id_generator: Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))), 0).expect("Unable to create work report id generator cell"),
+            work_reports: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1)))),
+            work_reports_local_archive: StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2)))),
+        }
+    );
 }
 
 #[post_upgrade]
 fn post_upgrade_hook() {
-    let (state,): (WorkReportState,) = stable_restore().expect("Unable to stable restore");
-    STATE.with_borrow_mut(|s| *s = state);
+    if let Some(legacy_state) = restore_legacy_state::<LegacyWorkReportState>() {
+        STATE.with_borrow_mut(|s| s.migrate_legacy(legacy_state));
+    }
 }
 
 export_candid!();
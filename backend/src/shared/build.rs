@@ -12,7 +12,7 @@ fn main() {
     println!("cargo:rerun-if-changed={}", env_file_path);
     let dest_path = "./src/env.rs";
 
-    let mut f = File::create(&dest_path).unwrap();
+    let mut f = File::create(dest_path).unwrap();
 
     // use the dotenv crate to get the .env values
     dotenv::from_filename(env_file_path).ok();
@@ -22,7 +22,7 @@ fn main() {
     for (key, value) in env::vars() {
         if key.starts_with("CAN_") {
             let line = format!(
-                "pub const {}: &'static str = \"{}\";\n",
+                "pub const {}: &str = \"{}\";\n",
                 key,
                 value.replace("\"", "\\\"")
             );
@@ -1,12 +1,20 @@
 use candid::{CandidType, Deserialize, Principal};
 use garde::Validate;
-use icrc_ledger_types::icrc1::transfer::BlockIndex;
+use icrc_ledger_types::icrc1::{
+    account::{Account, Subaccount},
+    transfer::BlockIndex,
+};
 
-use crate::{e8s::E8s, Guard, TimestampNs, ENV_VARS};
+use crate::{
+    e8s::E8s,
+    pagination::{PageRequest, PageResponse},
+    votings::types::VotingId,
+    Guard, TimestampNs, ENV_VARS,
+};
 
 use super::{
     state::BankState,
-    types::{SwapFrom, SwapInto},
+    types::{SwapFrom, SwapInto, TreasuryAsset, TreasuryCaps, TreasurySpending, TreasuryTransfer},
 };
 
 #[derive(CandidType, Deserialize, Validate, Clone)]
@@ -29,7 +37,7 @@ impl Guard<BankState> for SwapRewardsRequest {
         self.validate(&()).map_err(|e| e.to_string())?;
 
         if self.qty == E8s::zero() {
-            Err(format!("Empty swap request"))
+            Err("Empty swap request".to_string())
         } else {
             Ok(())
         }
@@ -68,7 +76,7 @@ impl Guard<BankState> for SetExchangeRateRequest {
         if caller == ENV_VARS.votings_canister_id {
             Ok(())
         } else {
-            Err(format!("Access denied"))
+            Err("Access denied".to_string())
         }
     }
 }
@@ -90,11 +98,13 @@ impl Guard<BankState> for GetExchangeRatesRequest {
     }
 }
 
+pub type ExchangeRateHistory = (SwapFrom, SwapInto, Vec<(TimestampNs, E8s)>);
+
 #[derive(CandidType, Deserialize, Validate, Clone)]
 pub struct GetExchangeRatesResponse {
     // rates are expressed as how much <into> one gets for 1.00 <from>
     #[garde(skip)]
-    pub exchange_rates: Vec<(SwapFrom, SwapInto, Vec<(TimestampNs, E8s)>)>,
+    pub exchange_rates: Vec<ExchangeRateHistory>,
 }
 
 #[derive(CandidType, Deserialize, Validate, Clone)]
@@ -118,3 +128,133 @@ pub struct GetFmjStatsResponse {
     #[garde(skip)]
     pub avg_monthly_inflation: E8s, // normalized exponential moving average
 }
+
+#[derive(CandidType, Deserialize, Validate, Clone)]
+pub struct TreasuryTransferRequest {
+    #[garde(skip)]
+    pub voting_id: VotingId,
+    // the action index, if the transfer is a step of a batch voting
+    #[garde(skip)]
+    pub batch_step: Option<u32>,
+    #[garde(dive)]
+    pub asset: TreasuryAsset,
+    #[garde(skip)]
+    pub from_subaccount: Option<Subaccount>,
+    #[garde(skip)]
+    pub to: Account,
+    #[garde(skip)]
+    pub amount: E8s,
+    #[garde(inner(length(max = 32)))]
+    pub memo: Option<Vec<u8>>,
+}
+
+impl TreasuryTransferRequest {
+    // a batch can pay out several times, once per step
+    pub fn attempt_key(&self) -> (VotingId, Option<u32>) {
+        (self.voting_id, self.batch_step)
+    }
+}
+
+impl Guard<BankState> for TreasuryTransferRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &BankState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        if self.amount == E8s::zero() {
+            return Err("Empty transfer request".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate, Clone)]
+pub struct TreasuryTransferResponse {
+    #[garde(skip)]
+    pub id: u64,
+    #[garde(skip)]
+    pub block_idx: BlockIndex,
+}
+
+#[derive(CandidType, Deserialize, Validate, Clone)]
+pub struct SetTreasuryCapsRequest {
+    #[garde(skip)]
+    pub caps: TreasuryCaps,
+}
+
+impl Guard<BankState> for SetTreasuryCapsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &BankState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        self.caps.check_bounds()
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate, Clone)]
+pub struct SetTreasuryCapsResponse {}
+
+#[derive(CandidType, Deserialize, Validate, Clone)]
+pub struct GetTreasuryCapsRequest {}
+
+impl Guard<BankState> for GetTreasuryCapsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &BankState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate, Clone)]
+pub struct GetTreasuryCapsResponse {
+    #[garde(skip)]
+    pub caps: TreasuryCaps,
+    // only assets, which were transferred at least once, are listed
+    #[garde(skip)]
+    pub spending: Vec<(TreasuryAsset, TreasurySpending)>,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetTreasuryTransfersRequest {
+    // the latest transfers go first, if no pagination is provided
+    #[garde(dive)]
+    pub pagination: Option<PageRequest>,
+}
+
+impl Guard<BankState> for GetTreasuryTransfersRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &BankState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetTreasuryTransfersResponse {
+    #[garde(skip)]
+    pub entries: Vec<TreasuryTransfer>,
+    #[garde(dive)]
+    pub pagination: PageResponse,
+}
@@ -3,7 +3,8 @@ use ic_cdk::{api::call::CallResult, call};
 
 use super::api::{
     GetExchangeRatesRequest, GetExchangeRatesResponse, SetExchangeRateRequest,
-    SetExchangeRateResponse, SwapRewardsRequest, SwapRewardsResponse,
+    SetExchangeRateResponse, SetTreasuryCapsRequest, SetTreasuryCapsResponse, SwapRewardsRequest,
+    SwapRewardsResponse, TreasuryTransferRequest, TreasuryTransferResponse,
 };
 
 pub struct BankCanisterClient {
@@ -44,4 +45,24 @@ impl BankCanisterClient {
             .await
             .map(|(it,)| it)
     }
+
+    #[allow(non_snake_case)]
+    pub async fn bank__treasury_transfer(
+        &self,
+        req: TreasuryTransferRequest,
+    ) -> CallResult<TreasuryTransferResponse> {
+        call(self.canister_id, "bank__treasury_transfer", (req,))
+            .await
+            .map(|(it,)| it)
+    }
+
+    #[allow(non_snake_case)]
+    pub async fn bank__set_treasury_caps(
+        &self,
+        req: SetTreasuryCapsRequest,
+    ) -> CallResult<SetTreasuryCapsResponse> {
+        call(self.canister_id, "bank__set_treasury_caps", (req,))
+            .await
+            .map(|(it,)| it)
+    }
 }
@@ -1,43 +1,115 @@
 use std::collections::{BTreeMap, LinkedList};
 
 use candid::{CandidType, Principal};
-use icrc_ledger_types::icrc1::{account::Account, transfer::TransferArg};
+use ic_stable_structures::{Cell, StableBTreeMap};
+use icrc_ledger_types::icrc1::{
+    account::Account,
+    transfer::{BlockIndex, Memo, TransferArg},
+};
 use serde::Deserialize;
 
 use crate::{
-    e8s::E8s, humans::api::SpendRewardsRequest, icrc1::ICRC1CanisterClient,
-    votings::types::ONE_MONTH_NS, TimestampNs,
+    e8s::E8s,
+    humans::api::SpendRewardsRequest,
+    icrc1::ICRC1CanisterClient,
+    pagination::{page_range, PageRequest, PageResponse},
+    stable_memory::Memory,
+    votings::types::{VotingId, ONE_DAY_NS, ONE_HOUR_NS, ONE_MONTH_NS},
+    TimestampNs,
 };
 
 use super::{
     api::{
-        GetExchangeRatesRequest, GetExchangeRatesResponse, GetFmjStatsRequest, GetFmjStatsResponse,
-        SetExchangeRateRequest, SetExchangeRateResponse, SwapRewardsRequest,
+        ExchangeRateHistory, GetExchangeRatesRequest, GetExchangeRatesResponse, GetFmjStatsRequest,
+        GetFmjStatsResponse, GetTreasuryCapsRequest, GetTreasuryCapsResponse,
+        GetTreasuryTransfersRequest, GetTreasuryTransfersResponse, SetExchangeRateRequest,
+        SetExchangeRateResponse, SetTreasuryCapsRequest, SetTreasuryCapsResponse,
+        SwapRewardsRequest, TreasuryTransferRequest,
+    },
+    types::{
+        SwapFrom, SwapInto, TreasuryAsset, TreasuryCaps, TreasurySpending, TreasuryTransfer,
+        TreasuryTransferAttempt,
     },
-    types::{SwapFrom, SwapInto},
 };
 
-#[derive(CandidType, Deserialize, Clone)]
+// how many of the latest transfers are returned, if no pagination is provided
+const TRANSFERS_DEFAULT_PAGE_LEN: u32 = 100;
+
 pub struct BankState {
-    pub fmj_canister_id: Principal,
-    pub icp_canister_id: Principal,
+    pub fmj_canister_id: Cell<Principal, Memory>,
+    pub icp_canister_id: Cell<Principal, Memory>,
 
     // rates are expressed as how much <into> one gets for 1.00 <from>
+    pub exchange_rates: StableBTreeMap<(SwapFrom, SwapInto, TimestampNs), E8s, Memory>,
+    pub monthly_minted_fmj: StableBTreeMap<TimestampNs, E8s, Memory>,
+    pub fmj_total_supply: Cell<E8s, Memory>,
+
+    pub treasury_caps: Cell<TreasuryCaps, Memory>,
+    pub treasury_spending: StableBTreeMap<TreasuryAsset, TreasurySpending, Memory>,
+    // transfer ids are sequential
+    pub treasury_transfers: StableBTreeMap<u64, TreasuryTransfer, Memory>,
+    pub treasury_transfer_attempts:
+        StableBTreeMap<(VotingId, Option<u32>), TreasuryTransferAttempt, Memory>,
+}
+
+// the heap layout this state had before it was moved to stable memory
+#[derive(CandidType, Deserialize)]
+pub struct LegacyBankState {
+    pub fmj_canister_id: Principal,
+    pub icp_canister_id: Principal,
     pub exchange_rates: BTreeMap<(SwapFrom, SwapInto), LinkedList<(TimestampNs, E8s)>>,
     pub monthly_minted_fmj: LinkedList<(TimestampNs, E8s)>,
     pub fmj_total_supply: E8s,
 }
 
 impl BankState {
-    pub fn new(fmj_canister_id: Principal, icp_canister_id: Principal) -> Self {
-        Self {
-            fmj_canister_id,
-            icp_canister_id,
+    pub fn init(
+        &mut self,
+        fmj_canister_id: Principal,
+        icp_canister_id: Principal,
+        exchange_rates: Vec<(SwapFrom, SwapInto, E8s)>,
+        now: TimestampNs,
+    ) {
+        self.fmj_canister_id
+            .set(fmj_canister_id)
+            .expect("Unable to store FMJ canister id");
+        self.icp_canister_id
+            .set(icp_canister_id)
+            .expect("Unable to store ICP canister id");
+
+        for (from, into, rate) in exchange_rates {
+            self.exchange_rates.insert((from, into, now), rate);
+        }
+    }
 
-            exchange_rates: BTreeMap::new(),
-            monthly_minted_fmj: LinkedList::new(),
-            fmj_total_supply: E8s::zero(),
+    pub fn migrate_legacy(&mut self, legacy: LegacyBankState) {
+        self.fmj_canister_id
+            .set(legacy.fmj_canister_id)
+            .expect("Unable to store FMJ canister id");
+        self.icp_canister_id
+            .set(legacy.icp_canister_id)
+            .expect("Unable to store ICP canister id");
+
+        for ((from, into), history) in legacy.exchange_rates {
+            for (timestamp, rate) in history {
+                self.exchange_rates.insert((from, into, timestamp), rate);
+            }
         }
+
+        for (timestamp, qty) in legacy.monthly_minted_fmj {
+            self.monthly_minted_fmj.insert(timestamp, qty);
+        }
+
+        self.fmj_total_supply
+            .set(legacy.fmj_total_supply)
+            .expect("Unable to store FMJ total supply");
+    }
+
+    pub fn get_current_exchange_rate(&self, from: SwapFrom, into: SwapInto) -> Option<E8s> {
+        self.exchange_rates
+            .range((from, into, 0)..=(from, into, TimestampNs::MAX))
+            .last()
+            .map(|(_, rate)| rate)
     }
 
     pub fn prepare_swap_data(
@@ -57,19 +129,13 @@ impl BankState {
             storypoints,
         };
 
-        let history = self
-            .exchange_rates
-            .get(&(req.from, req.into))
-            .cloned()
-            .unwrap();
-
-        let (_, exchange_rate) = history.front().as_ref().unwrap();
+        let exchange_rate = self.get_current_exchange_rate(req.from, req.into).unwrap();
 
         let qty = &req.qty * exchange_rate;
 
         let canister_id = match req.into {
-            SwapInto::ICP => self.icp_canister_id,
-            SwapInto::FMJ => self.fmj_canister_id,
+            SwapInto::ICP => *self.icp_canister_id.get(),
+            SwapInto::FMJ => *self.fmj_canister_id.get(),
         };
 
         let icrc1_client = ICRC1CanisterClient::new(canister_id);
@@ -93,37 +159,39 @@ impl BankState {
         req: SetExchangeRateRequest,
         now: TimestampNs,
     ) -> SetExchangeRateResponse {
-        let history = self.exchange_rates.get_mut(&(req.from, req.into)).unwrap();
-
-        history.push_front((now, req.rate));
+        self.exchange_rates
+            .insert((req.from, req.into, now), req.rate);
 
         SetExchangeRateResponse {}
     }
 
     pub fn get_exchange_rates(&self, _req: GetExchangeRatesRequest) -> GetExchangeRatesResponse {
-        let mut exchange_rates = Vec::new();
-
-        for ((from, into), history) in &self.exchange_rates {
-            exchange_rates.push((
-                from.clone(),
-                into.clone(),
-                history.iter().rev().cloned().collect(),
-            ));
+        let mut exchange_rates: Vec<ExchangeRateHistory> = Vec::new();
+
+        for ((from, into, timestamp), rate) in self.exchange_rates.iter() {
+            match exchange_rates.last_mut() {
+                Some((f, i, history)) if *f == from && *i == into => {
+                    history.push((timestamp, rate));
+                }
+                _ => exchange_rates.push((from, into, vec![(timestamp, rate)])),
+            }
         }
 
         GetExchangeRatesResponse { exchange_rates }
     }
 
     pub fn update_fmj_stats(&mut self, fmj_minted: E8s, now: TimestampNs) {
-        self.fmj_total_supply += &fmj_minted;
+        self.fmj_total_supply
+            .set(&fmj_minted + self.fmj_total_supply.get())
+            .expect("Unable to store FMJ total supply");
 
         let mut needs_new_entry = false;
 
-        if let Some((timestamp, qty)) = self.monthly_minted_fmj.back_mut() {
-            if *timestamp + ONE_MONTH_NS <= now {
+        if let Some((timestamp, qty)) = self.monthly_minted_fmj.last_key_value() {
+            if timestamp + ONE_MONTH_NS <= now {
                 needs_new_entry = true;
             } else {
-                *qty += &fmj_minted;
+                self.monthly_minted_fmj.insert(timestamp, qty + &fmj_minted);
             }
         } else {
             needs_new_entry = true;
@@ -131,10 +199,10 @@ impl BankState {
 
         if needs_new_entry {
             if self.monthly_minted_fmj.len() == 12 {
-                self.monthly_minted_fmj.pop_front();
+                self.monthly_minted_fmj.pop_first();
             }
 
-            self.monthly_minted_fmj.push_back((now, fmj_minted));
+            self.monthly_minted_fmj.insert(now, fmj_minted);
         }
     }
 
@@ -153,18 +221,284 @@ impl BankState {
         let mut avg = E8s::zero();
 
         for (idx, (_, qty)) in self.monthly_minted_fmj.iter().enumerate() {
-            avg += qty * &multiplier;
+            avg += &qty * &multiplier;
 
-            if idx == len - 1 {
-                avg += qty * &multiplier;
+            if idx as u64 == len - 1 {
+                avg += &qty * &multiplier;
             }
 
             multiplier /= &divider;
         }
 
-        return GetFmjStatsResponse {
-            total_supply: self.fmj_total_supply.clone(),
+        GetFmjStatsResponse {
+            total_supply: self.fmj_total_supply.get().clone(),
             avg_monthly_inflation: avg,
+        }
+    }
+
+    // the amount is reserved before the transfer, so concurrent transfers could not exceed the cap
+    pub fn reserve_treasury_spending(
+        &mut self,
+        req: &TreasuryTransferRequest,
+        now: TimestampNs,
+    ) -> Result<(ICRC1CanisterClient, TransferArg), String> {
+        let created_at_time = match self.treasury_transfer_attempts.get(&req.attempt_key()) {
+            None => now,
+            Some(it) if it.transfer_id.is_some() => {
+                return Err("The voting has already been paid out".to_string())
+            }
+            Some(it) if it.in_progress => return Err("The voting is being paid out".to_string()),
+            // the ledger only deduplicates transfers made within the last day
+            Some(it) if now - it.created_at_time < ONE_DAY_NS - ONE_HOUR_NS => it.created_at_time,
+            Some(_) => now,
+        };
+
+        let caps = self.treasury_caps.get();
+        let mut spending = self.treasury_spending.get(&req.asset).unwrap_or_default();
+        spending.actualize(caps, now);
+
+        let cap = caps.get(req.asset);
+        let spent = &spending.spent + &req.amount;
+
+        if &spent > cap {
+            return Err(format!(
+                "The transfer exceeds the {:?} spending cap of this period ({} left)",
+                req.asset,
+                cap - &spending.spent.clone().min(cap.clone())
+            ));
+        }
+
+        spending.spent = spent;
+        self.treasury_spending.insert(req.asset, spending);
+
+        let attempt = TreasuryTransferAttempt {
+            created_at_time,
+            in_progress: true,
+            transfer_id: None,
+        };
+        self.treasury_transfer_attempts
+            .insert(req.attempt_key(), attempt);
+
+        let canister_id = match req.asset {
+            TreasuryAsset::ICP => *self.icp_canister_id.get(),
+            TreasuryAsset::FMJ => *self.fmj_canister_id.get(),
         };
+
+        let icrc1_client = ICRC1CanisterClient::new(canister_id);
+        let arg = TransferArg {
+            from_subaccount: req.from_subaccount,
+            to: req.to,
+            amount: req.amount.0.clone(),
+            created_at_time: Some(created_at_time),
+            memo: req.memo.clone().map(Memo::from),
+            fee: None,
+        };
+
+        Ok((icrc1_client, arg))
+    }
+
+    // the period could have been restarted while the transfer was in progress
+    pub fn release_treasury_spending(&mut self, req: &TreasuryTransferRequest) {
+        if let Some(mut spending) = self.treasury_spending.get(&req.asset) {
+            spending.spent =
+                spending.spent.clone() - spending.spent.clone().min(req.amount.clone());
+            self.treasury_spending.insert(req.asset, spending);
+        }
+
+        // the voting can retry later with the same created_at_time
+        if let Some(mut attempt) = self.treasury_transfer_attempts.get(&req.attempt_key()) {
+            attempt.in_progress = false;
+            self.treasury_transfer_attempts
+                .insert(req.attempt_key(), attempt);
+        }
+    }
+
+    pub fn record_treasury_transfer(
+        &mut self,
+        req: TreasuryTransferRequest,
+        block_idx: BlockIndex,
+        now: TimestampNs,
+    ) -> u64 {
+        let id = self.treasury_transfers.len();
+        let attempt_key = req.attempt_key();
+
+        // the bank is the minter of FMJ, so transfers from its main account are mints
+        if matches!(req.asset, TreasuryAsset::FMJ) && req.from_subaccount.is_none() {
+            self.update_fmj_stats(req.amount.clone(), now);
+        }
+
+        let transfer = TreasuryTransfer {
+            id,
+            voting_id: req.voting_id,
+            batch_step: req.batch_step,
+            asset: req.asset,
+            from_subaccount: req.from_subaccount,
+            to: req.to,
+            amount: req.amount,
+            memo: req.memo,
+            block_idx,
+            transferred_at: now,
+        };
+
+        self.treasury_transfers.insert(id, transfer);
+
+        if let Some(mut attempt) = self.treasury_transfer_attempts.get(&attempt_key) {
+            attempt.in_progress = false;
+            attempt.transfer_id = Some(id);
+            self.treasury_transfer_attempts.insert(attempt_key, attempt);
+        }
+
+        id
+    }
+
+    pub fn set_treasury_caps(&mut self, req: SetTreasuryCapsRequest) -> SetTreasuryCapsResponse {
+        self.treasury_caps
+            .set(req.caps)
+            .expect("Unable to store treasury caps");
+
+        SetTreasuryCapsResponse {}
+    }
+
+    pub fn get_treasury_caps(
+        &self,
+        _req: GetTreasuryCapsRequest,
+        now: TimestampNs,
+    ) -> GetTreasuryCapsResponse {
+        let caps = self.treasury_caps.get().clone();
+
+        let spending = self
+            .treasury_spending
+            .iter()
+            .map(|(asset, mut spending)| {
+                spending.actualize(&caps, now);
+                (asset, spending)
+            })
+            .collect();
+
+        GetTreasuryCapsResponse { caps, spending }
+    }
+
+    pub fn get_treasury_transfers(
+        &self,
+        req: GetTreasuryTransfersRequest,
+    ) -> GetTreasuryTransfersResponse {
+        let pagination = req.pagination.unwrap_or(PageRequest {
+            reversed: true,
+            skip: 0,
+            take: TRANSFERS_DEFAULT_PAGE_LEN,
+        });
+
+        let (from, to, left) = page_range(self.treasury_transfers.len(), &pagination);
+
+        let mut entries: Vec<_> = self
+            .treasury_transfers
+            .range(from..to)
+            .map(|(_, transfer)| transfer)
+            .collect();
+
+        if pagination.reversed {
+            entries.reverse();
+        }
+
+        GetTreasuryTransfersResponse {
+            entries,
+            pagination: PageResponse { left, next: None },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candid::Nat;
+    use ic_stable_structures::{
+        memory_manager::{MemoryId, MemoryManager},
+        DefaultMemoryImpl,
+    };
+
+    use super::*;
+
+    fn e8s(whole: u64) -> E8s {
+        E8s(Nat::from(whole * 1_0000_0000))
+    }
+
+    fn state() -> BankState {
+        let m = MemoryManager::init(DefaultMemoryImpl::default());
+        let caps = TreasuryCaps {
+            max_icp: e8s(100),
+            ..Default::default()
+        };
+
+        BankState {
+            fmj_canister_id: Cell::init(m.get(MemoryId::new(0)), Principal::anonymous()).unwrap(),
+            icp_canister_id: Cell::init(m.get(MemoryId::new(1)), Principal::anonymous()).unwrap(),
+            exchange_rates: StableBTreeMap::init(m.get(MemoryId::new(2))),
+            monthly_minted_fmj: StableBTreeMap::init(m.get(MemoryId::new(3))),
+            fmj_total_supply: Cell::init(m.get(MemoryId::new(4)), E8s::zero()).unwrap(),
+            treasury_caps: Cell::init(m.get(MemoryId::new(5)), caps).unwrap(),
+            treasury_spending: StableBTreeMap::init(m.get(MemoryId::new(6))),
+            treasury_transfers: StableBTreeMap::init(m.get(MemoryId::new(7))),
+            treasury_transfer_attempts: StableBTreeMap::init(m.get(MemoryId::new(8))),
+        }
+    }
+
+    fn transfer(voting_id: VotingId, batch_step: Option<u32>) -> TreasuryTransferRequest {
+        TreasuryTransferRequest {
+            voting_id,
+            batch_step,
+            asset: TreasuryAsset::ICP,
+            from_subaccount: None,
+            to: Account {
+                owner: Principal::anonymous(),
+                subaccount: None,
+            },
+            amount: e8s(10),
+            memo: None,
+        }
+    }
+
+    #[test]
+    fn batch_pays_out_once_per_step() {
+        let mut s = state();
+        let batch_id = VotingId::Batch([1; 32]);
+
+        for step in 0..2 {
+            let req = transfer(batch_id, Some(step));
+
+            s.reserve_treasury_spending(&req, 0).unwrap();
+            s.record_treasury_transfer(req, Nat::from(step), 0);
+        }
+
+        let steps: Vec<_> = s
+            .treasury_transfers
+            .iter()
+            .map(|(_, it)| it.batch_step)
+            .collect();
+        assert_eq!(steps, vec![Some(0), Some(1)]);
+
+        assert_eq!(
+            s.reserve_treasury_spending(&transfer(batch_id, Some(1)), 1)
+                .err(),
+            Some("The voting has already been paid out".to_string())
+        );
+        assert!(s
+            .reserve_treasury_spending(&transfer(VotingId::TreasuryTransfer([2; 32]), None), 1)
+            .is_ok());
+    }
+
+    #[test]
+    fn failed_transfer_is_retried_with_the_same_timestamp() {
+        let mut s = state();
+        let req = transfer(VotingId::Batch([1; 32]), Some(0));
+
+        assert!(s.reserve_treasury_spending(&req, 5).is_ok());
+        assert_eq!(
+            s.reserve_treasury_spending(&req, 6).err(),
+            Some("The voting is being paid out".to_string())
+        );
+
+        s.release_treasury_spending(&req);
+
+        let (_, arg) = s.reserve_treasury_spending(&req, 7).unwrap();
+        assert_eq!(arg.created_at_time, Some(5));
     }
 }
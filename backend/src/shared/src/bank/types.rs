@@ -1,5 +1,16 @@
-use candid::{CandidType, Deserialize};
+use candid::{CandidType, Deserialize, Nat};
 use garde::Validate;
+use icrc_ledger_types::icrc1::{
+    account::{Account, Subaccount},
+    transfer::BlockIndex,
+};
+
+use crate::{
+    e8s::E8s,
+    impl_storable_for_candid, impl_storable_for_u8_enum,
+    votings::types::{VotingId, ONE_DAY_NS, ONE_WEEK_NS},
+    DurationNs, TimestampNs,
+};
 
 #[derive(CandidType, Deserialize, Validate, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum SwapFrom {
@@ -12,3 +23,137 @@ pub enum SwapInto {
     ICP,
     FMJ,
 }
+
+#[derive(CandidType, Deserialize, Validate, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TreasuryAsset {
+    ICP,
+    FMJ,
+}
+
+// how much of each asset the treasury can transfer out during a single period
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TreasuryCaps {
+    pub period_ns: DurationNs,
+    pub max_icp: E8s,
+    pub max_fmj: E8s,
+}
+
+impl Default for TreasuryCaps {
+    // nothing can be transferred by default, until governance decides so
+    fn default() -> Self {
+        Self {
+            period_ns: ONE_WEEK_NS * 4,
+            max_icp: E8s::zero(),
+            max_fmj: E8s::zero(),
+        }
+    }
+}
+
+impl TreasuryCaps {
+    pub fn check_bounds(&self) -> Result<(), String> {
+        if self.period_ns < ONE_DAY_NS || self.period_ns > ONE_WEEK_NS * 52 {
+            return Err("Period should be between one day and one year".to_string());
+        }
+
+        if self.max_icp > E8s(Nat::from(10_0000_0000_0000u64)) {
+            return Err("Max ICP cap is 100000 ICP per period".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, asset: TreasuryAsset) -> &E8s {
+        match asset {
+            TreasuryAsset::ICP => &self.max_icp,
+            TreasuryAsset::FMJ => &self.max_fmj,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct TreasurySpending {
+    pub period_started_at: TimestampNs,
+    pub spent: E8s,
+}
+
+impl TreasurySpending {
+    // a new period starts with the first transfer after the previous one is over
+    pub fn actualize(&mut self, caps: &TreasuryCaps, now: TimestampNs) {
+        if self.period_started_at + caps.period_ns <= now {
+            self.period_started_at = now;
+            self.spent = E8s::zero();
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TreasuryTransfer {
+    pub id: u64,
+    pub voting_id: VotingId,
+    pub batch_step: Option<u32>,
+    pub asset: TreasuryAsset,
+    pub from_subaccount: Option<Subaccount>,
+    pub to: Account,
+    pub amount: E8s,
+    pub memo: Option<Vec<u8>>,
+    pub block_idx: BlockIndex,
+    pub transferred_at: TimestampNs,
+}
+
+// used as keys, so encoded as fixed bytes instead of candid
+impl_storable_for_u8_enum!(SwapFrom { Storypoint = 0, Hour = 1 });
+impl_storable_for_u8_enum!(SwapInto { ICP = 0, FMJ = 1 });
+impl_storable_for_u8_enum!(TreasuryAsset { ICP = 0, FMJ = 1 });
+
+// each transfer voting (or each step of a batch voting) can only pay out once
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct TreasuryTransferAttempt {
+    // reused by retries, so the ledger deduplicates a transfer that went through unnoticed
+    pub created_at_time: TimestampNs,
+    pub in_progress: bool,
+    pub transfer_id: Option<u64>,
+}
+
+impl_storable_for_candid!(
+    TreasuryCaps,
+    TreasurySpending,
+    TreasuryTransfer,
+    TreasuryTransferAttempt
+);
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use ic_stable_structures::Storable;
+
+    use super::*;
+
+    #[test]
+    fn enum_keys_round_trip() {
+        for from in [SwapFrom::Storypoint, SwapFrom::Hour] {
+            assert_eq!(SwapFrom::from_bytes(from.to_bytes()), from);
+        }
+        for into in [SwapInto::ICP, SwapInto::FMJ] {
+            assert_eq!(SwapInto::from_bytes(into.to_bytes()), into);
+        }
+        for asset in [TreasuryAsset::ICP, TreasuryAsset::FMJ] {
+            assert_eq!(TreasuryAsset::from_bytes(asset.to_bytes()), asset);
+        }
+
+        assert_eq!(SwapFrom::Hour.to_bytes(), Cow::Borrowed(&[1u8][..]));
+        assert_eq!(TreasuryAsset::FMJ.to_bytes(), Cow::Borrowed(&[1u8][..]));
+    }
+
+    #[test]
+    fn exchange_rate_keys_are_ordered_by_time() {
+        let key = |at: TimestampNs| (SwapFrom::Hour, SwapInto::ICP, at);
+
+        assert_eq!(
+            <(SwapFrom, SwapInto, TimestampNs)>::from_bytes(key(42).to_bytes()),
+            key(42)
+        );
+        assert!(key(1).to_bytes() < key(256).to_bytes());
+        assert!(key(u64::MAX).to_bytes() < (SwapFrom::Hour, SwapInto::FMJ, 0u64).to_bytes());
+    }
+}
@@ -83,6 +83,13 @@ impl E8s {
         Self(Nat(whole.0.sqrt()) * Nat::from(1_0000_0000u64))
     }
 
+    // unlike sqrt(), keeps the fractional part
+    pub fn sqrt_precise(&self) -> Self {
+        let scaled = self.0.clone() * Nat::from(1_0000_0000u64);
+
+        Self(Nat(scaled.0.sqrt()))
+    }
+
     pub fn to_precision_2(self) -> E8s {
         Self(self.0 / Nat::from(100_0000u64) * Nat::from(100_0000u64))
     }
@@ -222,7 +229,7 @@ impl Mul<E8s> for &E8s {
 
 impl MulAssign<&E8s> for E8s {
     fn mul_assign(&mut self, rhs: &E8s) {
-        *(&mut self.0 .0) = (&self.0 .0).mul(&rhs.0 .0).div(E8s::one().0 .0)
+        self.0 .0 = (&self.0 .0).mul(&rhs.0 .0).div(E8s::one().0 .0)
     }
 }
 
@@ -266,7 +273,7 @@ impl Div<E8s> for &E8s {
 
 impl DivAssign<&E8s> for E8s {
     fn div_assign(&mut self, rhs: &E8s) {
-        *(&mut self.0 .0) = (&self.0 .0).mul(E8s::one().0 .0).div(&rhs.0 .0)
+        self.0 .0 = (&self.0 .0).mul(E8s::one().0 .0).div(&rhs.0 .0)
     }
 }
 
@@ -277,7 +284,7 @@ impl DivAssign for E8s {
 }
 
 impl Storable for E8s {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         let mut buf = Vec::new();
 
         let balance_buf = self.0 .0.to_bytes_le();
@@ -289,7 +296,7 @@ impl Storable for E8s {
         buf.push(balance_buf.len() as u8);
         buf.extend_from_slice(&balance_buf);
 
-        return std::borrow::Cow::Owned(buf);
+        std::borrow::Cow::Owned(buf)
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
@@ -1,12 +1,12 @@
 // This file is automatically generated by build.rs
 
-pub const CAN_BANK_CANISTER_ID: &'static str = "qz32s-aqaaa-aaaag-alfya-cai";
-pub const CAN_HUMANS_CANISTER_ID: &'static str = "qxzx2-3aaaa-aaaag-alfza-cai";
-pub const CAN_TASKS_CANISTER_ID: &'static str = "ql5nl-maaaa-aaaag-alf3a-cai";
-pub const CAN_VOTINGS_CANISTER_ID: &'static str = "qm4l7-byaaa-aaaag-alf3q-cai";
-pub const CAN_WORK_REPORTS_CANISTER_ID: &'static str = "n7263-faaaa-aaaag-almpq-cai";
-pub const CAN_REPUTATION_CANISTER_ID: &'static str = "qf7ad-xqaaa-aaaag-alf2a-cai";
-pub const CAN_LIQUID_DEMOCRACY_CANISTER_ID: &'static str = "qqyro-wyaaa-aaaag-alfzq-cai";
-pub const CAN_FMJ_CANISTER_ID: &'static str = "q624g-niaaa-aaaag-alfyq-cai";
-pub const CAN_ICP_CANISTER_ID: &'static str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
-pub const CAN_ROOT_KEY: &'static str = "[48, 129, 130, 48, 29, 6, 13, 43, 6, 1, 4, 1, 130, 220, 124, 5, 3, 1, 2, 1, 6, 12, 43, 6, 1, 4, 1, 130, 220, 124, 5, 3, 2, 1, 3, 97, 0, 129, 76, 14, 110, 199, 31, 171, 88, 59, 8, 189, 129, 55, 60, 37, 92, 60, 55, 27, 46, 132, 134, 60, 152, 164, 241, 224, 139, 116, 35, 93, 20, 251, 93, 156, 12, 213, 70, 217, 104, 95, 145, 58, 12, 11, 44, 197, 52, 21, 131, 191, 75, 67, 146, 228, 103, 219, 150, 214, 91, 155, 180, 203, 113, 113, 18, 248, 71, 46, 13, 90, 77, 20, 80, 95, 253, 116, 132, 176, 18, 145, 9, 28, 95, 135, 185, 136, 131, 70, 63, 152, 9, 26, 11, 170, 174]";
+pub const CAN_BANK_CANISTER_ID: &str = "qz32s-aqaaa-aaaag-alfya-cai";
+pub const CAN_HUMANS_CANISTER_ID: &str = "qxzx2-3aaaa-aaaag-alfza-cai";
+pub const CAN_TASKS_CANISTER_ID: &str = "ql5nl-maaaa-aaaag-alf3a-cai";
+pub const CAN_VOTINGS_CANISTER_ID: &str = "qm4l7-byaaa-aaaag-alf3q-cai";
+pub const CAN_WORK_REPORTS_CANISTER_ID: &str = "n7263-faaaa-aaaag-almpq-cai";
+pub const CAN_REPUTATION_CANISTER_ID: &str = "qf7ad-xqaaa-aaaag-alf2a-cai";
+pub const CAN_LIQUID_DEMOCRACY_CANISTER_ID: &str = "qqyro-wyaaa-aaaag-alfzq-cai";
+pub const CAN_FMJ_CANISTER_ID: &str = "q624g-niaaa-aaaag-alfyq-cai";
+pub const CAN_ICP_CANISTER_ID: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
+pub const CAN_ROOT_KEY: &str = "[48, 129, 130, 48, 29, 6, 13, 43, 6, 1, 4, 1, 130, 220, 124, 5, 3, 1, 2, 1, 6, 12, 43, 6, 1, 4, 1, 130, 220, 124, 5, 3, 2, 1, 3, 97, 0, 129, 76, 14, 110, 199, 31, 171, 88, 59, 8, 189, 129, 55, 60, 37, 92, 60, 55, 27, 46, 132, 134, 60, 152, 164, 241, 224, 139, 116, 35, 93, 20, 251, 93, 156, 12, 213, 70, 217, 104, 95, 145, 58, 12, 11, 44, 197, 52, 21, 131, 191, 75, 67, 146, 228, 103, 219, 150, 214, 91, 155, 180, 203, 113, 113, 18, 248, 71, 46, 13, 90, 77, 20, 80, 95, 253, 116, 132, 176, 18, 145, 9, 28, 95, 135, 185, 136, 131, 70, 63, 152, 9, 26, 11, 170, 174]";
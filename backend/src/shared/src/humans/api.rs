@@ -51,15 +51,15 @@ impl Guard<HumansState> for RegisterRequest {
         self.validate(&()).map_err(|e| e.to_string())?;
 
         if state.profiles.contains_key(&caller) {
-            return Err(format!("The profile already exists"));
+            return Err("The profile already exists".to_string());
         }
 
         if let Some(name) = &mut self.name {
-            *name = escape_script_tag(&name);
+            *name = escape_script_tag(name);
         }
 
         if !bufs_le(&self.pow[0..POW_COMPLEXITY.len()], POW_COMPLEXITY) {
-            return Err(format!("The Proof Of Work is of invalid complexity"));
+            return Err("The Proof Of Work is of invalid complexity".to_string());
         }
 
         let expected = self.hash(&caller, &ENV_VARS.humans_canister_id);
@@ -93,13 +93,11 @@ impl Guard<HumansState> for EditProfileRequest {
         self.validate(&()).map_err(|e| e.to_string())?;
 
         if !state.profiles.contains_key(&caller) {
-            return Err(format!("The profile does not exist"));
+            return Err("The profile does not exist".to_string());
         }
 
-        if let Some(new_name) = &mut self.new_name_opt {
-            if let Some(name) = new_name {
-                *name = escape_script_tag(&name);
-            }
+        if let Some(Some(name)) = &mut self.new_name_opt {
+            *name = escape_script_tag(name);
         }
 
         Ok(())
@@ -124,8 +122,12 @@ impl Guard<HumansState> for MintRewardsRequest {
     ) -> Result<(), String> {
         self.validate(&()).map_err(|e| e.to_string())?;
 
-        if caller != ENV_VARS.tasks_canister_id && caller != ENV_VARS.work_reports_canister_id {
-            return Err(format!("Access denied"));
+        // the votings canister funds conviction proposals
+        if caller != ENV_VARS.tasks_canister_id
+            && caller != ENV_VARS.work_reports_canister_id
+            && caller != ENV_VARS.votings_canister_id
+        {
+            return Err("Access denied".to_string());
         }
 
         Ok(())
@@ -154,21 +156,22 @@ impl Guard<HumansState> for SpendRewardsRequest {
     ) -> Result<(), String> {
         self.validate(&()).map_err(|e| e.to_string())?;
 
-        if caller != ENV_VARS.bank_canister_id {
-            return Err(format!("Access denied"));
+        // the votings canister locks deposits of votings
+        if caller != ENV_VARS.bank_canister_id && caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
         }
 
         let profile = state
             .profiles
             .get(&self.spender)
-            .ok_or(format!("No profile exists"))?;
+            .ok_or("No profile exists".to_string())?;
 
         if profile.hours_balance < self.hours {
-            return Err(format!("Insufficient hours balance"));
+            return Err("Insufficient hours balance".to_string());
         }
 
         if profile.storypoints_balance < self.storypoints {
-            return Err(format!("Insufficient storypoints balance"));
+            return Err("Insufficient storypoints balance".to_string());
         }
 
         Ok(())
@@ -197,15 +200,15 @@ impl Guard<HumansState> for RefundRewardsRequest {
     ) -> Result<(), String> {
         self.validate(&()).map_err(|e| e.to_string())?;
 
-        if caller != ENV_VARS.bank_canister_id {
-            return Err(format!("Access denied"));
+        if caller != ENV_VARS.bank_canister_id && caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
         }
 
         state
             .profiles
             .get(&self.spender)
             .map(|_| ())
-            .ok_or(format!("No profile exists"))
+            .ok_or("No profile exists".to_string())
     }
 }
 
@@ -230,20 +233,20 @@ impl Guard<HumansState> for EmployRequest {
         self.validate(&()).map_err(|e| e.to_string())?;
 
         if caller != ENV_VARS.votings_canister_id {
-            return Err(format!("Access denied"));
+            return Err("Access denied".to_string());
         }
 
         if self.hours_a_week_commitment > E8s(Nat::from(40_0000_0000u64)) {
-            return Err(format!("One can only commit up to 40 hours a week"));
+            return Err("One can only commit up to 40 hours a week".to_string());
         }
 
         let profile = state
             .profiles
             .get(&self.candidate)
-            .ok_or(format!("Only humans with a profile can be employed"))?;
+            .ok_or("Only humans with a profile can be employed".to_string())?;
 
         if profile.is_employed() {
-            return Err(format!("Already an employee"));
+            return Err("Already an employee".to_string());
         }
 
         Ok(())
@@ -269,16 +272,16 @@ impl Guard<HumansState> for UnemployRequest {
         self.validate(&()).map_err(|e| e.to_string())?;
 
         if caller != ENV_VARS.votings_canister_id {
-            return Err(format!("Access denied"));
+            return Err("Access denied".to_string());
         }
 
         let profile = state
             .profiles
             .get(&self.team_member)
-            .ok_or(format!("Only humans with a profile can be unemployed"))?;
+            .ok_or("Only humans with a profile can be unemployed".to_string())?;
 
         if !profile.is_employed() {
-            return Err(format!("Already an not a team member"));
+            return Err("Already an not a team member".to_string());
         }
 
         Ok(())
@@ -346,7 +349,7 @@ impl Guard<HumansState> for GetProfileProofsRequest {
         if state.profiles.contains_key(&caller) {
             Ok(())
         } else {
-            Err(format!("No profile exist"))
+            Err("No profile exist".to_string())
         }
     }
 }
@@ -4,10 +4,9 @@ use ic_cdk::{api::call::CallResult, call};
 use super::api::{
     EditProfileRequest, EditProfileResponse, EmployRequest, EmployResponse, GetProfileIdsRequest,
     GetProfileIdsResponse, GetProfileProofsRequest, GetProfileProofsResponse, GetProfilesRequest,
-    GetProfilesResponse, GetTotalsRequest, GetTotalsResponse,
-    MintRewardsRequest, MintRewardsResponse, RefundRewardsRequest, RefundRewardsResponse,
-    RegisterRequest, RegisterResponse, SpendRewardsRequest, SpendRewardsResponse, UnemployRequest,
-    UnemployResponse,
+    GetProfilesResponse, GetTotalsRequest, GetTotalsResponse, MintRewardsRequest,
+    MintRewardsResponse, RefundRewardsRequest, RefundRewardsResponse, RegisterRequest,
+    RegisterResponse, SpendRewardsRequest, SpendRewardsResponse, UnemployRequest, UnemployResponse,
 };
 
 pub struct HumansCanisterClient {
@@ -1,8 +1,9 @@
 use std::collections::BTreeMap;
 
 use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_stable_structures::{Cell, StableBTreeMap};
 
-use crate::{e8s::E8s, TimestampNs};
+use crate::{e8s::E8s, stable_memory::Memory, TimestampNs};
 
 use super::{
     api::{
@@ -16,8 +17,16 @@ use super::{
     types::{Profile, ProfileProofBody, PROFILE_PROOFS_MARKER},
 };
 
-#[derive(CandidType, Deserialize, Default)]
 pub struct HumansState {
+    pub profiles: StableBTreeMap<Principal, Profile, Memory>,
+    pub total_hours_minted: Cell<E8s, Memory>,
+    pub total_storypoints_minted: Cell<E8s, Memory>,
+    pub inited: Cell<bool, Memory>,
+}
+
+// the heap layout this state had before it was moved to stable memory
+#[derive(CandidType, Deserialize)]
+pub struct LegacyHumansState {
     pub profiles: BTreeMap<Principal, Profile>,
     pub total_hours_minted: E8s,
     pub total_storypoints_minted: E8s,
@@ -25,6 +34,22 @@ pub struct HumansState {
 }
 
 impl HumansState {
+    pub fn migrate_legacy(&mut self, legacy: LegacyHumansState) {
+        for (id, profile) in legacy.profiles {
+            self.profiles.insert(id, profile);
+        }
+
+        self.total_hours_minted
+            .set(legacy.total_hours_minted)
+            .expect("Unable to store total hours minted");
+        self.total_storypoints_minted
+            .set(legacy.total_storypoints_minted)
+            .expect("Unable to store total storypoints minted");
+        self.inited
+            .set(legacy.inited)
+            .expect("Unable to store the inited flag");
+    }
+
     pub fn register(
         &mut self,
         req: RegisterRequest,
@@ -42,9 +67,10 @@ impl HumansState {
         req: EditProfileRequest,
         caller: Principal,
     ) -> EditProfileResponse {
-        let profile = self.profiles.get_mut(&caller).unwrap();
+        let mut profile = self.profiles.get(&caller).unwrap();
 
         profile.edit_profile(req.new_name_opt);
+        self.profiles.insert(caller, profile);
 
         EditProfileResponse {}
     }
@@ -54,66 +80,71 @@ impl HumansState {
         let mut minted_storypoints = E8s::zero();
 
         for entry in req.rewards {
-            if let Some(profile) = self.profiles.get_mut(&entry.solver) {
+            if let Some(mut profile) = self.profiles.get(&entry.solver) {
                 minted_hours += &entry.reward_hours;
                 minted_storypoints += &entry.reward_storypoints;
 
                 profile.mint_rewards(entry.reward_hours, entry.reward_storypoints);
+                self.profiles.insert(entry.solver, profile);
             } else {
                 // No rewards for people without a profile!
             }
         }
 
-        self.total_hours_minted += minted_hours;
-        self.total_storypoints_minted += minted_storypoints;
+        self.total_hours_minted
+            .set(minted_hours + self.total_hours_minted.get())
+            .expect("Unable to store total hours minted");
+        self.total_storypoints_minted
+            .set(minted_storypoints + self.total_storypoints_minted.get())
+            .expect("Unable to store total storypoints minted");
 
         MintRewardsResponse {}
     }
 
     pub fn spend_rewards(&mut self, req: SpendRewardsRequest) -> SpendRewardsResponse {
-        let profile = self.profiles.get_mut(&req.spender).unwrap();
+        let mut profile = self.profiles.get(&req.spender).unwrap();
 
         profile.spend_rewards(req.hours, req.storypoints);
+        self.profiles.insert(req.spender, profile);
 
         SpendRewardsResponse {}
     }
 
     pub fn refund_rewards(&mut self, req: RefundRewardsRequest) -> RefundRewardsResponse {
-        let profile = self.profiles.get_mut(&req.spender).unwrap();
+        let mut profile = self.profiles.get(&req.spender).unwrap();
 
         profile.refund_rewards(req.hours, req.storypoints);
+        self.profiles.insert(req.spender, profile);
 
         RefundRewardsResponse {}
     }
 
     pub fn employ(&mut self, req: EmployRequest, now: TimestampNs) -> EmployResponse {
-        let profile = self.profiles.get_mut(&req.candidate).unwrap();
+        let mut profile = self.profiles.get(&req.candidate).unwrap();
 
         profile.employ(req.hours_a_week_commitment, now);
+        self.profiles.insert(req.candidate, profile);
 
         EmployResponse {}
     }
 
     pub fn unemploy(&mut self, req: UnemployRequest) -> UnemployResponse {
-        let profile = self.profiles.get_mut(&req.team_member).unwrap();
+        let mut profile = self.profiles.get(&req.team_member).unwrap();
 
         profile.unemploy();
+        self.profiles.insert(req.team_member, profile);
 
         UnemployResponse {}
     }
 
     pub fn get_profiles(&self, req: GetProfilesRequest) -> GetProfilesResponse {
-        let profiles = req
-            .ids
-            .iter()
-            .map(|id| self.profiles.get(id).cloned())
-            .collect();
+        let profiles = req.ids.iter().map(|id| self.profiles.get(id)).collect();
 
         GetProfilesResponse { entries: profiles }
     }
 
     pub fn get_profile_ids(&self, _req: GetProfileIdsRequest) -> GetProfileIdsResponse {
-        let ids = self.profiles.keys().cloned().collect();
+        let ids = self.profiles.iter().map(|(id, _)| id).collect();
 
         GetProfileIdsResponse { ids }
     }
@@ -138,25 +169,26 @@ impl HumansState {
 
     pub fn get_totals(&self, _req: GetTotalsRequest) -> GetTotalsResponse {
         GetTotalsResponse {
-            hours: self.total_hours_minted.clone(),
-            storypoints: self.total_storypoints_minted.clone(),
+            hours: self.total_hours_minted.get().clone(),
+            storypoints: self.total_storypoints_minted.get().clone(),
             contributors: self.profiles.len() as u32,
             team_members: self
                 .profiles
                 .iter()
                 .filter(|it| it.1.is_employed())
                 .map(|it| it.0)
-                .copied()
                 .collect(),
         }
     }
 
     pub fn init(&mut self, caller: Principal, now: TimestampNs) {
-        if self.inited {
+        if *self.inited.get() {
             panic!("Can't init twice");
         }
 
-        self.inited = true;
+        self.inited
+            .set(true)
+            .expect("Unable to store the inited flag");
 
         self.register(
             RegisterRequest {
@@ -1,7 +1,7 @@
 use candid::{CandidType, Deserialize, Principal};
 use garde::Validate;
 
-use crate::{e8s::E8s, TimestampNs};
+use crate::{e8s::E8s, impl_storable_for_candid, TimestampNs};
 
 pub const PROFILE_PROOFS_MARKER: &str = "FMJ HUMANS CANISTER GET PROFILE PROOFS RESPONSE";
 
@@ -72,6 +72,8 @@ impl Profile {
     }
 }
 
+impl_storable_for_candid!(Profile);
+
 #[derive(CandidType, Deserialize, Clone)]
 pub struct Employment {
     pub employed_at: TimestampNs,
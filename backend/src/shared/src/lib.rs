@@ -17,6 +17,7 @@ pub mod liquid_democracy;
 pub mod pagination;
 pub mod proof;
 pub mod reputation;
+pub mod stable_memory;
 pub mod task_archive;
 pub mod tasks;
 pub mod votings;
@@ -52,6 +53,12 @@ pub struct EnvVarsState {
     pub ic_root_key: Vec<u8>,
 }
 
+impl Default for EnvVarsState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl EnvVarsState {
     pub fn new() -> Self {
         Self {
@@ -3,11 +3,11 @@ use std::collections::BTreeMap;
 use candid::{CandidType, Deserialize, Principal};
 use garde::Validate;
 
-use crate::{proof::ReputationProof, Guard};
+use crate::{escape_script_tag, proof::ReputationProof, Guard, ENV_VARS};
 
 use super::{
-    state::LiquidDemocracyState,
-    types::{DecisionTopic, DecisionTopicSet, DelegationTreeNode},
+    state::{LiquidDemocracyState, GENERAL_TOPIC_ID},
+    types::{DecisionTopic, DecisionTopicId, DecisionTopicSet, DelegationTreeNode},
 };
 
 #[derive(CandidType, Deserialize, Validate)]
@@ -30,10 +30,8 @@ impl Guard<LiquidDemocracyState> for FollowRequest {
         self.validate(&()).map_err(|e| e.to_string())?;
         self.proof.assert_valid_for(caller, now)?;
 
-        if let Some(callers_followees) = state.followees_of.get(&caller) {
-            if callers_followees.len() == 10 {
-                return Err(format!("Only 10 followees are allowed"));
-            }
+        if state.count_followees_of(caller) == 10 {
+            return Err("Only 10 followees are allowed".to_string());
         }
 
         Ok(())
@@ -130,3 +128,144 @@ pub struct GetLiquidDemocracyProofResponse {
     #[garde(dive)]
     pub tree_root: DelegationTreeNode,
 }
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct CreateDecisionTopicRequest {
+    #[garde(length(graphemes, min = 1, max = 64))]
+    pub name: String,
+    #[garde(length(graphemes, min = 16, max = 1024))]
+    pub description: String,
+}
+
+impl Guard<LiquidDemocracyState> for CreateDecisionTopicRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &LiquidDemocracyState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        self.name = escape_script_tag(&self.name);
+        self.description = escape_script_tag(&self.description);
+
+        state.assert_topic_name_is_free(&self.name, None)
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct CreateDecisionTopicResponse {
+    #[garde(skip)]
+    pub id: DecisionTopicId,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct EditDecisionTopicRequest {
+    #[garde(skip)]
+    pub id: DecisionTopicId,
+    #[garde(inner(length(graphemes, min = 1, max = 64)))]
+    pub new_name_opt: Option<String>,
+    #[garde(inner(length(graphemes, min = 16, max = 1024)))]
+    pub new_description_opt: Option<String>,
+}
+
+impl Guard<LiquidDemocracyState> for EditDecisionTopicRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &LiquidDemocracyState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        let topic = state
+            .decision_topics
+            .get(&self.id)
+            .ok_or(format!("Decision topic {} not found", self.id))?;
+
+        if topic.is_deprecated() {
+            return Err(format!("Decision topic {} is deprecated", self.id));
+        }
+
+        if let Some(new_name) = &mut self.new_name_opt {
+            *new_name = escape_script_tag(new_name);
+            state.assert_topic_name_is_free(new_name, Some(self.id))?;
+        }
+
+        if let Some(new_description) = &mut self.new_description_opt {
+            *new_description = escape_script_tag(new_description);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct EditDecisionTopicResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct DeprecateDecisionTopicRequest {
+    #[garde(skip)]
+    pub id: DecisionTopicId,
+    // topics the deprecated one was split or merged into, existing delegations are extended to them
+    #[garde(length(max = 8))]
+    pub successors: Vec<DecisionTopicId>,
+}
+
+impl Guard<LiquidDemocracyState> for DeprecateDecisionTopicRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &LiquidDemocracyState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        let topic = state
+            .decision_topics
+            .get(&self.id)
+            .ok_or(format!("Decision topic {} not found", self.id))?;
+
+        if topic.is_deprecated() {
+            return Err(format!("Decision topic {} is already deprecated", self.id));
+        }
+
+        // governance votings are always about this topic
+        if self.id == GENERAL_TOPIC_ID {
+            return Err("The general decision topic can't be deprecated".to_string());
+        }
+
+        if state.count_active_topics() == 1 {
+            return Err("The last active decision topic can't be deprecated".to_string());
+        }
+
+        self.successors.sort();
+        self.successors.dedup();
+
+        for successor in &self.successors {
+            if *successor == self.id {
+                return Err("A decision topic can't succeed itself".to_string());
+            }
+
+            state.assert_topics_are_active(&[*successor])?;
+        }
+
+        Ok(())
+    }
+}
+
+// delegations are migrated to the successors in the background
+#[derive(CandidType, Deserialize, Validate)]
+pub struct DeprecateDecisionTopicResponse {}
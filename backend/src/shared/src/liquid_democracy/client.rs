@@ -2,6 +2,8 @@ use candid::Principal;
 use ic_cdk::{api::call::CallResult, call};
 
 use super::api::{
+    CreateDecisionTopicRequest, CreateDecisionTopicResponse, DeprecateDecisionTopicRequest,
+    DeprecateDecisionTopicResponse, EditDecisionTopicRequest, EditDecisionTopicResponse,
     FollowRequest, FollowResponse, GetDecisionTopicsRequest, GetDecisionTopicsResponse,
     GetFolloweesOfRequest, GetFolloweesOfResponse, GetFollowersOfRequest, GetFollowersOfResponse,
     GetLiquidDemocracyProofRequest, GetLiquidDemocracyProofResponse,
@@ -78,4 +80,46 @@ impl LiquidDemocracyCanisterClient {
         .await
         .map(|(it,)| it)
     }
+
+    #[allow(non_snake_case)]
+    pub async fn liquid_democracy__create_decision_topic(
+        &self,
+        req: CreateDecisionTopicRequest,
+    ) -> CallResult<CreateDecisionTopicResponse> {
+        call(
+            self.canister_id,
+            "liquid_democracy__create_decision_topic",
+            (req,),
+        )
+        .await
+        .map(|(it,)| it)
+    }
+
+    #[allow(non_snake_case)]
+    pub async fn liquid_democracy__edit_decision_topic(
+        &self,
+        req: EditDecisionTopicRequest,
+    ) -> CallResult<EditDecisionTopicResponse> {
+        call(
+            self.canister_id,
+            "liquid_democracy__edit_decision_topic",
+            (req,),
+        )
+        .await
+        .map(|(it,)| it)
+    }
+
+    #[allow(non_snake_case)]
+    pub async fn liquid_democracy__deprecate_decision_topic(
+        &self,
+        req: DeprecateDecisionTopicRequest,
+    ) -> CallResult<DeprecateDecisionTopicResponse> {
+        call(
+            self.canister_id,
+            "liquid_democracy__deprecate_decision_topic",
+            (req,),
+        )
+        .await
+        .map(|(it,)| it)
+    }
 }
@@ -1,10 +1,13 @@
 use candid::{CandidType, Deserialize, Principal};
-use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+use ic_stable_structures::{Cell, StableBTreeMap};
+use std::collections::{BTreeMap, BTreeSet};
 
-use crate::btreemap;
+use crate::{stable_memory::Memory, TimestampNs};
 
 use super::{
     api::{
+        CreateDecisionTopicRequest, CreateDecisionTopicResponse, DeprecateDecisionTopicRequest,
+        DeprecateDecisionTopicResponse, EditDecisionTopicRequest, EditDecisionTopicResponse,
         FollowRequest, FollowResponse, GetDecisionTopicsRequest, GetDecisionTopicsResponse,
         GetFolloweesOfRequest, GetFolloweesOfResponse, GetFollowersOfRequest,
         GetFollowersOfResponse, GetLiquidDemocracyProofRequest, GetLiquidDemocracyProofResponse,
@@ -22,8 +25,20 @@ pub const DESIGN_TOPIC_ID: DecisionTopicId = 3;
 pub const DOCUMENTATION_TOPIC_ID: DecisionTopicId = 4;
 pub const TESTING_TOPIC_ID: DecisionTopicId = 5;
 
-#[derive(CandidType, Deserialize)]
 pub struct LiquidDemocracyState {
+    pub decision_topic_id_counter: Cell<DecisionTopicId, Memory>,
+    pub decision_topics: StableBTreeMap<DecisionTopicId, DecisionTopic, Memory>,
+    // (follower, followee)
+    pub followees_of: StableBTreeMap<(Principal, Principal), (), Memory>,
+    // (followee, follower) -> topics the follower delegates to the followee
+    pub followers_of: StableBTreeMap<(Principal, Principal), DecisionTopicSet, Memory>,
+    // deprecated topics, whose delegations are still being migrated -> the last migrated followers_of key
+    pub topic_migrations: StableBTreeMap<DecisionTopicId, Option<(Principal, Principal)>, Memory>,
+}
+
+// the heap layout this state had before it was moved to stable memory
+#[derive(CandidType, Deserialize)]
+pub struct LegacyLiquidDemocracyState {
     pub decision_topic_id_counter: DecisionTopicId,
     pub decision_topics: BTreeMap<DecisionTopicId, DecisionTopic>,
     pub followees_of: BTreeMap<Principal, BTreeSet<Principal>>,
@@ -31,29 +46,37 @@ pub struct LiquidDemocracyState {
 }
 
 impl LiquidDemocracyState {
-    pub fn new() -> Self {
+    pub fn init(&mut self) {
         let general_topic = DecisionTopic {
             id: GENERAL_TOPIC_ID,
             name: String::from("Governance"),
             description: String::from("Runtime parameters. For example, what tokens are whitelisted in MSQ, what exchange rates are we using in FMJ swaps, etc."),
+            deprecated_at: None,
+            successors: None,
         };
 
         let development_topic = DecisionTopic {
             id: DEVELOPMENT_TOPIC_ID,
             name: String::from("Development"),
             description: String::from("Everything about the code. Probably GitHub-related."),
+            deprecated_at: None,
+            successors: None,
         };
 
         let marketing_topic = DecisionTopic {
             id: MARKETING_TOPIC_ID,
             name: String::from("Marketing"),
             description: String::from("Everything about public presence. Tasks of this topic are usually about making some kind of content for the public."),
+            deprecated_at: None,
+            successors: None,
         };
 
         let design_topic = DecisionTopic {
             id: DESIGN_TOPIC_ID,
             name: String::from("Design"),
             description: String::from("Everything about the UX and visuals. Figma and others."),
+            deprecated_at: None,
+            successors: None,
         };
 
         let documentation_topic = DecisionTopic {
@@ -62,6 +85,8 @@ impl LiquidDemocracyState {
             description: String::from(
                 "Concepts, tech designs and other writing about our projects. Notion or similar.",
             ),
+            deprecated_at: None,
+            successors: None,
         };
 
         let testing_topic = DecisionTopic {
@@ -70,79 +95,84 @@ impl LiquidDemocracyState {
             description: String::from(
                 "Checking if everything works correctly and reporting finding. Notion or similar.",
             ),
+            deprecated_at: None,
+            successors: None,
         };
 
-        Self {
-            decision_topic_id_counter: 4,
-            decision_topics: btreemap! {
-                GENERAL_TOPIC_ID => general_topic,
-                DEVELOPMENT_TOPIC_ID => development_topic,
-                MARKETING_TOPIC_ID => marketing_topic,
-                DESIGN_TOPIC_ID => design_topic,
-                DOCUMENTATION_TOPIC_ID => documentation_topic,
-                TESTING_TOPIC_ID => testing_topic,
-            },
-            followees_of: BTreeMap::new(),
-            followers_of: BTreeMap::new(),
+        for topic in [
+            general_topic,
+            development_topic,
+            marketing_topic,
+            design_topic,
+            documentation_topic,
+            testing_topic,
+        ] {
+            self.decision_topics.insert(topic.id, topic);
         }
+
+        self.decision_topic_id_counter
+            .set(TESTING_TOPIC_ID + 1)
+            .expect("Unable to store decision topic id counter");
     }
 
-    pub fn default_topicset() -> DecisionTopicSet {
-        use DecisionTopicSet as S;
+    // older versions seeded the counter below the ids of the seeded topics
+    pub fn fix_decision_topic_id_counter(&mut self) {
+        let Some((last_id, _)) = self.decision_topics.last_key_value() else {
+            return;
+        };
 
-        S::or(
-            S::it(TESTING_TOPIC_ID),
-            S::or(
-                S::it(DOCUMENTATION_TOPIC_ID),
-                S::or(
-                    S::it(GENERAL_TOPIC_ID),
-                    S::or(
-                        S::it(DEVELOPMENT_TOPIC_ID),
-                        S::or_it(MARKETING_TOPIC_ID, DESIGN_TOPIC_ID),
-                    ),
-                ),
-            ),
-        )
+        if *self.decision_topic_id_counter.get() <= last_id {
+            self.decision_topic_id_counter
+                .set(last_id + 1)
+                .expect("Unable to store decision topic id counter");
+        }
     }
 
-    pub fn follow(&mut self, req: FollowRequest, caller: Principal) -> FollowResponse {
-        if let Some(topics) = req.topics {
-            match self.followees_of.entry(caller) {
-                Entry::Vacant(e) => {
-                    let mut s = BTreeSet::new();
-                    s.insert(req.followee);
+    pub fn migrate_legacy(&mut self, legacy: LegacyLiquidDemocracyState) {
+        self.decision_topic_id_counter
+            .set(legacy.decision_topic_id_counter)
+            .expect("Unable to store decision topic id counter");
 
-                    e.insert(s);
-                }
-                Entry::Occupied(mut e) => {
-                    e.get_mut().insert(req.followee);
-                }
-            };
-
-            match self.followers_of.entry(req.followee) {
-                Entry::Vacant(e) => {
-                    let mut s = BTreeMap::new();
-                    s.insert(caller, topics);
+        for (id, topic) in legacy.decision_topics {
+            self.decision_topics.insert(id, topic);
+        }
 
-                    e.insert(s);
-                }
-                Entry::Occupied(mut e) => {
-                    e.get_mut().insert(caller, topics);
-                }
-            };
-        } else {
-            if let Some(f) = self.followees_of.get_mut(&caller) {
-                f.remove(&req.followee);
+        for (follower, followees) in legacy.followees_of {
+            for followee in followees {
+                self.followees_of.insert((follower, followee), ());
             }
+        }
 
-            if let Some(f) = self.followers_of.get_mut(&req.followee) {
-                f.remove(&caller);
+        for (followee, followers) in legacy.followers_of {
+            for (follower, topicset) in followers {
+                self.followers_of.insert((followee, follower), topicset);
             }
         }
+    }
+
+    // one always votes for themselves, whatever the topic is, including the ones created later
+    pub fn default_topicset() -> DecisionTopicSet {
+        use DecisionTopicSet as S;
+
+        S::or(S::it(GENERAL_TOPIC_ID), S::not_it(GENERAL_TOPIC_ID))
+    }
+
+    pub fn follow(&mut self, req: FollowRequest, caller: Principal) -> FollowResponse {
+        if let Some(topics) = req.topics {
+            self.followees_of.insert((caller, req.followee), ());
+            self.followers_of.insert((req.followee, caller), topics);
+        } else {
+            self.followees_of.remove(&(caller, req.followee));
+            self.followers_of.remove(&(req.followee, caller));
+        }
 
         FollowResponse {}
     }
 
+    pub fn count_followees_of(&self, of: Principal) -> usize {
+        self.followees_of_iter(of).count()
+    }
+
     pub fn get_followers_of(&self, req: GetFollowersOfRequest) -> GetFollowersOfResponse {
         let entries = req
             .ids
@@ -204,58 +234,228 @@ impl LiquidDemocracyState {
     }
 
     pub fn get_decision_topics(&self, _req: GetDecisionTopicsRequest) -> GetDecisionTopicsResponse {
-        let entries = self.decision_topics.values().cloned().collect();
+        let entries = self
+            .decision_topics
+            .iter()
+            .map(|(_, topic)| topic)
+            .collect();
 
         GetDecisionTopicsResponse { entries }
     }
 
-    #[allow(unused)]
+    pub fn create_decision_topic(
+        &mut self,
+        req: CreateDecisionTopicRequest,
+    ) -> CreateDecisionTopicResponse {
+        let id = self.generate_id();
+
+        let topic = DecisionTopic {
+            id,
+            name: req.name,
+            description: req.description,
+            deprecated_at: None,
+            successors: None,
+        };
+
+        self.decision_topics.insert(id, topic);
+
+        CreateDecisionTopicResponse { id }
+    }
+
+    pub fn edit_decision_topic(
+        &mut self,
+        req: EditDecisionTopicRequest,
+    ) -> EditDecisionTopicResponse {
+        let mut topic = self
+            .decision_topics
+            .get(&req.id)
+            .expect("UNREACHEABLE - decision topic should exist");
+
+        if let Some(new_name) = req.new_name_opt {
+            topic.name = new_name;
+        }
+
+        if let Some(new_description) = req.new_description_opt {
+            topic.description = new_description;
+        }
+
+        self.decision_topics.insert(req.id, topic);
+
+        EditDecisionTopicResponse {}
+    }
+
+    pub fn deprecate_decision_topic(
+        &mut self,
+        req: DeprecateDecisionTopicRequest,
+        now: TimestampNs,
+    ) -> DeprecateDecisionTopicResponse {
+        let mut topic = self
+            .decision_topics
+            .get(&req.id)
+            .expect("UNREACHEABLE - decision topic should exist");
+
+        topic.deprecated_at = Some(now);
+        topic.successors = Some(req.successors.clone());
+
+        self.decision_topics.insert(req.id, topic);
+
+        // without successors the topic simply stops being used, delegations are left as they are
+        if !req.successors.is_empty() {
+            self.topic_migrations.insert(req.id, None);
+        }
+
+        DeprecateDecisionTopicResponse {}
+    }
+
+    // returns true if should reschedule another migration round
+    pub fn migrate_delegations(&mut self, items_num: u64) -> bool {
+        let (topic_id, start) = match self.topic_migrations.first_key_value() {
+            Some(it) => it,
+            None => return false,
+        };
+
+        let successors = self
+            .decision_topics
+            .get(&topic_id)
+            .and_then(|it| it.successors)
+            .unwrap_or_default();
+
+        let mut iter = if let Some(start) = start {
+            let mut i = self.followers_of.range(start..);
+            i.next();
+
+            i
+        } else {
+            self.followers_of.iter()
+        };
+
+        let mut entries_to_update = Vec::new();
+        let mut last_key = None;
+
+        for _ in 0..items_num {
+            if let Some((key, topicset)) = iter.next() {
+                if topicset.contains(topic_id) {
+                    entries_to_update.push((key, topicset.replace(topic_id, &successors)));
+                }
+
+                last_key = Some(key);
+            } else {
+                last_key = None;
+                break;
+            }
+        }
+
+        drop(iter);
+
+        for (key, topicset) in entries_to_update {
+            self.followers_of.insert(key, topicset);
+        }
+
+        if last_key.is_some() {
+            self.topic_migrations.insert(topic_id, last_key);
+        } else {
+            self.topic_migrations.remove(&topic_id);
+        }
+
+        !self.topic_migrations.is_empty()
+    }
+
+    pub fn count_active_topics(&self) -> usize {
+        self.decision_topics
+            .iter()
+            .filter(|(_, topic)| !topic.is_deprecated())
+            .count()
+    }
+
+    pub fn assert_topics_are_active(&self, ids: &[DecisionTopicId]) -> Result<(), String> {
+        for id in ids {
+            let topic = self
+                .decision_topics
+                .get(id)
+                .ok_or(format!("Decision topic {} not found", id))?;
+
+            if topic.is_deprecated() {
+                return Err(format!("Decision topic {} is deprecated", id));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn assert_topic_name_is_free(
+        &self,
+        name: &str,
+        except: Option<DecisionTopicId>,
+    ) -> Result<(), String> {
+        let taken = self.decision_topics.iter().any(|(id, topic)| {
+            Some(id) != except && topic.name.to_lowercase() == name.to_lowercase()
+        });
+
+        if taken {
+            return Err(format!("Decision topic {} already exists", name));
+        }
+
+        Ok(())
+    }
+
     fn generate_id(&mut self) -> DecisionTopicId {
-        let id = self.decision_topic_id_counter;
-        self.decision_topic_id_counter += 1;
+        let id = *self.decision_topic_id_counter.get();
+        self.decision_topic_id_counter
+            .set(id + 1)
+            .expect("Unable to store decision topic id counter");
 
         id
     }
 
+    fn followees_of_iter(&self, of: Principal) -> impl Iterator<Item = Principal> + '_ {
+        self.followees_of
+            .range((of, Principal::management_canister())..)
+            .take_while(move |((follower, _), _)| *follower == of)
+            .map(|((_, followee), _)| followee)
+    }
+
+    fn followers_of_iter(
+        &self,
+        of: Principal,
+    ) -> impl Iterator<Item = (Principal, DecisionTopicSet)> + '_ {
+        self.followers_of
+            .range((of, Principal::management_canister())..)
+            .take_while(move |((followee, _), _)| *followee == of)
+            .map(|((_, follower), topicset)| (follower, topicset))
+    }
+
     fn followers_of(
         &self,
         of: &Principal,
         parent: &mut DelegationTreeNode,
         loop_check: &mut BTreeSet<Principal>,
     ) {
-        if let Some(followers) = self.followers_of.get(of) {
-            for (follower, topicset) in followers {
-                if loop_check.contains(follower) {
-                    continue;
-                }
+        for (follower, topicset) in self.followers_of_iter(*of) {
+            if loop_check.contains(&follower) {
+                continue;
+            }
 
-                loop_check.insert(*follower);
+            loop_check.insert(follower);
 
-                let mut child = DelegationTreeNode {
-                    id: *follower,
-                    topicset: topicset.clone(),
-                    followers: Vec::new(),
-                };
-                self.followers_of(follower, &mut child, loop_check); // recursive invocation
+            let mut child = DelegationTreeNode {
+                id: follower,
+                topicset,
+                followers: Vec::new(),
+            };
+            self.followers_of(&follower, &mut child, loop_check); // recursive invocation
 
-                parent.followers.push(child);
-            }
+            parent.followers.push(child);
         }
     }
 
     fn followees_of(&self, of: &Principal, result: &mut BTreeMap<Principal, DecisionTopicSet>) {
-        if let Some(followees) = self.followees_of.get(of) {
-            for followee in followees {
-                let topicset = self
-                    .followers_of
-                    .get(followee)
-                    .expect("Unreacheable: followee followers list should not be empty")
-                    .get(of)
-                    .expect("Unreacheable: followee topicset should not be empty")
-                    .clone();
-
-                result.insert(*followee, topicset);
-            }
+        for followee in self.followees_of_iter(*of) {
+            let topicset = self
+                .followers_of
+                .get(&(followee, *of))
+                .expect("Unreacheable: followee topicset should not be empty");
+
+            result.insert(followee, topicset);
         }
     }
 }
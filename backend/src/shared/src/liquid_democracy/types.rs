@@ -1,6 +1,8 @@
 use candid::{CandidType, Deserialize, Principal};
 use garde::Validate;
 
+use crate::{impl_storable_for_candid, TimestampNs};
+
 pub type DecisionTopicId = u32;
 pub const LIQUID_DEMOCRACY_PROOF_MARKER: &str =
     "FMJ LIQUID DEMOCRACY CANISTER GET LIQUID DEMOCRACY PROOF RESPONSE";
@@ -10,6 +12,35 @@ pub struct DecisionTopic {
     pub id: DecisionTopicId,
     pub name: String,
     pub description: String,
+    // deprecated topics stay attached to old tasks, but can't be picked for new ones
+    pub deprecated_at: Option<TimestampNs>,
+    // topics that took over the deprecated one, delegations are migrated to them
+    pub successors: Option<Vec<DecisionTopicId>>,
+}
+
+impl DecisionTopic {
+    pub fn is_deprecated(&self) -> bool {
+        self.deprecated_at.is_some()
+    }
+}
+
+// for canisters, which only have a list of topics fetched from the liquid democracy canister
+pub fn assert_decision_topics_are_active(
+    topics: &[DecisionTopic],
+    ids: &[DecisionTopicId],
+) -> Result<(), String> {
+    for id in ids {
+        let topic = topics
+            .iter()
+            .find(|it| it.id == *id)
+            .ok_or(format!("Decision topic {} not found", id))?;
+
+        if topic.is_deprecated() {
+            return Err(format!("Decision topic {} is deprecated", id));
+        }
+    }
+
+    Ok(())
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -30,10 +61,44 @@ impl DecisionTopicSet {
         }
     }
 
+    pub fn contains(&self, id: DecisionTopicId) -> bool {
+        match &self {
+            DecisionTopicSet::It(it) => *it == id,
+            DecisionTopicSet::Not(s) => s.contains(id),
+            DecisionTopicSet::And(a, b) | DecisionTopicSet::Or(a, b) => {
+                a.contains(id) || b.contains(id)
+            }
+        }
+    }
+
+    // makes the set treat the successors the same way it treats the replaced topic,
+    // so a delegation keeps covering a topic after it was split or merged into others
+    pub fn replace(&self, id: DecisionTopicId, successors: &[DecisionTopicId]) -> Self {
+        match &self {
+            DecisionTopicSet::It(it) => {
+                if *it != id {
+                    return self.clone();
+                }
+
+                successors.iter().fold(Self::it(id), |acc, successor| {
+                    Self::or(acc, Self::it(*successor))
+                })
+            }
+            DecisionTopicSet::Not(s) => Self::not(s.replace(id, successors)),
+            DecisionTopicSet::And(a, b) => {
+                Self::and(a.replace(id, successors), b.replace(id, successors))
+            }
+            DecisionTopicSet::Or(a, b) => {
+                Self::or(a.replace(id, successors), b.replace(id, successors))
+            }
+        }
+    }
+
     pub fn it(id: DecisionTopicId) -> Self {
         Self::It(id)
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn not(set: DecisionTopicSet) -> Self {
         Self::Not(Box::new(set))
     }
@@ -59,6 +124,8 @@ impl DecisionTopicSet {
     }
 }
 
+impl_storable_for_candid!(DecisionTopic, DecisionTopicSet);
+
 #[derive(CandidType, Deserialize, Clone, Debug, Validate)]
 pub struct DelegationTreeNode {
     #[garde(skip)]
@@ -68,3 +135,49 @@ pub struct DelegationTreeNode {
     #[garde(dive)]
     pub followers: Vec<DelegationTreeNode>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_covers_successors() {
+        let set = DecisionTopicSet::it(1).replace(1, &[2, 3]);
+
+        assert!(set.matches(&[1]));
+        assert!(set.matches(&[2]));
+        assert!(set.matches(&[3]));
+        assert!(!set.matches(&[4]));
+    }
+
+    #[test]
+    fn replace_keeps_exclusions_and_intersections() {
+        let not = DecisionTopicSet::not_it(1).replace(1, &[2]);
+
+        assert!(!not.matches(&[2]));
+        assert!(not.matches(&[4]));
+
+        let and = DecisionTopicSet::and_it(1, 4).replace(1, &[2]);
+
+        assert!(and.matches(&[2, 4]));
+        assert!(!and.matches(&[2]));
+        assert!(!and.matches(&[4]));
+    }
+
+    #[test]
+    fn replace_ignores_other_topics() {
+        let set = DecisionTopicSet::or_it(4, 5).replace(1, &[2]);
+
+        assert!(!set.contains(1));
+        assert!(!set.contains(2));
+        assert!(set.matches(&[5]));
+    }
+
+    #[test]
+    fn replace_without_successors_changes_nothing() {
+        let set = DecisionTopicSet::it(1).replace(1, &[]);
+
+        assert!(set.matches(&[1]));
+        assert!(!set.matches(&[2]));
+    }
+}
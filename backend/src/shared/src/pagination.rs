@@ -19,3 +19,21 @@ pub struct PageResponse {
     #[garde(skip)]
     pub next: Option<Principal>,
 }
+
+// returns the [from, to) range of sequential indices and how many entries are left after the page
+pub fn page_range(len: u64, pagination: &PageRequest) -> (u64, u64, u32) {
+    let skip = pagination.skip as u64;
+    let take = pagination.take as u64;
+
+    if pagination.reversed {
+        let to = len.saturating_sub(skip);
+        let from = to.saturating_sub(take);
+
+        (from, to, from as u32)
+    } else {
+        let from = skip.min(len);
+        let to = (from + take).min(len);
+
+        (from, to, (len - to) as u32)
+    }
+}
@@ -1,6 +1,6 @@
 use std::{cell::RefCell, collections::BTreeMap, fmt::Debug, num::NonZeroUsize, time::Duration};
 
-use candid::{decode_args, CandidType, Nat, Principal};
+use candid::{decode_args, CandidType, Principal};
 use garde::Validate;
 use ic_cbor::CertificateToCbor;
 use ic_cdk_timers::set_timer_interval;
@@ -53,7 +53,7 @@ impl ReputationProof {
         )?;
 
         if get_reputation_proof_response.marker != REPUTATION_PROOF_MARKER {
-            return Err(format!("Inavalid reputation proof marker"));
+            return Err("Inavalid reputation proof marker".to_string());
         }
 
         // verify that the response is about the caller
@@ -63,9 +63,7 @@ impl ReputationProof {
             .id
             != caller
         {
-            return Err(format!(
-                "The caller is not the owner of the reputation proof"
-            ));
+            return Err("The caller is not the owner of the reputation proof".to_string());
         }
 
         self.body = Some(get_reputation_proof_response.proof);
@@ -107,19 +105,19 @@ impl ReputationProof {
 */
 
 pub fn rep_to_cooldown_ns(rep: &E8s) -> Option<u64> {
-    if &rep.0 >= &Nat::from(500_0000_0000u64) {
+    if rep.0 >= 500_0000_0000u64 {
         return Some(0);
     }
 
-    if &rep.0 >= &Nat::from(100_0000_0000u64) {
+    if rep.0 >= 100_0000_0000u64 {
         return Some(ONE_MINUTE_NS * 10);
     }
 
-    if &rep.0 >= &Nat::from(50_0000_0000u64) {
+    if rep.0 >= 50_0000_0000u64 {
         return Some(ONE_HOUR_NS);
     }
 
-    if &rep.0 >= &Nat::from(20_0000_0000u64) {
+    if rep.0 >= 20_0000_0000u64 {
         return Some(ONE_DAY_NS);
     }
 
@@ -163,12 +161,12 @@ impl ProfileProof {
         )?;
 
         if get_proof_response.marker != PROFILE_PROOFS_MARKER {
-            return Err(format!("Invalid profile proof marker"));
+            return Err("Invalid profile proof marker".to_string());
         }
 
         // verify that the response is about the caller
         if get_proof_response.proof.id != caller {
-            return Err(format!("The caller is not the owner of the profile proof"));
+            return Err("The caller is not the owner of the profile proof".to_string());
         }
 
         self.body = Some(get_proof_response.proof);
@@ -195,14 +193,12 @@ impl LiquidDemocracyProof {
         )?;
 
         if get_proof_response.marker != LIQUID_DEMOCRACY_PROOF_MARKER {
-            return Err(format!("Invalid liquid democracy proof marker"));
+            return Err("Invalid liquid democracy proof marker".to_string());
         }
 
         // verify that the response is about the caller
         if get_proof_response.tree_root.id != caller {
-            return Err(format!(
-                "The caller is not the owner of the liquid democracy proof"
-            ));
+            return Err("The caller is not the owner of the liquid democracy proof".to_string());
         }
 
         self.body = Some(get_proof_response.tree_root);
@@ -216,7 +212,7 @@ fn verify_and_decode<T: CandidType + DeserializeOwned + Debug>(
     issuer_id: &Principal,
     now: u64,
 ) -> Result<T, String> {
-    let cert = Certificate::from_cbor(&cert_raw).map_err(|e| e.to_string())?;
+    let cert = Certificate::from_cbor(cert_raw).map_err(|e| e.to_string())?;
     let request_id = request_id_of(&cert.tree)?;
 
     // check cache
@@ -235,10 +231,10 @@ fn verify_and_decode<T: CandidType + DeserializeOwned + Debug>(
         let timestamp_nanos = leb128::read::unsigned(&mut date_bytes).map_err(|e| e.to_string())?;
 
         if now > timestamp_nanos && (now - timestamp_nanos) >= PROOF_TTL_NS {
-            return Err(format!("The liquid democracy proof has expired"));
+            return Err("The liquid democracy proof has expired".to_string());
         }
     } else {
-        return Err(format!("Unable to find 'time' field in the certificate"));
+        return Err("Unable to find 'time' field in the certificate".to_string());
     }
 
     // verify that the certificate contains the expected response
@@ -248,11 +244,7 @@ fn verify_and_decode<T: CandidType + DeserializeOwned + Debug>(
         .lookup_path([REQUEST_STATUS_PATH, &request_id, REPLY_PATH])
     {
         LookupResult::Found(blob) => decode_args::<(T,)>(blob).map_err(|e| e.to_string())?.0,
-        _ => {
-            return Err(format!(
-                "Unable to find liquid democracy proof in the reply"
-            ))
-        }
+        _ => return Err("Unable to find liquid democracy proof in the reply".to_string()),
     };
 
     Ok(res)
@@ -262,8 +254,8 @@ fn request_id_of(tree: &HashTree<Vec<u8>>) -> Result<Vec<u8>, String> {
     Ok(tree
         .list_paths()
         .iter()
-        .find(|path| path[0].as_bytes() == &REQUEST_STATUS_BYTES)
-        .ok_or(format!("Can't find reply status in the certificate"))?
+        .find(|path| path[0].as_bytes() == REQUEST_STATUS_BYTES)
+        .ok_or("Can't find reply status in the certificate".to_string())?
         .get(1)
         .expect("Unreacheable")
         .as_bytes()
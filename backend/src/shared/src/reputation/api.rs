@@ -2,11 +2,21 @@ use candid::{CandidType, Principal};
 use garde::Validate;
 use serde::Deserialize;
 
-use crate::{e8s::E8s, proof::LiquidDemocracyProof, Guard, ENV_VARS};
+use crate::{
+    e8s::E8s,
+    escape_script_tag,
+    pagination::{PageRequest, PageResponse},
+    proof::LiquidDemocracyProof,
+    votings::types::VotingId,
+    Guard, ENV_VARS,
+};
 
 use super::{
     state::ReputationState,
-    types::{RepBalanceEntry, ReputationProofBody},
+    types::{
+        DecayParams, RepBalanceEntry, ReputationHistoryEntry, ReputationProofBody,
+        ReputationSnapshot, ReputationSnapshotId, SlashAmount,
+    },
 };
 
 #[derive(CandidType, Deserialize, Validate)]
@@ -25,7 +35,7 @@ impl Guard<ReputationState> for MintRepRequest {
         self.validate(&()).map_err(|e| e.to_string())?;
 
         if caller != ENV_VARS.tasks_canister_id && caller != ENV_VARS.work_reports_canister_id {
-            return Err(format!("Access denied"));
+            return Err("Access denied".to_string());
         }
 
         Ok(())
@@ -76,23 +86,39 @@ impl Guard<ReputationState> for GetTotalSupplyRequest {
 pub struct GetTotalSupplyResponse {
     #[garde(skip)]
     pub total_supply: E8s,
+    #[garde(skip)]
+    pub quadratic_total_supply: E8s,
 }
 
 #[derive(CandidType, Deserialize, Validate, Clone)]
 pub struct GetRepProofRequest {
     #[garde(dive)]
     pub liquid_democracy_proof: LiquidDemocracyProof,
+    // votings only accept proofs made against the snapshot taken at their start
+    #[garde(skip)]
+    pub snapshot_id: Option<ReputationSnapshotId>,
 }
 
 impl Guard<ReputationState> for GetRepProofRequest {
     fn validate_and_escape(
         &mut self,
-        _state: &ReputationState,
+        state: &ReputationState,
         caller: Principal,
         now: crate::TimestampNs,
     ) -> Result<(), String> {
         self.validate(&()).map_err(|e| e.to_string())?;
 
+        if let Some(snapshot_id) = &self.snapshot_id {
+            let snapshot = state
+                .snapshots
+                .get(snapshot_id)
+                .ok_or("The snapshot does not exist".to_string())?;
+
+            if snapshot.is_expired(now) {
+                return Err("The snapshot is expired".to_string());
+            }
+        }
+
         self.liquid_democracy_proof.assert_valid_for(caller, now)
     }
 }
@@ -104,3 +130,186 @@ pub struct GetRepProofResponse {
     #[garde(skip)]
     pub proof: ReputationProofBody,
 }
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct CreateSnapshotRequest {}
+
+impl Guard<ReputationState> for CreateSnapshotRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &ReputationState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct CreateSnapshotResponse {
+    #[garde(skip)]
+    pub snapshot: ReputationSnapshot,
+}
+
+// every voting releases its snapshot once, when it is finished
+#[derive(CandidType, Deserialize, Validate)]
+pub struct ReleaseSnapshotsRequest {
+    #[garde(length(min = 1, max = 1000))]
+    pub entries: Vec<(ReputationSnapshotId, u32)>,
+}
+
+impl Guard<ReputationState> for ReleaseSnapshotsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &ReputationState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct ReleaseSnapshotsResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SlashRepRequest {
+    #[garde(skip)]
+    pub target: Principal,
+    #[garde(dive)]
+    pub amount_or_fraction: SlashAmount,
+    #[garde(length(graphemes, min = 16, max = 4096))]
+    pub reason: String,
+    #[garde(skip)]
+    pub voting_id: VotingId,
+    // the action index, if the slash is a step of a batch voting
+    #[garde(skip)]
+    pub batch_step: Option<u32>,
+}
+
+impl Guard<ReputationState> for SlashRepRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &ReputationState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        self.amount_or_fraction.check_bounds()?;
+        self.reason = escape_script_tag(&self.reason);
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SlashRepResponse {
+    #[garde(skip)]
+    pub burned: E8s,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetReputationHistoryRequest {
+    // the latest entries go first, if no pagination is provided
+    #[garde(dive)]
+    pub pagination: Option<PageRequest>,
+}
+
+impl Guard<ReputationState> for GetReputationHistoryRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &ReputationState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetReputationHistoryResponse {
+    #[garde(skip)]
+    pub entries: Vec<ReputationHistoryEntry>,
+    #[garde(dive)]
+    pub pagination: PageResponse,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SetDecayParamsRequest {
+    #[garde(skip)]
+    pub params: DecayParams,
+}
+
+impl Guard<ReputationState> for SetDecayParamsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &ReputationState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        self.params.check_bounds()
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SetDecayParamsResponse {}
+
+// uses the current decay params, if none are provided
+#[derive(CandidType, Deserialize, Validate)]
+pub struct ProjectDecayRequest {
+    #[garde(skip)]
+    pub balance: E8s,
+    #[garde(range(min = 1, max = 120))]
+    pub periods: u32,
+    #[garde(skip)]
+    pub params: Option<DecayParams>,
+}
+
+impl Guard<ReputationState> for ProjectDecayRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &ReputationState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if let Some(params) = &self.params {
+            params.check_bounds()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct ProjectDecayResponse {
+    #[garde(skip)]
+    pub params: DecayParams,
+    // the balance at the end of each period
+    #[garde(skip)]
+    pub balances: Vec<E8s>,
+}
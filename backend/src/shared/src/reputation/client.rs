@@ -2,8 +2,10 @@ use candid::Principal;
 use ic_cdk::{api::call::CallResult, call};
 
 use super::api::{
-    GetBalanceRequest, GetBalanceResponse, GetRepProofRequest, GetRepProofResponse,
-    GetTotalSupplyRequest, GetTotalSupplyResponse, MintRepRequest, MintRepResponse,
+    CreateSnapshotRequest, CreateSnapshotResponse, GetBalanceRequest, GetBalanceResponse,
+    GetRepProofRequest, GetRepProofResponse, GetTotalSupplyRequest, GetTotalSupplyResponse,
+    MintRepRequest, MintRepResponse, ReleaseSnapshotsRequest, ReleaseSnapshotsResponse,
+    SetDecayParamsRequest, SetDecayParamsResponse, SlashRepRequest, SlashRepResponse,
 };
 
 pub struct ReputationCanisterClient {
@@ -25,6 +27,26 @@ impl ReputationCanisterClient {
             .map(|(it,)| it)
     }
 
+    #[allow(non_snake_case)]
+    pub async fn reputation__create_snapshot(
+        &self,
+        req: CreateSnapshotRequest,
+    ) -> CallResult<CreateSnapshotResponse> {
+        call(self.canister_id, "reputation__create_snapshot", (req,))
+            .await
+            .map(|(it,)| it)
+    }
+
+    #[allow(non_snake_case)]
+    pub async fn reputation__release_snapshots(
+        &self,
+        req: ReleaseSnapshotsRequest,
+    ) -> CallResult<ReleaseSnapshotsResponse> {
+        call(self.canister_id, "reputation__release_snapshots", (req,))
+            .await
+            .map(|(it,)| it)
+    }
+
     #[allow(non_snake_case)]
     pub async fn reputation__get_balance(
         &self,
@@ -51,4 +73,19 @@ impl ReputationCanisterClient {
             .await
             .map(|(it,)| it)
     }
+    #[allow(non_snake_case)]
+    pub async fn reputation__slash(&self, req: SlashRepRequest) -> CallResult<SlashRepResponse> {
+        call(self.canister_id, "reputation__slash", (req,))
+            .await
+            .map(|(it,)| it)
+    }
+    #[allow(non_snake_case)]
+    pub async fn reputation__set_decay_params(
+        &self,
+        req: SetDecayParamsRequest,
+    ) -> CallResult<SetDecayParamsResponse> {
+        call(self.canister_id, "reputation__set_decay_params", (req,))
+            .await
+            .map(|(it,)| it)
+    }
 }
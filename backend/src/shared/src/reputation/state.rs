@@ -1,30 +1,48 @@
 use candid::Principal;
-use ic_stable_structures::{
-    memory_manager::VirtualMemory, Cell, DefaultMemoryImpl, StableBTreeMap,
-};
+use ic_stable_structures::{Cell, StableBTreeMap};
 
 use crate::{
-    e8s::E8s, liquid_democracy::types::DelegationTreeNode, votings::types::ONE_HOUR_NS, TimestampNs,
+    e8s::E8s,
+    liquid_democracy::types::DelegationTreeNode,
+    pagination::{page_range, PageRequest, PageResponse},
+    stable_memory::Memory,
+    votings::types::ONE_HOUR_NS,
+    TimestampNs,
 };
 
 use super::{
     api::{
-        GetBalanceRequest, GetBalanceResponse, GetRepProofRequest, GetRepProofResponse,
-        GetTotalSupplyRequest, GetTotalSupplyResponse, MintRepRequest, MintRepResponse,
+        CreateSnapshotRequest, CreateSnapshotResponse, GetBalanceRequest, GetBalanceResponse,
+        GetRepProofRequest, GetRepProofResponse, GetReputationHistoryRequest,
+        GetReputationHistoryResponse, GetTotalSupplyRequest, GetTotalSupplyResponse,
+        MintRepRequest, MintRepResponse, ProjectDecayRequest, ProjectDecayResponse,
+        ReleaseSnapshotsRequest, ReleaseSnapshotsResponse, SetDecayParamsRequest,
+        SetDecayParamsResponse, SlashRepRequest, SlashRepResponse,
     },
     types::{
-        RepBalanceEntry, ReputationDelegationTreeNode, ReputationProofBody, REPUTATION_PROOF_MARKER,
+        DecayParams, RepBalanceEntry, ReputationDelegationTreeNode, ReputationHistoryEntry,
+        ReputationHistoryEvent, ReputationProofBody, ReputationSnapshot, ReputationSnapshotId,
+        REPUTATION_PROOF_MARKER,
     },
 };
 
-pub type Memory = VirtualMemory<DefaultMemoryImpl>;
 pub const CACHE_TTL_NS: TimestampNs = ONE_HOUR_NS * 8;
+// how many of the latest history entries are returned, if no pagination is provided
+const HISTORY_DEFAULT_PAGE_LEN: u32 = 100;
 
 pub struct ReputationState {
     pub balances: StableBTreeMap<Principal, RepBalanceEntry, Memory>,
     pub total_supply: Cell<E8s, Memory>,
+    pub quadratic_total_supply: Cell<E8s, Memory>,
     pub decay_start_key: Cell<Option<Principal>, Memory>,
     pub initialized: Cell<bool, Memory>,
+    pub snapshots: StableBTreeMap<ReputationSnapshotId, ReputationSnapshot, Memory>,
+    // balances as they were at the moment of the snapshot, only for accounts changed since then
+    pub snapshot_balances: StableBTreeMap<(ReputationSnapshotId, Principal), E8s, Memory>,
+    pub snapshot_id_generator: Cell<ReputationSnapshotId, Memory>,
+    // entry ids are sequential
+    pub history: StableBTreeMap<u64, ReputationHistoryEntry, Memory>,
+    pub decay_params: Cell<DecayParams, Memory>,
 }
 
 impl ReputationState {
@@ -43,6 +61,30 @@ impl ReputationState {
         self.total_supply
             .set(E8s::one())
             .expect("Unable to store total supply");
+
+        self.quadratic_total_supply
+            .set(E8s::one())
+            .expect("Unable to store quadratic total supply");
+    }
+
+    // should be called after upgrades, since balances could change without the quadratic total supply being tracked
+    pub fn recalculate_quadratic_total_supply(&mut self) {
+        let quadratic_total_supply = self.balances.iter().fold(E8s::zero(), |acc, (_, entry)| {
+            acc + entry.balance.sqrt_precise()
+        });
+
+        self.quadratic_total_supply
+            .set(quadratic_total_supply)
+            .expect("Unable to store quadratic total supply");
+    }
+
+    fn update_quadratic_total_supply(&mut self, prev_balance: &E8s, new_balance: &E8s) {
+        let quadratic_total_supply = self.quadratic_total_supply.get() + new_balance.sqrt_precise()
+            - prev_balance.sqrt_precise();
+
+        self.quadratic_total_supply
+            .set(quadratic_total_supply)
+            .expect("Unable to store quadratic total supply");
     }
 
     pub fn mint(&mut self, req: MintRepRequest, now: TimestampNs) -> MintRepResponse {
@@ -52,6 +94,10 @@ impl ReputationState {
             total += &qty;
 
             let prev_opt = self.balances.get(&account);
+            let prev_balance = prev_opt
+                .as_ref()
+                .map(|it| it.balance.clone())
+                .unwrap_or_default();
 
             let entry = if let Some(mut prev) = prev_opt {
                 prev.balance += qty;
@@ -62,6 +108,8 @@ impl ReputationState {
                 RepBalanceEntry::new(qty, now)
             };
 
+            self.remember_prev_balance(account, &prev_balance);
+            self.update_quadratic_total_supply(&prev_balance, &entry.balance);
             self.balances.insert(account, entry);
         }
 
@@ -72,6 +120,75 @@ impl ReputationState {
         MintRepResponse {}
     }
 
+    // the activity timestamp is kept as is, so the slash does not postpone the decay
+    pub fn slash(&mut self, req: SlashRepRequest, now: TimestampNs) -> SlashRepResponse {
+        let mut entry = self.balances.get(&req.target).unwrap_or_default();
+        let prev_balance = entry.balance.clone();
+
+        let burned = req.amount_or_fraction.calc_burned(&prev_balance);
+
+        if burned != E8s::zero() {
+            entry.balance -= &burned;
+
+            self.remember_prev_balance(req.target, &prev_balance);
+            self.update_quadratic_total_supply(&prev_balance, &entry.balance);
+
+            if entry.balance == E8s::zero() {
+                self.balances.remove(&req.target);
+            } else {
+                self.balances.insert(req.target, entry);
+            }
+
+            self.total_supply
+                .set(self.total_supply.get() - &burned)
+                .expect("Unable to store total supply");
+        }
+
+        let id = self.history.len();
+        self.history.insert(
+            id,
+            ReputationHistoryEntry {
+                id,
+                account: req.target,
+                timestamp: now,
+                event: ReputationHistoryEvent::Slashed {
+                    voting_id: req.voting_id,
+                    batch_step: req.batch_step,
+                    amount_or_fraction: req.amount_or_fraction,
+                    burned: burned.clone(),
+                    reason: req.reason,
+                },
+            },
+        );
+
+        SlashRepResponse { burned }
+    }
+
+    pub fn get_history(&self, req: GetReputationHistoryRequest) -> GetReputationHistoryResponse {
+        let pagination = req.pagination.unwrap_or(PageRequest {
+            reversed: true,
+            skip: 0,
+            take: HISTORY_DEFAULT_PAGE_LEN,
+        });
+
+        let (from, to, left) = page_range(self.history.len(), &pagination);
+
+        let mut entries: Vec<_> = self
+            .history
+            .range(from..to)
+            .map(|(_, entry)| entry)
+            .collect();
+
+        if pagination.reversed {
+            entries.reverse();
+        }
+
+        GetReputationHistoryResponse {
+            entries,
+            pagination: PageResponse { left, next: None },
+        }
+    }
+
     // returns true if should reschedule another decay
     pub fn decay(&mut self, items_num: u64, now: TimestampNs) -> bool {
         let mut iter = if let Some(start) = self.decay_start_key.get() {
@@ -87,12 +204,16 @@ impl ReputationState {
         let mut entries_to_update = Vec::new();
         let mut last_decay_key = None;
         let mut total_decay_amount = E8s::zero();
+        let mut quadratic_updates = Vec::new();
+        let params = self.decay_params.get();
 
         for _ in 0..items_num {
             if let Some((account, mut entry)) = iter.next() {
-                if entry.should_decay(now) {
-                    let (should_delete, decay_amount) = entry.decay();
+                if entry.should_decay(params, now) {
+                    let prev_balance = entry.balance.clone();
+                    let (should_delete, decay_amount) = entry.decay(&params.policy);
                     total_decay_amount += decay_amount;
+                    quadratic_updates.push((account, prev_balance, entry.balance.clone()));
 
                     if should_delete {
                         entries_to_delete.push(account);
@@ -118,6 +239,11 @@ impl ReputationState {
             self.balances.insert(account, entry);
         }
 
+        for (account, prev_balance, new_balance) in quadratic_updates {
+            self.remember_prev_balance(account, &prev_balance);
+            self.update_quadratic_total_supply(&prev_balance, &new_balance);
+        }
+
         self.total_supply
             .set(self.total_supply.get() - total_decay_amount)
             .expect("Unable to store total supply");
@@ -129,6 +255,126 @@ impl ReputationState {
         should_reschedule
     }
 
+    pub fn set_decay_params(&mut self, req: SetDecayParamsRequest) -> SetDecayParamsResponse {
+        self.decay_params
+            .set(req.params)
+            .expect("Unable to store decay params");
+
+        SetDecayParamsResponse {}
+    }
+
+    pub fn project_decay(&self, req: ProjectDecayRequest) -> ProjectDecayResponse {
+        let params = req
+            .params
+            .unwrap_or_else(|| self.decay_params.get().clone());
+        let balances = params.project(req.balance, req.periods);
+
+        ProjectDecayResponse { params, balances }
+    }
+
+    pub fn create_snapshot(
+        &mut self,
+        _req: CreateSnapshotRequest,
+        now: TimestampNs,
+    ) -> CreateSnapshotResponse {
+        self.remove_expired_snapshots(now);
+
+        if let Some((id, mut snapshot)) = self.snapshots.last_key_value() {
+            if snapshot.can_be_shared(now) {
+                snapshot.holders += 1;
+                self.snapshots.insert(id, snapshot.clone());
+
+                return CreateSnapshotResponse { snapshot };
+            }
+        }
+
+        let id = *self.snapshot_id_generator.get();
+        self.snapshot_id_generator
+            .set(id + 1)
+            .expect("Unable to store snapshot id generator");
+
+        let snapshot = ReputationSnapshot {
+            id,
+            created_at: now,
+            total_supply: self.total_supply.get().clone(),
+            quadratic_total_supply: self.quadratic_total_supply.get().clone(),
+            holders: 1,
+        };
+
+        self.snapshots.insert(id, snapshot.clone());
+
+        CreateSnapshotResponse { snapshot }
+    }
+
+    fn remove_expired_snapshots(&mut self, now: TimestampNs) {
+        let expired: Vec<_> = self
+            .snapshots
+            .iter()
+            .filter(|(_, it)| it.is_expired(now))
+            .map(|(id, _)| id)
+            .collect();
+
+        for id in expired {
+            self.remove_snapshot(id);
+        }
+    }
+
+    pub fn release_snapshots(&mut self, req: ReleaseSnapshotsRequest) -> ReleaseSnapshotsResponse {
+        for (id, times) in req.entries {
+            let mut snapshot = match self.snapshots.get(&id) {
+                Some(it) => it,
+                None => continue,
+            };
+
+            snapshot.holders = snapshot.holders.saturating_sub(times);
+
+            if snapshot.holders == 0 {
+                self.remove_snapshot(id);
+            } else {
+                self.snapshots.insert(id, snapshot);
+            }
+        }
+
+        ReleaseSnapshotsResponse {}
+    }
+
+    fn remove_snapshot(&mut self, id: ReputationSnapshotId) {
+        let keys: Vec<_> = self
+            .snapshot_balances
+            .range((id, Principal::management_canister())..)
+            .take_while(|((snapshot_id, _), _)| *snapshot_id == id)
+            .map(|(key, _)| key)
+            .collect();
+
+        for key in keys {
+            self.snapshot_balances.remove(&key);
+        }
+
+        self.snapshots.remove(&id);
+    }
+
+    // only the first change after a snapshot is remembered - that's the balance at the moment of the snapshot
+    fn remember_prev_balance(&mut self, account: Principal, prev_balance: &E8s) {
+        let snapshot_ids: Vec<_> = self.snapshots.iter().map(|(id, _)| id).collect();
+
+        for id in snapshot_ids {
+            if !self.snapshot_balances.contains_key(&(id, account)) {
+                self.snapshot_balances
+                    .insert((id, account), prev_balance.clone());
+            }
+        }
+    }
+
+    fn balance_at(&self, snapshot_id: Option<ReputationSnapshotId>, account: Principal) -> E8s {
+        if let Some(id) = snapshot_id {
+            if let Some(balance) = self.snapshot_balances.get(&(id, account)) {
+                return balance;
+            }
+        }
+
+        self.balances.get(&account).unwrap_or_default().balance
+    }
+
     pub fn get_balances(&self, req: GetBalanceRequest) -> GetBalanceResponse {
         let entries = req
             .ids
@@ -143,18 +389,28 @@ impl ReputationState {
     pub fn get_total_supply(&self, _req: GetTotalSupplyRequest) -> GetTotalSupplyResponse {
         GetTotalSupplyResponse {
             total_supply: self.total_supply.get().clone(),
+            quadratic_total_supply: self.quadratic_total_supply.get().clone(),
         }
     }
 
     pub fn get_rep_proof(&self, req: GetRepProofRequest) -> GetRepProofResponse {
         let cur_ld_node = req.liquid_democracy_proof.body.expect("UNREACHEABLE");
-        let rep_root = self.map_ld_proof(cur_ld_node);
-
-        let reputation_total_supply = self.total_supply.get().clone();
+        let rep_root = self.map_ld_proof(cur_ld_node, req.snapshot_id);
+
+        let (reputation_total_supply, reputation_quadratic_total_supply) =
+            match req.snapshot_id.and_then(|id| self.snapshots.get(&id)) {
+                Some(snapshot) => (snapshot.total_supply, snapshot.quadratic_total_supply),
+                None => (
+                    self.total_supply.get().clone(),
+                    self.quadratic_total_supply.get().clone(),
+                ),
+            };
 
         let rep_proof = ReputationProofBody {
             reputation_total_supply,
+            reputation_quadratic_total_supply,
             reputation_delegation_tree: rep_root,
+            snapshot_id: req.snapshot_id,
         };
 
         GetRepProofResponse {
@@ -163,17 +419,17 @@ impl ReputationState {
         }
     }
 
-    fn map_ld_proof(&self, ld_proof_node: DelegationTreeNode) -> ReputationDelegationTreeNode {
-        let reputation = self
-            .balances
-            .get(&ld_proof_node.id)
-            .unwrap_or_default()
-            .balance;
+    fn map_ld_proof(
+        &self,
+        ld_proof_node: DelegationTreeNode,
+        snapshot_id: Option<ReputationSnapshotId>,
+    ) -> ReputationDelegationTreeNode {
+        let reputation = self.balance_at(snapshot_id, ld_proof_node.id);
 
         let followers = ld_proof_node
             .followers
             .into_iter()
-            .map(|it| self.map_ld_proof(it))
+            .map(|it| self.map_ld_proof(it, snapshot_id))
             .collect();
 
         ReputationDelegationTreeNode {
@@ -184,3 +440,151 @@ impl ReputationState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use candid::Nat;
+    use ic_stable_structures::{
+        memory_manager::{MemoryId, MemoryManager},
+        DefaultMemoryImpl,
+    };
+
+    use crate::{
+        reputation::types::SlashAmount,
+        votings::types::{VoteWeighting, VotingId},
+    };
+
+    use super::*;
+
+    fn e8s(whole: u64) -> E8s {
+        E8s(Nat::from(whole * 1_0000_0000))
+    }
+
+    fn principal(n: u8) -> Principal {
+        Principal::from_slice(&[n; 29])
+    }
+
+    fn state() -> ReputationState {
+        let m = MemoryManager::init(DefaultMemoryImpl::default());
+
+        ReputationState {
+            balances: StableBTreeMap::init(m.get(MemoryId::new(0))),
+            total_supply: Cell::init(m.get(MemoryId::new(1)), E8s::zero()).unwrap(),
+            decay_start_key: Cell::init(m.get(MemoryId::new(2)), None).unwrap(),
+            initialized: Cell::init(m.get(MemoryId::new(3)), false).unwrap(),
+            quadratic_total_supply: Cell::init(m.get(MemoryId::new(4)), E8s::zero()).unwrap(),
+            snapshots: StableBTreeMap::init(m.get(MemoryId::new(5))),
+            snapshot_balances: StableBTreeMap::init(m.get(MemoryId::new(6))),
+            snapshot_id_generator: Cell::init(m.get(MemoryId::new(7)), 0).unwrap(),
+            history: StableBTreeMap::init(m.get(MemoryId::new(8))),
+            decay_params: Cell::init(m.get(MemoryId::new(9)), DecayParams::default()).unwrap(),
+        }
+    }
+
+    fn weighted_sum(s: &ReputationState, weighting: VoteWeighting) -> E8s {
+        s.balances.iter().fold(E8s::zero(), |acc, (_, entry)| {
+            acc + weighting.apply(&entry.balance)
+        })
+    }
+
+    #[test]
+    fn quadratic_weighting_applies_square_root() {
+        assert_eq!(VoteWeighting::Linear.apply(&e8s(9)), e8s(9));
+        assert_eq!(VoteWeighting::Quadratic.apply(&e8s(9)), e8s(3));
+        assert_eq!(
+            VoteWeighting::Quadratic.apply(&E8s(Nat::from(2500_0000u64))),
+            E8s(Nat::from(5000_0000u64))
+        );
+        assert_eq!(VoteWeighting::Quadratic.apply(&E8s::zero()), E8s::zero());
+    }
+
+    #[test]
+    fn quadratic_total_supply_matches_weighted_votes() {
+        let mut s = state();
+        s.init(principal(0), 0);
+
+        s.mint(
+            MintRepRequest {
+                entries: vec![(principal(1), e8s(4)), (principal(2), e8s(3))],
+            },
+            0,
+        );
+        s.mint(
+            MintRepRequest {
+                entries: vec![(principal(1), e8s(5))],
+            },
+            1,
+        );
+        s.slash(
+            SlashRepRequest {
+                target: principal(2),
+                amount_or_fraction: SlashAmount::Fraction(E8s::f0_5()),
+                reason: "Slashed for the test purposes".to_string(),
+                voting_id: VotingId::Motion([0; 32]),
+                batch_step: None,
+            },
+            2,
+        );
+        s.slash(
+            SlashRepRequest {
+                target: principal(0),
+                amount_or_fraction: SlashAmount::Fraction(E8s::one()),
+                reason: "Slashed for the test purposes".to_string(),
+                voting_id: VotingId::Motion([1; 32]),
+                batch_step: None,
+            },
+            3,
+        );
+
+        let expected = weighted_sum(&s, VoteWeighting::Quadratic);
+        assert_eq!(*s.quadratic_total_supply.get(), expected);
+        assert_eq!(
+            *s.total_supply.get(),
+            weighted_sum(&s, VoteWeighting::Linear)
+        );
+
+        s.recalculate_quadratic_total_supply();
+        assert_eq!(*s.quadratic_total_supply.get(), expected);
+    }
+
+    #[test]
+    fn batched_slashes_are_told_apart_by_step() {
+        let mut s = state();
+        s.init(principal(0), 0);
+        s.mint(
+            MintRepRequest {
+                entries: vec![(principal(1), e8s(10))],
+            },
+            0,
+        );
+
+        let batch_id = VotingId::Batch([1; 32]);
+
+        for step in 0..2 {
+            s.slash(
+                SlashRepRequest {
+                    target: principal(1),
+                    amount_or_fraction: SlashAmount::Amount(e8s(1)),
+                    reason: "Slashed for the test purposes".to_string(),
+                    voting_id: batch_id,
+                    batch_step: Some(step),
+                },
+                1,
+            );
+        }
+
+        let steps: Vec<_> = s
+            .history
+            .iter()
+            .map(|(_, it)| match it.event {
+                ReputationHistoryEvent::Slashed {
+                    voting_id,
+                    batch_step,
+                    ..
+                } => (voting_id, batch_step),
+            })
+            .collect();
+
+        assert_eq!(steps, vec![(batch_id, Some(0)), (batch_id, Some(1))]);
+    }
+}
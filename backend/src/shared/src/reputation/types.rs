@@ -5,10 +5,99 @@ use num_bigint::BigUint;
 use serde::Deserialize;
 
 use crate::{
-    e8s::E8s, liquid_democracy::types::DecisionTopicSet, votings::types::ONE_WEEK_NS, TimestampNs,
+    e8s::E8s,
+    impl_storable_for_candid,
+    liquid_democracy::types::DecisionTopicSet,
+    votings::types::{VotingId, ONE_DAY_NS, ONE_HOUR_NS, ONE_MONTH_NS, ONE_WEEK_NS},
+    DurationNs, TimestampNs,
 };
 
 pub const REPUTATION_PROOF_MARKER: &str = "FMJ REPUTATION CANISTER GET REPUTATION PROOF RESPONSE";
+// longer than the longest possible voting (including the reveal phase)
+pub const SNAPSHOT_TTL_NS: DurationNs = ONE_WEEK_NS * 6;
+// votings started within this period share a snapshot, so only a few snapshots have to remember changed balances
+pub const SNAPSHOT_SHARING_PERIOD_NS: DurationNs = ONE_HOUR_NS;
+
+pub type ReputationSnapshotId = u64;
+
+// balances are not copied when a snapshot is taken - only the previous values of the ones that change afterwards are stored
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ReputationSnapshot {
+    pub id: ReputationSnapshotId,
+    pub created_at: TimestampNs,
+    pub total_supply: E8s,
+    pub quadratic_total_supply: E8s,
+    // how many votings use the snapshot, it is removed once all of them are finished
+    pub holders: u32,
+}
+
+impl ReputationSnapshot {
+    pub fn can_be_shared(&self, now: TimestampNs) -> bool {
+        now < self.created_at + SNAPSHOT_SHARING_PERIOD_NS
+    }
+
+    // only catches snapshots whose votings never released them
+    pub fn is_expired(&self, now: TimestampNs) -> bool {
+        now > self.created_at + SNAPSHOT_SHARING_PERIOD_NS + SNAPSHOT_TTL_NS
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate, Clone, Debug)]
+pub enum SlashAmount {
+    Amount(#[garde(skip)] E8s),
+    // normalized, 1.0 burns the whole balance
+    Fraction(#[garde(skip)] E8s),
+}
+
+impl SlashAmount {
+    pub fn check_bounds(&self) -> Result<(), String> {
+        let (value, max) = match self {
+            SlashAmount::Amount(amount) => (amount, None),
+            SlashAmount::Fraction(fraction) => (fraction, Some(E8s::one())),
+        };
+
+        if *value == E8s::zero() {
+            return Err("Nothing to slash".to_string());
+        }
+
+        if let Some(max) = max {
+            if *value > max {
+                return Err("The fraction should not exceed 1.0".to_string());
+            }
+        }
+
+        Ok(())
+    }
+
+    // can't burn more than there is
+    pub fn calc_burned(&self, balance: &E8s) -> E8s {
+        match self {
+            SlashAmount::Amount(amount) => amount.clone().min(balance.clone()),
+            SlashAmount::Fraction(fraction) => (balance * fraction).min(balance.clone()),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum ReputationHistoryEvent {
+    Slashed {
+        voting_id: VotingId,
+        batch_step: Option<u32>,
+        amount_or_fraction: SlashAmount,
+        burned: E8s,
+        reason: String,
+    },
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ReputationHistoryEntry {
+    pub id: u64,
+    pub account: Principal,
+    pub timestamp: TimestampNs,
+    pub event: ReputationHistoryEvent,
+}
+
+impl_storable_for_candid!(ReputationSnapshot, ReputationHistoryEntry, DecayParams);
 
 #[derive(CandidType, Deserialize, Validate, Clone, Debug, Default)]
 pub struct RepBalanceEntry {
@@ -26,26 +115,26 @@ impl RepBalanceEntry {
         }
     }
 
-    pub fn should_decay(&self, now: TimestampNs) -> bool {
+    pub fn should_decay(&self, params: &DecayParams, now: TimestampNs) -> bool {
         if now < self.updated_at {
             return false;
         }
 
-        return now - self.updated_at >= ONE_WEEK_NS;
+        now - self.updated_at >= params.grace_period_ns
     }
 
     // returns true if the entry should be deleted (the balance decayed completely)
-    pub fn decay(&mut self) -> (bool, E8s) {
-        let decay_amount = self.balance.sqrt();
+    pub fn decay(&mut self, policy: &DecayPolicy) -> (bool, E8s) {
+        let decay_amount = policy.calc_decay(&self.balance);
 
         self.balance -= &decay_amount;
 
-        return (self.balance == E8s::zero(), decay_amount);
+        (self.balance == E8s::zero(), decay_amount)
     }
 }
 
 impl Storable for RepBalanceEntry {
-    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+    fn to_bytes(&self) -> std::borrow::Cow<'_, [u8]> {
         let mut buf = Vec::new();
 
         buf.extend_from_slice(&self.updated_at.to_le_bytes());
@@ -59,7 +148,7 @@ impl Storable for RepBalanceEntry {
         buf.push(balance_buf.len() as u8);
         buf.extend_from_slice(&balance_buf);
 
-        return std::borrow::Cow::Owned(buf);
+        std::borrow::Cow::Owned(buf)
     }
 
     fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
@@ -85,6 +174,96 @@ impl Storable for RepBalanceEntry {
     };
 }
 
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum DecayPolicy {
+    None,
+    // removes the square root of the balance, truncated to whole units, so balances below 1.0 never decay
+    Sqrt,
+    // the balance halves every `half_life_periods` periods
+    ExponentialHalfLife { half_life_periods: u32 },
+    // removes the same amount every period, until nothing is left
+    Linear { amount_per_period: E8s },
+}
+
+impl DecayPolicy {
+    // never more than the balance itself
+    pub fn calc_decay(&self, balance: &E8s) -> E8s {
+        let decay = match self {
+            DecayPolicy::None => E8s::zero(),
+            DecayPolicy::Sqrt => balance.sqrt(),
+            DecayPolicy::ExponentialHalfLife { half_life_periods } => {
+                let kept = 0.5f64.powf(1.0 / *half_life_periods as f64);
+                let decayed_normalized = E8s(Nat::from(((1.0 - kept) * 1_0000_0000f64) as u64));
+
+                balance * decayed_normalized
+            }
+            DecayPolicy::Linear { amount_per_period } => amount_per_period.clone(),
+        };
+
+        decay.min(balance.clone())
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct DecayParams {
+    pub policy: DecayPolicy,
+    // how often decay rounds happen
+    pub period_ns: DurationNs,
+    // balances updated more recently than that are skipped by a round
+    pub grace_period_ns: DurationNs,
+}
+
+impl Default for DecayParams {
+    // the policy the canister had before it was made configurable
+    fn default() -> Self {
+        Self {
+            policy: DecayPolicy::Sqrt,
+            period_ns: ONE_MONTH_NS,
+            grace_period_ns: ONE_WEEK_NS,
+        }
+    }
+}
+
+impl DecayParams {
+    pub fn check_bounds(&self) -> Result<(), String> {
+        if self.period_ns < ONE_DAY_NS || self.period_ns > ONE_WEEK_NS * 52 {
+            return Err("Decay period should be between one day and one year".to_string());
+        }
+
+        if self.grace_period_ns > ONE_WEEK_NS * 52 {
+            return Err("Grace period should not exceed one year".to_string());
+        }
+
+        match &self.policy {
+            DecayPolicy::ExponentialHalfLife { half_life_periods } => {
+                if *half_life_periods == 0 || *half_life_periods > 1000 {
+                    return Err("Half-life should be between 1 and 1000 periods".to_string());
+                }
+            }
+            DecayPolicy::Linear { amount_per_period } => {
+                if *amount_per_period == E8s::zero() {
+                    return Err("Linear decay amount should not be zero".to_string());
+                }
+            }
+            DecayPolicy::None | DecayPolicy::Sqrt => {}
+        }
+
+        Ok(())
+    }
+
+    // the balance at the end of each of the next periods, if it does not change otherwise
+    pub fn project(&self, balance: E8s, periods: u32) -> Vec<E8s> {
+        let mut balance = balance;
+
+        (0..periods)
+            .map(|_| {
+                balance -= self.policy.calc_decay(&balance);
+                balance.clone()
+            })
+            .collect()
+    }
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug, Validate)]
 pub struct ReputationDelegationTreeNode {
     #[garde(skip)]
@@ -99,7 +278,7 @@ pub struct ReputationDelegationTreeNode {
 
 impl ReputationDelegationTreeNode {
     pub fn traverse<F: FnMut(&Self, u32) -> bool>(&self, f: &mut F, depth: u32) {
-        if !f(&self, depth) {
+        if !f(self, depth) {
             return;
         }
 
@@ -113,6 +292,111 @@ impl ReputationDelegationTreeNode {
 pub struct ReputationProofBody {
     #[garde(skip)]
     pub reputation_total_supply: E8s,
+    // the sum of square roots of all balances, used by quadratic votings
+    #[garde(skip)]
+    pub reputation_quadratic_total_supply: E8s,
     #[garde(dive)]
     pub reputation_delegation_tree: ReputationDelegationTreeNode,
+    // None means the balances and the total supplies are the current ones
+    #[garde(skip)]
+    pub snapshot_id: Option<ReputationSnapshotId>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn e8s(whole: u64) -> E8s {
+        E8s(Nat::from(whole * 1_0000_0000))
+    }
+
+    fn params(policy: DecayPolicy) -> DecayParams {
+        DecayParams {
+            policy,
+            ..DecayParams::default()
+        }
+    }
+
+    #[test]
+    fn calc_burned_is_capped_by_balance() {
+        assert_eq!(SlashAmount::Amount(e8s(30)).calc_burned(&e8s(100)), e8s(30));
+        assert_eq!(
+            SlashAmount::Amount(e8s(300)).calc_burned(&e8s(100)),
+            e8s(100)
+        );
+
+        assert_eq!(
+            SlashAmount::Fraction(E8s::f0_25()).calc_burned(&e8s(100)),
+            e8s(25)
+        );
+        assert_eq!(
+            SlashAmount::Fraction(E8s::one()).calc_burned(&e8s(100)),
+            e8s(100)
+        );
+        assert_eq!(
+            SlashAmount::Fraction(E8s::f0_5()).calc_burned(&E8s::zero()),
+            E8s::zero()
+        );
+    }
+
+    #[test]
+    fn slash_amount_bounds() {
+        assert!(SlashAmount::Amount(e8s(1000)).check_bounds().is_ok());
+        assert!(SlashAmount::Fraction(E8s::one()).check_bounds().is_ok());
+        assert!(SlashAmount::Amount(E8s::zero()).check_bounds().is_err());
+        assert!(SlashAmount::Fraction(E8s::two()).check_bounds().is_err());
+    }
+
+    #[test]
+    fn calc_decay_per_policy() {
+        assert_eq!(DecayPolicy::None.calc_decay(&e8s(100)), E8s::zero());
+
+        assert_eq!(DecayPolicy::Sqrt.calc_decay(&e8s(100)), e8s(10));
+        assert_eq!(DecayPolicy::Sqrt.calc_decay(&E8s::f0_5()), E8s::zero());
+
+        let half_life = DecayPolicy::ExponentialHalfLife {
+            half_life_periods: 1,
+        };
+        assert_eq!(half_life.calc_decay(&e8s(100)), e8s(50));
+
+        let linear = DecayPolicy::Linear {
+            amount_per_period: e8s(30),
+        };
+        assert_eq!(linear.calc_decay(&e8s(100)), e8s(30));
+    }
+
+    #[test]
+    fn calc_decay_never_exceeds_balance() {
+        let linear = DecayPolicy::Linear {
+            amount_per_period: e8s(30),
+        };
+
+        assert_eq!(linear.calc_decay(&e8s(10)), e8s(10));
+        assert_eq!(linear.calc_decay(&E8s::zero()), E8s::zero());
+    }
+
+    #[test]
+    fn project_applies_decay_every_period() {
+        let linear = params(DecayPolicy::Linear {
+            amount_per_period: e8s(40),
+        });
+        assert_eq!(
+            linear.project(e8s(100), 4),
+            vec![e8s(60), e8s(20), E8s::zero(), E8s::zero()]
+        );
+
+        let half_life = params(DecayPolicy::ExponentialHalfLife {
+            half_life_periods: 1,
+        });
+        assert_eq!(
+            half_life.project(e8s(100), 3),
+            vec![e8s(50), e8s(25), E8s(Nat::from(12_5000_0000u64))]
+        );
+
+        assert_eq!(
+            params(DecayPolicy::None).project(e8s(100), 2),
+            vec![e8s(100), e8s(100)]
+        );
+        assert!(params(DecayPolicy::Sqrt).project(e8s(100), 0).is_empty());
+    }
 }
@@ -0,0 +1,96 @@
+use candid::CandidType;
+use ic_cdk::{
+    api::stable::{stable_read, stable_size},
+    storage::stable_restore,
+};
+use ic_stable_structures::{memory_manager::VirtualMemory, DefaultMemoryImpl};
+use serde::de::DeserializeOwned;
+
+pub type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+// the header every memory manager writes at the beginning of the stable memory
+const MEMORY_MANAGER_MAGIC: &[u8; 3] = b"MGR";
+
+/*
+    Canisters that used to keep their state on the heap were saving it as a single candid blob in pre_upgrade.
+    This function has to be called in post_upgrade BEFORE the state (and its memory manager) is touched for the
+    first time, because the memory manager overwrites the blob with its own layout once initialized.
+*/
+pub fn restore_legacy_state<T: CandidType + DeserializeOwned>() -> Option<T> {
+    if stable_size() == 0 {
+        return None;
+    }
+
+    let mut magic = [0u8; 3];
+    stable_read(0, &mut magic);
+
+    if &magic == MEMORY_MANAGER_MAGIC {
+        return None;
+    }
+
+    let (state,): (T,) = stable_restore().expect("Unable to restore the legacy state");
+
+    Some(state)
+}
+
+/// Implements [ic_stable_structures::Storable] by encoding the type with candid
+#[macro_export]
+macro_rules! impl_storable_for_candid {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl ::ic_stable_structures::Storable for $t {
+                fn to_bytes(&self) -> ::std::borrow::Cow<'_, [u8]> {
+                    ::std::borrow::Cow::Owned(
+                        ::candid::encode_one(self).expect("Unable to encode a stable value"),
+                    )
+                }
+
+                fn from_bytes(bytes: ::std::borrow::Cow<'_, [u8]>) -> Self {
+                    ::candid::decode_one(&bytes).expect("Unable to decode a stable value")
+                }
+
+                const BOUND: ::ic_stable_structures::storable::Bound =
+                    ::ic_stable_structures::storable::Bound::Unbounded;
+            }
+        )*
+    };
+}
+
+/// Implements [ic_stable_structures::Storable] for fieldless enums as a single byte with explicit values.
+/// Unlike candid, the encoding does not change when new variants are added, so such enums can be used as keys.
+/// The values of existing variants should never change.
+#[macro_export]
+macro_rules! impl_storable_for_u8_enum {
+    ($t:ident { $($variant:ident = $tag:literal),* $(,)? }) => {
+        impl $t {
+            pub fn as_u8(&self) -> u8 {
+                match self {
+                    $($t::$variant => $tag,)*
+                }
+            }
+
+            pub fn from_u8(tag: u8) -> Self {
+                match tag {
+                    $($tag => $t::$variant,)*
+                    _ => panic!("Unknown {} tag {}", stringify!($t), tag),
+                }
+            }
+        }
+
+        impl ::ic_stable_structures::Storable for $t {
+            fn to_bytes(&self) -> ::std::borrow::Cow<'_, [u8]> {
+                ::std::borrow::Cow::Owned(vec![self.as_u8()])
+            }
+
+            fn from_bytes(bytes: ::std::borrow::Cow<'_, [u8]>) -> Self {
+                Self::from_u8(bytes[0])
+            }
+
+            const BOUND: ::ic_stable_structures::storable::Bound =
+                ::ic_stable_structures::storable::Bound::Bounded {
+                    max_size: 1,
+                    is_fixed_size: true,
+                };
+        }
+    };
+}
@@ -29,7 +29,7 @@ impl Guard<TaskArchiveState> for AppendBatchRequest {
         self.validate(&()).map_err(|e| e.to_string())?;
 
         if caller != ENV_VARS.tasks_canister_id {
-            return Err(format!("Access denied"));
+            return Err("Access denied".to_string());
         }
 
         Ok(())
@@ -127,7 +127,7 @@ impl Guard<TaskArchiveState> for SetNextRequest {
         self.validate(&()).map_err(|e| e.to_string())?;
 
         if caller != ENV_VARS.tasks_canister_id {
-            return Err(format!("Access denied"));
+            return Err("Access denied".to_string());
         }
 
         Ok(())
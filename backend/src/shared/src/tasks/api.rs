@@ -3,7 +3,6 @@ use std::collections::{BTreeMap, BTreeSet};
 use candid::{CandidType, Nat, Principal};
 use garde::Validate;
 use serde::Deserialize;
-use sha2::Digest;
 
 use crate::{
     e8s::E8s,
@@ -57,15 +56,15 @@ impl Guard<TasksState> for CreateTaskRequest {
         self.validate(&()).map_err(|e| e.to_string())?;
 
         if self.hours_base > E8s(Nat::from(16_0000_0000u64)) {
-            return Err(format!("Max possible reward is 16 hours"));
+            return Err("Max possible reward is 16 hours".to_string());
         }
 
         if self.storypoints_base > E8s(Nat::from(10_0000_0000u64)) {
-            return Err(format!("Max possible reward is 10 base storypoints"));
+            return Err("Max possible reward is 10 base storypoints".to_string());
         }
 
         if self.storypoints_ext_budget > E8s(Nat::from(50_0000_0000u64)) {
-            return Err(format!("Max possible reward is 50 additional storypoints"));
+            return Err("Max possible reward is 50 additional storypoints".to_string());
         }
 
         self.profile_proof.assert_valid_for(caller, now)?;
@@ -86,7 +85,7 @@ impl Guard<TasksState> for CreateTaskRequest {
                 .reputation_proof
                 .rep_reliant_action_can_be_done(caller, now)
             {
-                return Err(format!("Access denied"));
+                return Err("Access denied".to_string());
             }
         }
 
@@ -155,33 +154,33 @@ impl Guard<TasksState> for EditTaskRequest {
         ) {
             (true, _, _) => {}
             (false, true, true) => {}
-            _ => return Err(format!("Access denied")),
+            _ => return Err("Access denied".to_string()),
         };
 
         if let Some(hours_base) = &self.new_hours_base_opt {
             if hours_base > &E8s(Nat::from(16_0000_0000u64)) {
-                return Err(format!("Max possible reward is 16 hours"));
+                return Err("Max possible reward is 16 hours".to_string());
             }
         }
 
         if let Some(storypoints_base) = &self.new_storypoints_base_opt {
             if storypoints_base > &E8s(Nat::from(10_0000_0000u64)) {
-                return Err(format!("Max possible reward is 10 base storypoints"));
+                return Err("Max possible reward is 10 base storypoints".to_string());
             }
         }
 
         if let Some(storypoints_ext) = &self.new_storypoints_ext_budget_opt {
             if storypoints_ext > &E8s(Nat::from(50_0000_0000u64)) {
-                return Err(format!("Max possible reward is 50 additional storypoints"));
+                return Err("Max possible reward is 50 additional storypoints".to_string());
             }
         }
 
         if let Some(new_title) = &mut self.new_title_opt {
-            *new_title = escape_script_tag(&new_title);
+            *new_title = escape_script_tag(new_title);
         }
 
         if let Some(new_description) = &mut self.new_description_opt {
-            *new_description = escape_script_tag(&new_description);
+            *new_description = escape_script_tag(new_description);
         }
 
         if let Some(new_solution_fields) = &mut self.new_solution_fields_opt {
@@ -220,7 +219,7 @@ impl Guard<TasksState> for FinishEditTaskRequest {
 
         match (task.can_edit(), caller == ENV_VARS.votings_canister_id) {
             (true, true) => Ok(()),
-            _ => Err(format!("Access denied")),
+            _ => Err("Access denied".to_string()),
         }
     }
 }
@@ -256,7 +255,7 @@ impl Guard<TasksState> for StartSolveTaskRequest {
             caller == ENV_VARS.votings_canister_id,
         ) {
             (true, true) => Ok(()),
-            _ => Err(format!("Access denied")),
+            _ => Err("Access denied".to_string()),
         }
     }
 }
@@ -289,7 +288,7 @@ impl Guard<TasksState> for BackToEditTaskRequest {
             caller == ENV_VARS.votings_canister_id,
         ) {
             (true, true) => Ok(()),
-            _ => Err(format!("Access denied")),
+            _ => Err("Access denied".to_string()),
         }
     }
 }
@@ -323,22 +322,21 @@ impl Guard<TasksState> for AttachToTaskRequest {
             .ok_or(format!("Task {} not found", self.id))?;
 
         if !task.can_attach() {
-            return Err(format!("Access denied"));
+            return Err("Access denied".to_string());
         }
 
         if task
             .solver_constraints
             .contains(&SolverConstraint::TeamOnly)
+            && !self.proof.body.as_ref().unwrap().is_team_member
         {
-            if !self.proof.body.as_ref().unwrap().is_team_member {
-                return Err(format!("This task can only be solved by team members"));
-            }
+            return Err("This task can only be solved by team members".to_string());
         }
 
         if task.assignees.is_some() {
-            return Err(format!(
-                "This task can only be solved by a predefined set of assignees"
-            ));
+            return Err(
+                "This task can only be solved by a predefined set of assignees".to_string(),
+            );
         }
 
         Ok(())
@@ -375,18 +373,16 @@ impl Guard<TasksState> for SolveTaskRequest {
             .ok_or(format!("Task {} not found", self.id))?;
 
         if !task.can_solve() {
-            return Err(format!("Access denied"));
+            return Err("Access denied".to_string());
         }
 
         if task.max_solutions() == (task.solutions.len() as u32) {
-            return Err(format!("Max solutions number reached"));
+            return Err("Max solutions number reached".to_string());
         }
 
         if let Some(assignees) = &task.assignees {
             if !assignees.contains(&caller) {
-                return Err(format!(
-                    "This task can only be solved by the preselected assignees"
-                ));
+                return Err("This task can only be solved by the preselected assignees".to_string());
             }
         }
 
@@ -400,7 +396,7 @@ impl Guard<TasksState> for SolveTaskRequest {
                 .expect("UNREACHEABLE")
                 .is_team_member
             {
-                return Err(format!("Only team members can solve this task"));
+                return Err("Only team members can solve this task".to_string());
             }
         }
 
@@ -419,10 +415,8 @@ impl Guard<TasksState> for SolveTaskRequest {
         }
 
         if let Some(filled_in_fields) = &mut self.filled_in_fields_opt {
-            for field_opt in filled_in_fields.iter_mut() {
-                if let Some(field) = field_opt {
-                    *field = escape_script_tag(&field);
-                }
+            for field in filled_in_fields.iter_mut().flatten() {
+                *field = escape_script_tag(field);
             }
         }
 
@@ -454,20 +448,18 @@ impl Guard<TasksState> for FinishSolveRequest {
             .ok_or(format!("Task {} not found", self.id))?;
 
         if caller != ENV_VARS.votings_canister_id {
-            return Err(format!("Access denied"));
+            return Err("Access denied".to_string());
         }
 
         match task.stage {
             TaskStage::Solve { until_timestamp } => {
                 if until_timestamp > now {
-                    Err(format!(
-                        "Unable to finish task solve stage faster than planned"
-                    ))
+                    Err("Unable to finish task solve stage faster than planned".to_string())
                 } else {
                     Ok(())
                 }
             }
-            _ => Err(format!("Access denied")),
+            _ => Err("Access denied".to_string()),
         }
     }
 }
@@ -501,7 +493,7 @@ impl Guard<TasksState> for EvaluateRequest {
             .ok_or(format!("Task {} not found", self.id))?;
 
         if !(task.can_evaluate() && caller == ENV_VARS.votings_canister_id) {
-            return Err(format!("Access denied"));
+            return Err("Access denied".to_string());
         }
 
         let evaluated_solutions: BTreeMap<Principal, Option<E8s>> =
@@ -612,7 +604,7 @@ impl Guard<TasksState> for DeleteRequest {
             return Ok(());
         }
 
-        Err(format!("Access denied"))
+        Err("Access denied".to_string())
     }
 }
 
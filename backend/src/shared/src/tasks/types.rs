@@ -33,6 +33,7 @@ pub struct Task {
 }
 
 impl Task {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: TaskId,
         title: String,
@@ -68,6 +69,7 @@ impl Task {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn edit(
         &mut self,
         new_title_opt: Option<String>,
@@ -361,7 +363,7 @@ impl SolutionField {
         if let Some(value) = field {
             match &self.kind {
                 SolutionFieldKind::Md => Ok(()),
-                SolutionFieldKind::Url { kind } => kind.validate(&value),
+                SolutionFieldKind::Url { kind } => kind.validate(value),
             }
         } else {
             if self.required {
@@ -401,7 +403,7 @@ impl URLKind {
             return Ok(());
         }
 
-        let domain = url.domain().ok_or(format!("Invalid domain name"))?;
+        let domain = url.domain().ok_or("Invalid domain name".to_string())?;
 
         match self {
             Self::Any => Ok(()),
@@ -409,42 +411,42 @@ impl URLKind {
                 if domain.ends_with("github.com") {
                     Ok(())
                 } else {
-                    Err(format!("Not a Github url"))
+                    Err("Not a Github url".to_string())
                 }
             }
             Self::Figma => {
                 if domain.ends_with("figma.com") {
                     Ok(())
                 } else {
-                    Err(format!("Not a Figma url"))
+                    Err("Not a Figma url".to_string())
                 }
             }
             Self::Notion => {
                 if domain.ends_with("notion.so") || domain.ends_with("notion.site") {
                     Ok(())
                 } else {
-                    Err(format!("Not a Notion url"))
+                    Err("Not a Notion url".to_string())
                 }
             }
             Self::Twitter => {
                 if domain.ends_with("twitter.com") || domain.ends_with("x.com") {
                     Ok(())
                 } else {
-                    Err(format!("Not a Twitter url"))
+                    Err("Not a Twitter url".to_string())
                 }
             }
             Self::DfinityForum => {
                 if domain.ends_with("forum.dfinity.org") {
                     Ok(())
                 } else {
-                    Err(format!("Not a Dfinity Forum url"))
+                    Err("Not a Dfinity Forum url".to_string())
                 }
             }
             Self::FortMajorSite => {
                 if domain.ends_with("fort-major.org") {
                     Ok(())
                 } else {
-                    Err(format!("Not a Fort Major url"))
+                    Err("Not a Fort Major url".to_string())
                 }
             }
         }
@@ -1,16 +1,25 @@
-use candid::CandidType;
+use candid::{CandidType, IDLArgs, Principal};
 use garde::Validate;
 use serde::Deserialize;
 
 use crate::{
     e8s::E8s,
+    escape_script_tag,
+    liquid_democracy::state::GENERAL_TOPIC_ID,
+    pagination::{PageRequest, PageResponse},
     proof::{ProfileProof, ReputationProof},
-    Guard,
+    Guard, ENV_VARS,
 };
 
 use super::{
     state::VotingsState,
-    types::{VotingEvent, VotingExt, VotingId, VotingKind},
+    types::{
+        ArchivedMotion, ArchivedVoting, ArchivedVotingId, ArchivedVotingsFilter, ChoiceBallot,
+        ConvictionParams, ConvictionProposalExt, ConvictionProposalId, DelegationBreakdown,
+        GenericCallTarget, GuardianCouncil, ParticipationEpoch, ParticipationParams, SecretBallots,
+        StagedWasm, VotingEvent, VotingExt, VotingId, VotingKind, VotingKindTag, VotingParams,
+        VotingProjection, VotingStage, WasmHash, MAX_STAGED_WASMS, MAX_WASM_SIZE,
+    },
 };
 
 #[derive(CandidType, Deserialize, Validate)]
@@ -32,10 +41,32 @@ impl Guard<VotingsState> for StartVotingRequest {
     ) -> Result<(), String> {
         self.validate(&()).map_err(|e| e.to_string())?;
 
-        let id = self.kind.get_id();
+        validate_and_escape_kind(&mut self.kind, state)?;
 
-        if let Some(_voting) = state.votings.get(&id) {
-            return Err(format!("The voting is already in progress"));
+        if let VotingKind::Batch {
+            description,
+            actions,
+            on_failure: _,
+        } = &mut self.kind
+        {
+            *description = escape_script_tag(description);
+
+            for action in actions.iter_mut() {
+                if !action.can_be_batched() {
+                    return Err(format!("{:?} actions can't be batched", action.get_tag()));
+                }
+
+                validate_and_escape_kind(action, state)?;
+            }
+        }
+
+        // the rest of the kinds are resolved by the first to pass
+        if !self.kind.get_tag().allows_concurrent_votings()
+            && !state
+                .get_votings_for_target(&self.kind.get_target())
+                .is_empty()
+        {
+            return Err("The voting is already in progress".to_string());
         }
 
         self.profile_proof.assert_valid_for(caller, now)?;
@@ -48,19 +79,187 @@ impl Guard<VotingsState> for StartVotingRequest {
             .expect("UNREACHEABLE")
             .is_team_member;
 
-        if !is_team_member {
-            if !self
+        if !is_team_member
+            && !self
                 .reputation_proof
                 .rep_reliant_action_can_be_done(caller, now)
-            {
-                return Err(format!("Access denied"));
-            }
+        {
+            return Err("Access denied".to_string());
         }
 
         Ok(())
     }
 }
 
+// per-kind checks, that are applied to batched actions as well
+fn validate_and_escape_kind(kind: &mut VotingKind, state: &VotingsState) -> Result<(), String> {
+    // motion ids depend on the text, so it should be escaped before the id is calculated
+    if let VotingKind::Motion {
+        title,
+        body,
+        topics: _,
+    } = kind
+    {
+        *title = escape_script_tag(title);
+        *body = escape_script_tag(body);
+    }
+
+    if let VotingKind::MultiChoice {
+        title,
+        body,
+        topics: _,
+        options,
+        mode: _,
+    } = kind
+    {
+        *title = escape_script_tag(title);
+        *body = escape_script_tag(body);
+
+        for option in options.iter_mut() {
+            *option = escape_script_tag(option);
+        }
+    }
+
+    if let VotingKind::GenericCall {
+        canister_id,
+        method_name,
+        args_raw,
+        description,
+    } = kind
+    {
+        let target = GenericCallTarget {
+            canister_id: *canister_id,
+            method_name: method_name.clone(),
+        };
+
+        if !state.generic_call_allow_list.contains_key(&target) {
+            return Err(format!(
+                "Method {} of canister {} is not in the allow-list",
+                method_name, canister_id
+            ));
+        }
+
+        IDLArgs::from_bytes(args_raw).map_err(|e| format!("Invalid candid arguments: {}", e))?;
+
+        *description = escape_script_tag(description);
+    }
+
+    if let VotingKind::SetVotingParams { kind: _, params } = kind {
+        params.check_bounds()?;
+    }
+
+    if let VotingKind::SetConvictionParams { params } = kind {
+        params.check_bounds()?;
+    }
+
+    if let VotingKind::SetGuardianCouncil { council } = kind {
+        council.check_bounds()?;
+    }
+
+    if let VotingKind::SetParticipationParams { params } = kind {
+        params.check_bounds()?;
+    }
+
+    if let VotingKind::CreateDecisionTopic { name, description } = kind {
+        *name = escape_script_tag(name);
+        *description = escape_script_tag(description);
+    }
+
+    if let VotingKind::EditDecisionTopic {
+        id: _,
+        new_name_opt,
+        new_description_opt,
+    } = kind
+    {
+        if new_name_opt.is_none() && new_description_opt.is_none() {
+            return Err("Nothing to edit".to_string());
+        }
+
+        if let Some(new_name) = new_name_opt {
+            *new_name = escape_script_tag(new_name);
+        }
+
+        if let Some(new_description) = new_description_opt {
+            *new_description = escape_script_tag(new_description);
+        }
+    }
+
+    if let VotingKind::DeprecateDecisionTopic { id, successors } = kind {
+        successors.sort();
+        successors.dedup();
+
+        if *id == GENERAL_TOPIC_ID {
+            return Err("The general decision topic can't be deprecated".to_string());
+        }
+
+        if successors.contains(id) {
+            return Err("A decision topic can't succeed itself".to_string());
+        }
+    }
+
+    if let VotingKind::Veto { voting_id, reason } = kind {
+        let target = state
+            .votings
+            .get(voting_id)
+            .ok_or("The vetoed voting does not exist".to_string())?;
+
+        if !target.is_queued() {
+            return Err("Only queued decisions can be vetoed".to_string());
+        }
+
+        *reason = escape_script_tag(reason);
+    }
+
+    if let VotingKind::TreasuryTransfer { amount, .. } = kind {
+        if *amount == E8s::zero() {
+            return Err("Empty transfer".to_string());
+        }
+    }
+
+    if let VotingKind::SetTreasuryCaps { caps } = kind {
+        caps.check_bounds()?;
+    }
+
+    if let VotingKind::SetDecayParams { params } = kind {
+        params.check_bounds()?;
+    }
+
+    if let VotingKind::SlashReputation {
+        target: _,
+        amount_or_fraction,
+        reason,
+    } = kind
+    {
+        amount_or_fraction.check_bounds()?;
+        *reason = escape_script_tag(reason);
+    }
+
+    if let VotingKind::UpgradeCanister {
+        canister_id,
+        wasm_hash,
+        arg,
+    } = kind
+    {
+        // a canister can't install code on itself, so this one is still upgraded by its other controllers
+        if *canister_id == ENV_VARS.votings_canister_id {
+            return Err("The votings canister can't upgrade itself".to_string());
+        }
+
+        let staged = state
+            .staged_wasms
+            .get(wasm_hash)
+            .ok_or("The wasm is not staged".to_string())?;
+
+        if !staged.verified {
+            return Err("The staged wasm is not verified yet".to_string());
+        }
+
+        IDLArgs::from_bytes(arg).map_err(|e| format!("Invalid candid arguments: {}", e))?;
+    }
+
+    Ok(())
+}
+
 #[derive(CandidType, Deserialize, Validate)]
 pub struct StartVotingResponse {
     #[garde(skip)]
@@ -77,6 +276,16 @@ pub struct CastVoteRequest {
     pub option_idx: u32,
     #[garde(skip)]
     pub normalized_approval_level: Option<E8s>,
+    // only used in multi-choice votings, in which case the two fields above are ignored
+    #[garde(skip)]
+    pub ballot: Option<ChoiceBallot>,
+    // only used in secret votings, where only the option index is used besides it
+    // made with SecretBallots::calc_hash and a random salt of 16 to 64 bytes - other salts can't be revealed
+    #[garde(skip)]
+    pub commitment: Option<[u8; 32]>,
+    // why the vote was cast, shown to everyone the vote was cast for
+    #[garde(inner(length(graphemes, min = 1, max = 2048)))]
+    pub rationale: Option<String>,
 }
 
 impl Guard<VotingsState> for CastVoteRequest {
@@ -92,17 +301,36 @@ impl Guard<VotingsState> for CastVoteRequest {
         let voting = state
             .votings
             .get(&self.id)
-            .ok_or(format!("The voting does not exist"))?;
+            .ok_or("The voting does not exist".to_string())?;
 
         if !voting.can_cast_vote() {
-            return Err(format!("The voting is in invalid state"));
+            return Err("The voting is in invalid state".to_string());
         }
 
-        if self.option_idx as usize >= voting.base.votes_per_option.len() {
-            return Err(format!("Option {} does not exist", self.option_idx));
+        voting
+            .base
+            .check_reputation_proof(self.proof.body.as_ref().unwrap())?;
+
+        if let Some(rationale) = &mut self.rationale {
+            // otherwise the rationale could leak the secret vote, it can be provided on reveal instead
+            if self.commitment.is_some() {
+                return Err("Secret votes can't have a rationale".to_string());
+            }
+
+            *rationale = escape_script_tag(rationale);
         }
 
-        Ok(())
+        match (&voting.base.secret_ballots, &self.commitment) {
+            (Some(secret_ballots), Some(_)) => {
+                if self.option_idx as usize >= secret_ballots.per_option.len() {
+                    return Err(format!("Option {} does not exist", self.option_idx));
+                }
+
+                Ok(())
+            }
+            (None, None) => voting.validate_vote(self.option_idx, &self.ballot),
+            _ => Err("Invalid commitment".to_string()),
+        }
     }
 }
 
@@ -113,36 +341,195 @@ pub struct CastVoteResponse {
 }
 
 #[derive(CandidType, Deserialize, Validate)]
-pub struct GetVotingsRequest {
-    #[garde(length(min = 1))]
-    pub ids: Vec<VotingId>,
+pub struct CastSpamVoteRequest {
+    #[garde(skip)]
+    pub id: VotingId,
+    #[garde(skip)]
+    pub proof: ReputationProof,
+    // false takes the mark back
+    #[garde(skip)]
+    pub spam: bool,
 }
 
-impl Guard<VotingsState> for GetVotingsRequest {
+impl Guard<VotingsState> for CastSpamVoteRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        caller: Principal,
+        now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+        self.proof.assert_valid_for(caller, now)?;
+
+        let voting = state
+            .votings
+            .get(&self.id)
+            .ok_or("The voting does not exist".to_string())?;
+
+        if voting.base.spam_votes.is_none() {
+            return Err("Only votings with a deposit can be marked as spam".to_string());
+        }
+
+        voting
+            .base
+            .check_reputation_proof(self.proof.body.as_ref().unwrap())?;
+
+        if !voting.can_cast_vote() {
+            return Err("The voting is in invalid state".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct CastSpamVoteResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct RevealVoteRequest {
+    #[garde(skip)]
+    pub id: VotingId,
+    #[garde(skip)]
+    pub option_idx: u32,
+    #[garde(skip)]
+    pub normalized_approval_level: Option<E8s>,
+    #[garde(skip)]
+    pub ballot: Option<ChoiceBallot>,
+    // too short salts would let anyone brute force the vote behind a commitment
+    #[garde(length(min = 16, max = 64))]
+    pub salt: Vec<u8>,
+    #[garde(inner(length(graphemes, min = 1, max = 2048)))]
+    pub rationale: Option<String>,
+}
+
+impl Guard<VotingsState> for RevealVoteRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        let voting = state
+            .votings
+            .get(&self.id)
+            .ok_or("The voting does not exist".to_string())?;
+
+        if !voting.is_revealing() {
+            return Err("The voting is in invalid state".to_string());
+        }
+
+        let hash = voting
+            .base
+            .secret_ballots
+            .as_ref()
+            .and_then(|it| it.per_option.get(self.option_idx as usize))
+            .and_then(|it| it.hashes.get(&caller))
+            .ok_or("Nothing to reveal".to_string())?;
+
+        let expected_hash = SecretBallots::calc_hash(
+            caller,
+            self.option_idx,
+            &self.normalized_approval_level,
+            &self.ballot,
+            &self.salt,
+        );
+
+        if hash != &expected_hash {
+            return Err("The vote does not match the commitment".to_string());
+        }
+
+        if let Some(rationale) = &mut self.rationale {
+            *rationale = escape_script_tag(rationale);
+        }
+
+        // an invalid vote can't be revealed, so it stays counted only towards the participation
+        voting.validate_vote(self.option_idx, &self.ballot)
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct RevealVoteResponse {}
+
+// anyone can re-trigger a failed execution, until it expires
+#[derive(CandidType, Deserialize, Validate)]
+pub struct RetryExecutionRequest {
+    #[garde(skip)]
+    pub id: VotingId,
+}
+
+impl Guard<VotingsState> for RetryExecutionRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        _caller: Principal,
+        now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        let voting = state
+            .votings
+            .get(&self.id)
+            .ok_or("The voting does not exist".to_string())?;
+
+        if !voting.can_retry_execution() {
+            return Err("The voting is in invalid state".to_string());
+        }
+
+        if let VotingStage::ExecutionFailed { expires_at, .. } = voting.stage {
+            if now >= expires_at {
+                return Err("The execution has expired".to_string());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct RetryExecutionResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct CancelQueuedVotingRequest {
+    #[garde(skip)]
+    pub id: VotingId,
+    #[garde(skip)]
+    pub reason: String,
+}
+
+impl Guard<VotingsState> for CancelQueuedVotingRequest {
     fn validate_and_escape(
         &mut self,
         _state: &VotingsState,
-        _caller: candid::Principal,
+        caller: Principal,
         _now: crate::TimestampNs,
     ) -> Result<(), String> {
-        self.validate(&()).map_err(|e| e.to_string())
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        Ok(())
     }
 }
 
+// the voting could have been executed already, by the time the veto is decided
 #[derive(CandidType, Deserialize, Validate)]
-pub struct GetVotingsResponse {
+pub struct CancelQueuedVotingResponse {
     #[garde(skip)]
-    pub entries: Vec<Option<VotingExt>>,
+    pub cancelled: bool,
 }
 
 #[derive(CandidType, Deserialize, Validate)]
-pub struct GetVotingEventsRequest {}
+pub struct GetQueuedVotingsRequest {}
 
-impl Guard<VotingsState> for GetVotingEventsRequest {
+impl Guard<VotingsState> for GetQueuedVotingsRequest {
     fn validate_and_escape(
         &mut self,
         _state: &VotingsState,
-        _caller: candid::Principal,
+        _caller: Principal,
         _now: crate::TimestampNs,
     ) -> Result<(), String> {
         self.validate(&()).map_err(|e| e.to_string())
@@ -150,19 +537,83 @@ impl Guard<VotingsState> for GetVotingEventsRequest {
 }
 
 #[derive(CandidType, Deserialize, Validate)]
-pub struct GetVotingEventsResponse {
+pub struct GetQueuedVotingsResponse {
     #[garde(skip)]
-    pub events: Vec<VotingEvent>,
+    pub entries: Vec<VotingExt>,
 }
 
 #[derive(CandidType, Deserialize, Validate)]
-pub struct GetActionableVotingsRequest {}
+pub struct SetGuardianCouncilRequest {
+    #[garde(dive)]
+    pub council: GuardianCouncil,
+}
 
-impl Guard<VotingsState> for GetActionableVotingsRequest {
+impl Guard<VotingsState> for SetGuardianCouncilRequest {
     fn validate_and_escape(
         &mut self,
         _state: &VotingsState,
-        _caller: candid::Principal,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        self.council.check_bounds()
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SetGuardianCouncilResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct ExecuteBatchRequest {
+    #[garde(skip)]
+    pub id: VotingId,
+}
+
+impl Guard<VotingsState> for ExecuteBatchRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        let voting = state
+            .votings
+            .get(&self.id)
+            .ok_or("The voting does not exist".to_string())?;
+
+        if !matches!(voting.stage, VotingStage::Executing) {
+            return Err("The voting is in invalid state".to_string());
+        }
+
+        match voting.kind {
+            VotingKind::Batch { .. } => Ok(()),
+            _ => Err("The voting is not a batch".to_string()),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct ExecuteBatchResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetGuardianCouncilRequest {}
+
+impl Guard<VotingsState> for GetGuardianCouncilRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: Principal,
         _now: crate::TimestampNs,
     ) -> Result<(), String> {
         self.validate(&()).map_err(|e| e.to_string())
@@ -170,7 +621,824 @@ impl Guard<VotingsState> for GetActionableVotingsRequest {
 }
 
 #[derive(CandidType, Deserialize, Validate)]
-pub struct GetActionableVotingsResponse {
+pub struct GetGuardianCouncilResponse {
     #[garde(skip)]
-    pub entries: Vec<VotingId>,
+    pub council: GuardianCouncil,
+    // votings with at least one veto and the guardians that have vetoed them
+    #[garde(skip)]
+    pub pending_vetoes: Vec<(VotingId, Vec<Principal>)>,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GuardianVetoRequest {
+    #[garde(skip)]
+    pub id: VotingId,
+    #[garde(length(graphemes, min = 16, max = 4096))]
+    pub reason: String,
 }
+
+impl Guard<VotingsState> for GuardianVetoRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if !state.guardian_council.get().is_member(&caller) {
+            return Err("Access denied".to_string());
+        }
+
+        let voting = state
+            .votings
+            .get(&self.id)
+            .ok_or("The voting does not exist".to_string())?;
+
+        if !voting.can_be_vetoed() {
+            return Err("The voting is in invalid state".to_string());
+        }
+
+        // otherwise guardians could protect themselves from being replaced
+        if matches!(voting.kind, VotingKind::SetGuardianCouncil { .. }) {
+            return Err("Guardians can't veto changes of the council".to_string());
+        }
+
+        self.reason = escape_script_tag(&self.reason);
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GuardianVetoResponse {
+    #[garde(skip)]
+    pub vetoed: bool,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetVotingsRequest {
+    #[garde(length(min = 1))]
+    pub ids: Vec<VotingId>,
+}
+
+impl Guard<VotingsState> for GetVotingsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: candid::Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetVotingsResponse {
+    #[garde(skip)]
+    pub entries: Vec<Option<VotingExt>>,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetVotingsByTargetRequest {
+    // the id a voting of this kind had before votings were numbered, e.g. EvaluateTask(task_id)
+    #[garde(skip)]
+    pub target: VotingId,
+}
+
+impl Guard<VotingsState> for GetVotingsByTargetRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetVotingsByTargetResponse {
+    #[garde(skip)]
+    pub entries: Vec<VotingExt>,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetVotingProjectionRequest {
+    #[garde(skip)]
+    pub id: VotingId,
+}
+
+impl Guard<VotingsState> for GetVotingProjectionRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        let voting = state
+            .votings
+            .get(&self.id)
+            .ok_or(format!("Voting {:?} not found", self.id))?;
+
+        // the tallies of secret votings are empty, until the votes are revealed
+        if voting.base.secret_ballots.is_some() && voting.can_cast_vote() {
+            return Err("Secret votings can only be projected once revealing".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetVotingProjectionResponse {
+    #[garde(skip)]
+    pub projection: VotingProjection,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetDelegationBreakdownRequest {
+    #[garde(skip)]
+    pub id: VotingId,
+    #[garde(skip)]
+    pub principal: Principal,
+}
+
+impl Guard<VotingsState> for GetDelegationBreakdownRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if !state.votings.contains_key(&self.id) {
+            return Err(format!("Voting {:?} not found", self.id));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetDelegationBreakdownResponse {
+    #[garde(skip)]
+    pub entries: Vec<DelegationBreakdown>,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetVotingEventsRequest {
+    // the latest events go first, if no pagination is provided
+    #[garde(dive)]
+    pub pagination: Option<PageRequest>,
+}
+
+impl Guard<VotingsState> for GetVotingEventsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: candid::Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetVotingEventsResponse {
+    #[garde(skip)]
+    pub events: Vec<VotingEvent>,
+    #[garde(dive)]
+    pub pagination: PageResponse,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetActionableVotingsRequest {}
+
+impl Guard<VotingsState> for GetActionableVotingsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: candid::Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetActionableVotingsResponse {
+    #[garde(skip)]
+    pub entries: Vec<VotingId>,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct UpdateGenericCallAllowListRequest {
+    #[garde(dive)]
+    pub add: Vec<GenericCallTarget>,
+    #[garde(dive)]
+    pub remove: Vec<GenericCallTarget>,
+}
+
+impl Guard<VotingsState> for UpdateGenericCallAllowListRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        caller: candid::Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller == ENV_VARS.votings_canister_id {
+            Ok(())
+        } else {
+            Err("Access denied".to_string())
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct UpdateGenericCallAllowListResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetGenericCallAllowListRequest {}
+
+impl Guard<VotingsState> for GetGenericCallAllowListRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: candid::Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetGenericCallAllowListResponse {
+    #[garde(skip)]
+    pub entries: Vec<GenericCallTarget>,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SetVotingParamsRequest {
+    #[garde(skip)]
+    pub kind: VotingKindTag,
+    #[garde(skip)]
+    pub params: VotingParams,
+}
+
+impl Guard<VotingsState> for SetVotingParamsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        caller: candid::Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        self.params.check_bounds()
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SetVotingParamsResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetVotingParamsRequest {}
+
+impl Guard<VotingsState> for GetVotingParamsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: candid::Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetVotingParamsResponse {
+    #[garde(skip)]
+    pub entries: Vec<(VotingKindTag, VotingParams)>,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetArchivedMotionsRequest {
+    #[garde(dive)]
+    pub pagination: PageRequest,
+}
+
+impl Guard<VotingsState> for GetArchivedMotionsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: candid::Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetArchivedMotionsResponse {
+    #[garde(skip)]
+    pub entries: Vec<ArchivedMotion>,
+    #[garde(dive)]
+    pub pagination: PageResponse,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetArchivedVotingsRequest {
+    #[garde(dive)]
+    pub filter: ArchivedVotingsFilter,
+    #[garde(dive)]
+    pub pagination: PageRequest,
+    // the cursor of the previous response, to continue scanning from where it stopped
+    #[garde(skip)]
+    pub cursor: Option<ArchivedVotingId>,
+}
+
+impl Guard<VotingsState> for GetArchivedVotingsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetArchivedVotingsResponse {
+    #[garde(skip)]
+    pub entries: Vec<ArchivedVoting>,
+    // left is the number of archived votings that are not scanned yet, not all of them match the filter
+    #[garde(dive)]
+    pub pagination: PageResponse,
+    // None if there is nothing left to scan
+    #[garde(skip)]
+    pub cursor: Option<ArchivedVotingId>,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct CreateConvictionProposalRequest {
+    #[garde(length(graphemes, min = 1, max = 256))]
+    pub title: String,
+    #[garde(length(graphemes, min = 16, max = 4096))]
+    pub description: String,
+    #[garde(skip)]
+    pub beneficiary: Principal,
+    #[garde(skip)]
+    pub requested_storypoints: E8s,
+    #[garde(dive)]
+    pub profile_proof: ProfileProof,
+    #[garde(dive)]
+    pub reputation_proof: ReputationProof,
+}
+
+impl Guard<VotingsState> for CreateConvictionProposalRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        caller: Principal,
+        now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if self.requested_storypoints == E8s::zero() {
+            return Err("Requested storypoints should be greater than zero".to_string());
+        }
+
+        self.profile_proof.assert_valid_for(caller, now)?;
+        self.reputation_proof.assert_valid_for(caller, now)?;
+
+        let is_team_member = self
+            .profile_proof
+            .body
+            .as_ref()
+            .expect("UNREACHEABLE")
+            .is_team_member;
+
+        if !is_team_member
+            && !self
+                .reputation_proof
+                .rep_reliant_action_can_be_done(caller, now)
+        {
+            return Err("Access denied".to_string());
+        }
+
+        self.title = escape_script_tag(&self.title);
+        self.description = escape_script_tag(&self.description);
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct CreateConvictionProposalResponse {
+    #[garde(skip)]
+    pub id: ConvictionProposalId,
+}
+
+// replaces all the previous support of the caller - this is how support is moved between proposals
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SetConvictionSupportRequest {
+    // shares of the caller's reputation staked on each proposal
+    #[garde(length(max = 16))]
+    pub supports: Vec<(ConvictionProposalId, E8s)>,
+    #[garde(skip)]
+    pub proof: ReputationProof,
+}
+
+impl Guard<VotingsState> for SetConvictionSupportRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        caller: Principal,
+        now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+        self.proof.assert_valid_for(caller, now)?;
+
+        let mut total_share = E8s::zero();
+
+        for (idx, (id, share)) in self.supports.iter().enumerate() {
+            if self.supports[..idx].iter().any(|(it, _)| it == id) {
+                return Err(format!("Proposal {} is supported twice", id));
+            }
+
+            let proposal = state
+                .conviction_proposals
+                .get(id)
+                .ok_or(format!("Proposal {} does not exist", id))?;
+
+            if !proposal.can_be_supported() {
+                return Err(format!("Proposal {} is in invalid state", id));
+            }
+
+            if share == &E8s::zero() {
+                return Err(format!(
+                    "Support of proposal {} should be greater than zero",
+                    id
+                ));
+            }
+
+            total_share += share;
+        }
+
+        if total_share > E8s::one() {
+            return Err("Can't stake more than 100% of the reputation".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SetConvictionSupportResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetConvictionProposalsRequest {}
+
+impl Guard<VotingsState> for GetConvictionProposalsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetConvictionProposalsResponse {
+    #[garde(skip)]
+    pub entries: Vec<ConvictionProposalExt>,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SetConvictionParamsRequest {
+    #[garde(skip)]
+    pub params: ConvictionParams,
+}
+
+impl Guard<VotingsState> for SetConvictionParamsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        self.params.check_bounds()
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SetConvictionParamsResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetConvictionParamsRequest {}
+
+impl Guard<VotingsState> for GetConvictionParamsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetConvictionParamsResponse {
+    #[garde(skip)]
+    pub params: ConvictionParams,
+    #[garde(skip)]
+    pub budget: E8s,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SetParticipationParamsRequest {
+    #[garde(skip)]
+    pub params: ParticipationParams,
+}
+
+impl Guard<VotingsState> for SetParticipationParamsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        self.params.check_bounds()
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct SetParticipationParamsResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetParticipationRequest {}
+
+impl Guard<VotingsState> for GetParticipationRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetParticipationResponse {
+    #[garde(skip)]
+    pub params: ParticipationParams,
+    #[garde(skip)]
+    pub epoch: ParticipationEpoch,
+    // how many of the eligible votings of the current epoch the caller took part in
+    #[garde(skip)]
+    pub participated: u32,
+}
+
+// work report evaluations count towards the participation as well
+#[derive(CandidType, Deserialize, Validate)]
+pub struct RecordParticipationRequest {
+    #[garde(length(max = 10000))]
+    pub participants: Vec<Principal>,
+}
+
+impl Guard<VotingsState> for RecordParticipationRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.work_reports_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        self.participants.sort();
+        self.participants.dedup();
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct RecordParticipationResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct UploadWasmChunkRequest {
+    #[garde(skip)]
+    pub wasm_hash: WasmHash,
+    #[garde(length(min = 1, max = 1048576))]
+    pub chunk: Vec<u8>,
+    #[garde(dive)]
+    pub profile_proof: ProfileProof,
+}
+
+impl Guard<VotingsState> for UploadWasmChunkRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        caller: Principal,
+        now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+        self.profile_proof.assert_valid_for(caller, now)?;
+
+        if !self.profile_proof.body.as_ref().unwrap().is_team_member {
+            return Err("Access denied".to_string());
+        }
+
+        match state.staged_wasms.get(&self.wasm_hash) {
+            Some(staged) => {
+                if staged.uploader != caller {
+                    return Err("The wasm is being uploaded by someone else".to_string());
+                }
+
+                if staged.verified {
+                    return Err("The wasm is already verified".to_string());
+                }
+
+                if staged.total_len + self.chunk.len() as u64 > MAX_WASM_SIZE {
+                    return Err("The wasm is too big".to_string());
+                }
+            }
+            None => {
+                if state.staged_wasms.len() >= MAX_STAGED_WASMS {
+                    return Err("Too many staged wasms".to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct UploadWasmChunkResponse {
+    #[garde(skip)]
+    pub staged: StagedWasm,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct VerifyStagedWasmRequest {
+    #[garde(skip)]
+    pub wasm_hash: WasmHash,
+}
+
+impl Guard<VotingsState> for VerifyStagedWasmRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        let staged = state
+            .staged_wasms
+            .get(&self.wasm_hash)
+            .ok_or("The wasm is not staged".to_string())?;
+
+        if staged.uploader != caller {
+            return Err("Access denied".to_string());
+        }
+
+        if staged.verified {
+            return Err("The wasm is already verified".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+// the staged wasm is removed, if its hash does not match
+#[derive(CandidType, Deserialize, Validate)]
+pub struct VerifyStagedWasmResponse {
+    #[garde(skip)]
+    pub verified: bool,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct DeleteStagedWasmRequest {
+    #[garde(skip)]
+    pub wasm_hash: WasmHash,
+}
+
+impl Guard<VotingsState> for DeleteStagedWasmRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        let staged = state
+            .staged_wasms
+            .get(&self.wasm_hash)
+            .ok_or("The wasm is not staged".to_string())?;
+
+        if staged.uploader != caller {
+            return Err("Access denied".to_string());
+        }
+
+        if state.is_wasm_proposed(&self.wasm_hash) {
+            return Err("The wasm is proposed for an upgrade".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct DeleteStagedWasmResponse {}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetStagedWasmsRequest {}
+
+impl Guard<VotingsState> for GetStagedWasmsRequest {
+    fn validate_and_escape(
+        &mut self,
+        _state: &VotingsState,
+        _caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct GetStagedWasmsResponse {
+    #[garde(skip)]
+    pub entries: Vec<(WasmHash, StagedWasm)>,
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct ExecuteUpgradeRequest {
+    #[garde(skip)]
+    pub id: VotingId,
+}
+
+impl Guard<VotingsState> for ExecuteUpgradeRequest {
+    fn validate_and_escape(
+        &mut self,
+        state: &VotingsState,
+        caller: Principal,
+        _now: crate::TimestampNs,
+    ) -> Result<(), String> {
+        self.validate(&()).map_err(|e| e.to_string())?;
+
+        if caller != ENV_VARS.votings_canister_id {
+            return Err("Access denied".to_string());
+        }
+
+        let voting = state
+            .votings
+            .get(&self.id)
+            .ok_or("The voting does not exist".to_string())?;
+
+        if !matches!(voting.stage, VotingStage::Executing) {
+            return Err("The voting is in invalid state".to_string());
+        }
+
+        match voting.kind {
+            VotingKind::UpgradeCanister { .. } => Ok(()),
+            _ => Err("The voting is not an upgrade".to_string()),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Validate)]
+pub struct ExecuteUpgradeResponse {}
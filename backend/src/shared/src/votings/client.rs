@@ -3,7 +3,8 @@ use ic_cdk::{api::call::CallResult, call};
 
 use super::api::{
     CastVoteRequest, CastVoteResponse, GetVotingEventsRequest, GetVotingEventsResponse,
-    GetVotingsRequest, GetVotingsResponse, StartVotingRequest, StartVotingResponse,
+    GetVotingsRequest, GetVotingsResponse, RecordParticipationRequest, RecordParticipationResponse,
+    StartVotingRequest, StartVotingResponse,
 };
 
 pub struct VotingsCanisterClient {
@@ -51,4 +52,14 @@ impl VotingsCanisterClient {
             .await
             .map(|(it,)| it)
     }
+
+    #[allow(non_snake_case)]
+    pub async fn votings__record_participation(
+        &self,
+        req: RecordParticipationRequest,
+    ) -> CallResult<RecordParticipationResponse> {
+        call(self.canister_id, "votings__record_participation", (req,))
+            .await
+            .map(|(it,)| it)
+    }
 }
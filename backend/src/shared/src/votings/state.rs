@@ -1,55 +1,213 @@
 use std::collections::{BTreeMap, LinkedList};
 
 use candid::{CandidType, Principal};
-
+use ic_stable_structures::{Cell, StableBTreeMap};
 use serde::Deserialize;
+use sha2::Digest;
 
-use crate::{liquid_democracy::types::DecisionTopicId, TimestampNs};
+use crate::{
+    e8s::E8s,
+    liquid_democracy::types::DecisionTopicId,
+    pagination::{page_range, PageRequest, PageResponse},
+    reputation::types::{ReputationSnapshot, ReputationSnapshotId},
+    stable_memory::Memory,
+    TimestampNs,
+};
 
 use super::{
     api::{
-        CastVoteRequest, CastVoteResponse, GetActionableVotingsRequest,
-        GetActionableVotingsResponse, GetVotingEventsRequest, GetVotingEventsResponse,
-        GetVotingsRequest, GetVotingsResponse, StartVotingRequest, StartVotingResponse,
+        CancelQueuedVotingRequest, CancelQueuedVotingResponse, CastSpamVoteRequest,
+        CastSpamVoteResponse, CastVoteRequest, CastVoteResponse, CreateConvictionProposalRequest,
+        CreateConvictionProposalResponse, DeleteStagedWasmRequest, DeleteStagedWasmResponse,
+        GetActionableVotingsRequest, GetActionableVotingsResponse, GetArchivedMotionsRequest,
+        GetArchivedMotionsResponse, GetArchivedVotingsRequest, GetArchivedVotingsResponse,
+        GetConvictionParamsRequest, GetConvictionParamsResponse, GetConvictionProposalsRequest,
+        GetConvictionProposalsResponse, GetDelegationBreakdownRequest,
+        GetDelegationBreakdownResponse, GetGenericCallAllowListRequest,
+        GetGenericCallAllowListResponse, GetGuardianCouncilRequest, GetGuardianCouncilResponse,
+        GetParticipationRequest, GetParticipationResponse, GetQueuedVotingsRequest,
+        GetQueuedVotingsResponse, GetStagedWasmsRequest, GetStagedWasmsResponse,
+        GetVotingEventsRequest, GetVotingEventsResponse, GetVotingParamsRequest,
+        GetVotingParamsResponse, GetVotingProjectionRequest, GetVotingProjectionResponse,
+        GetVotingsByTargetRequest, GetVotingsByTargetResponse, GetVotingsRequest,
+        GetVotingsResponse, GuardianVetoRequest, GuardianVetoResponse, RecordParticipationRequest,
+        RecordParticipationResponse, RevealVoteRequest, RevealVoteResponse,
+        SetConvictionParamsRequest, SetConvictionParamsResponse, SetConvictionSupportRequest,
+        SetConvictionSupportResponse, SetGuardianCouncilRequest, SetGuardianCouncilResponse,
+        SetParticipationParamsRequest, SetParticipationParamsResponse, SetVotingParamsRequest,
+        SetVotingParamsResponse, StartVotingRequest, StartVotingResponse,
+        UpdateGenericCallAllowListRequest, UpdateGenericCallAllowListResponse,
+        UploadWasmChunkRequest, UploadWasmChunkResponse, VerifyStagedWasmRequest,
+        VerifyStagedWasmResponse,
+    },
+    types::{
+        ArchivedMotion, ArchivedVoting, ArchivedVotingId, ArchivedVotingV0001, CallToExecute,
+        ClosingParticipationEpoch, ConvictionParams, ConvictionProposal, ConvictionProposalId,
+        DepositRefund, GenericCallTarget, GuardianCouncil, GuardianVetoes, MotionId, OptionVotes,
+        ParticipationEpoch, ParticipationParams, PreparedUpgrade, StagedWasm, TargetVotings,
+        VoteWeighting, Voting, VotingEvent, VotingEventV1, VotingId, VotingKind, VotingKindTag,
+        VotingParams, VotingStage, VotingTimer, WasmHash, ONE_MINUTE_NS, ONE_WEEK_NS,
     },
-    types::{CallToExecute, Voting, VotingEvent, VotingEventV1, VotingId, VotingTimer},
 };
 
-const EVENTS_LOG_LEN: usize = 1000;
+// how many archived votings a single query looks at, the rest is scanned by following the cursor
+const ARCHIVE_SCAN_LIMIT: u32 = 1000;
+// how many snapshots are released with a single call to the reputation canister
+const SNAPSHOTS_RELEASE_BATCH_LEN: usize = 1000;
+// how many of the latest events are returned, if no pagination is provided
+const EVENTS_DEFAULT_PAGE_LEN: u32 = 1000;
 
-#[derive(CandidType, Deserialize, Default)]
 pub struct VotingsState {
+    pub votings: StableBTreeMap<VotingId, Voting, Memory>,
+    pub events: StableBTreeMap<u64, VotingEvent, Memory>,
+    pub timers: StableBTreeMap<VotingId, VotingTimer, Memory>,
+    pub generic_call_allow_list: StableBTreeMap<GenericCallTarget, (), Memory>,
+    // only contains params changed by governance, the rest are defaults
+    pub voting_params: StableBTreeMap<VotingKindTag, VotingParams, Memory>,
+    pub motions_archive: StableBTreeMap<MotionId, ArchivedMotion, Memory>,
+    pub votings_archive: StableBTreeMap<ArchivedVotingId, ArchivedVoting, Memory>,
+    pub conviction_proposals: StableBTreeMap<ConvictionProposalId, ConvictionProposal, Memory>,
+    pub conviction_proposal_id_generator: Cell<ConvictionProposalId, Memory>,
+    pub conviction_params: Cell<ConvictionParams, Memory>,
+    // storypoints that are not yet allocated to any conviction proposal
+    pub conviction_budget: Cell<E8s, Memory>,
+    pub guardian_council: Cell<GuardianCouncil, Memory>,
+    pub guardian_vetoes: StableBTreeMap<VotingId, GuardianVetoes, Memory>,
+    // keyed by numbered voting ids, which are never reused - a voting that failed to start takes an id of its own
+    pub deposit_refunds: StableBTreeMap<VotingId, DepositRefund, Memory>,
+    pub voting_id_generator: Cell<u64, Memory>,
+    // only contains votings started with numbered ids, older ones are stored under their target
+    pub votings_by_target: StableBTreeMap<VotingId, TargetVotings, Memory>,
+    pub participation_params: Cell<ParticipationParams, Memory>,
+    pub participation_epoch: Cell<ParticipationEpoch, Memory>,
+    // how many eligible votings of an epoch each principal took part in, keyed by the epoch id
+    pub participation: StableBTreeMap<(u64, Principal), u32, Memory>,
+    pub closing_participation_epoch: Cell<ClosingParticipationEpoch, Memory>,
+    // storypoints of finished epochs, which are not yet minted
+    pub participation_rewards: StableBTreeMap<Principal, E8s, Memory>,
+    pub staged_wasms: StableBTreeMap<WasmHash, StagedWasm, Memory>,
+    pub wasm_chunks: StableBTreeMap<(WasmHash, u32), Vec<u8>, Memory>,
+    // how many finished votings held each reputation snapshot, released in batches
+    pub snapshots_to_release: StableBTreeMap<ReputationSnapshotId, u32, Memory>,
+}
+
+// the heap layout this state had before it was moved to stable memory
+#[derive(CandidType, Deserialize)]
+pub struct LegacyVotingsState {
     pub votings: BTreeMap<VotingId, Voting>,
     pub events: LinkedList<VotingEvent>,
     pub timers: BTreeMap<VotingId, VotingTimer>,
 }
 
 impl VotingsState {
-    pub fn new() -> Self {
-        Self {
-            votings: BTreeMap::new(),
-            events: LinkedList::new(),
-            timers: BTreeMap::new(),
+    pub fn migrate_legacy(&mut self, legacy: LegacyVotingsState) {
+        for (id, voting) in legacy.votings {
+            self.votings.insert(id, voting);
+        }
+
+        // legacy events are stored newest first
+        for event in legacy.events.into_iter().rev() {
+            self.save_event(event);
+        }
+
+        for (id, timer) in legacy.timers {
+            self.timers.insert(id, timer);
+        }
+    }
+
+    // team members don't lock anything
+    pub fn get_deposit_for(&self, req: &StartVotingRequest) -> Option<E8s> {
+        let is_team_member = req
+            .profile_proof
+            .body
+            .as_ref()
+            .expect("The proof is not computed")
+            .is_team_member;
+
+        if is_team_member {
+            return None;
+        }
+
+        let deposit = self.get_voting_params_for(req.kind.get_tag()).deposit();
+
+        if deposit == E8s::zero() {
+            None
+        } else {
+            Some(deposit)
+        }
+    }
+
+    // the deposit is locked before the voting is started, so it is given back if the voting could not start
+    pub fn refund_failed_start(&mut self, depositor: Principal, storypoints: E8s) {
+        let id = VotingId::Proposal(self.next_voting_seq());
+        let refund = DepositRefund {
+            voting_id: id,
+            depositor,
+            storypoints,
+        };
+
+        self.deposit_refunds.insert(id, refund);
+    }
+
+    pub fn release_snapshot(&mut self, id: ReputationSnapshotId, times: u32) {
+        let prev_times = self.snapshots_to_release.get(&id).unwrap_or_default();
+        self.snapshots_to_release.insert(id, prev_times + times);
+    }
+
+    pub fn take_snapshots_to_release(&mut self) -> Vec<(ReputationSnapshotId, u32)> {
+        let mut entries = Vec::new();
+
+        while entries.len() < SNAPSHOTS_RELEASE_BATCH_LEN {
+            match self.snapshots_to_release.pop_first() {
+                Some(it) => entries.push(it),
+                None => break,
+            }
         }
+
+        entries
+    }
+
+    fn next_voting_seq(&mut self) -> u64 {
+        let seq = *self.voting_id_generator.get();
+        self.voting_id_generator
+            .set(seq + 1)
+            .expect("Unable to store voting id generator");
+
+        seq
     }
 
     pub fn start_voting(
         &mut self,
         req: StartVotingRequest,
         topics: Vec<DecisionTopicId>,
+        deposit: Option<E8s>,
+        snapshot: ReputationSnapshot,
         caller: Principal,
         now: TimestampNs,
     ) -> (StartVotingResponse, VotingTimer) {
-        let voting = Voting::new(
-            req.reputation_proof
-                .body
-                .expect("The proof is not computed")
-                .reputation_total_supply,
-            req.kind,
-            topics,
-            caller,
-            now,
-        );
+        let params = self.get_voting_params_for(req.kind.get_tag());
+
+        // quorum and finish early of quadratic votings are calculated from the quadratic total supply
+        let total_supply = match params.weighting.unwrap_or_default() {
+            VoteWeighting::Linear => snapshot.total_supply,
+            VoteWeighting::Quadratic => snapshot.quadratic_total_supply,
+        };
+
+        let id = VotingId::Proposal(self.next_voting_seq());
+        let mut voting = Voting::new(id, total_supply, params, req.kind, topics, caller, now);
+
+        for target in voting.kind.get_targets() {
+            let mut target_votings = self.votings_by_target.get(&target).unwrap_or_default();
+            target_votings.ids.push(id);
+
+            self.votings_by_target.insert(target, target_votings);
+        }
+
+        if deposit.is_some() {
+            voting.base.spam_votes = Some(OptionVotes::default());
+        }
+        voting.base.deposit = deposit;
+        voting.base.reputation_snapshot_id = Some(snapshot.id);
 
         let resp = StartVotingResponse { id: voting.id };
         let timer = VotingTimer::ExecOnQuorum {
@@ -74,125 +232,1356 @@ impl VotingsState {
     pub fn cast_vote(
         &mut self,
         req: CastVoteRequest,
-        _caller: Principal,
-    ) -> (CastVoteResponse, Option<CallToExecute>) {
+        caller: Principal,
+        now: TimestampNs,
+    ) -> (CastVoteResponse, Option<CallToExecute>, Option<VotingTimer>) {
         let rep_proof = req.proof.body.expect("The proof is not computed");
-        let voting = self.votings.get_mut(&req.id).unwrap();
+        let mut voting = self.votings.get(&req.id).unwrap();
 
-        let result = voting.cast_vote(
-            req.option_idx,
-            req.normalized_approval_level,
-            rep_proof.reputation_delegation_tree,
-        );
+        // secret votings can't finish early, since nobody knows the tally
+        if let Some(hash) = req.commitment {
+            voting.cast_commitment(
+                req.option_idx,
+                hash,
+                rep_proof.reputation_delegation_tree,
+                caller,
+            );
+            self.votings.insert(req.id, voting);
+
+            return (
+                CastVoteResponse {
+                    decision_made: false,
+                },
+                None,
+                None,
+            );
+        }
+
+        let result = if let Some(ballot) = req.ballot {
+            voting.cast_choice_vote(ballot, req.rationale, rep_proof.reputation_delegation_tree)
+        } else {
+            voting.cast_vote(
+                req.option_idx,
+                req.normalized_approval_level,
+                req.rationale,
+                rep_proof.reputation_delegation_tree,
+            )
+        };
+
+        self.votings.insert(req.id, voting);
 
         match result {
-            Ok(o) => (
+            Ok(Some(call)) => {
+                let (call_opt, timer_opt) = self.queue_or_execute(req.id, call, now);
+
+                (
+                    CastVoteResponse {
+                        decision_made: true,
+                    },
+                    call_opt,
+                    timer_opt,
+                )
+            }
+            Ok(None) => (
                 CastVoteResponse {
-                    decision_made: o.is_some(),
+                    decision_made: false,
                 },
-                o,
+                None,
+                None,
             ),
             Err(event) => {
-                self.save_event(event);
-                self.delete_voting(req.id);
+                self.finish_voting(req.id, *event, now);
 
                 (
                     CastVoteResponse {
                         decision_made: true,
                     },
                     None,
+                    None,
                 )
             }
         }
     }
 
-    pub fn resolve_on_timer(&mut self, id: VotingId) -> Option<CallToExecute> {
-        // ignore if no voting is found or invalid state - it means that the timer is triggered for an already finished voting
-        let voting = self.votings.get_mut(&id)?;
+    // passed decisions of kinds with a timelock are queued, instead of being executed right away
+    fn queue_or_execute(
+        &mut self,
+        id: VotingId,
+        call: CallToExecute,
+        now: TimestampNs,
+    ) -> (Option<CallToExecute>, Option<VotingTimer>) {
+        if !self.resolve_conflicts(id, now) {
+            return (None, None);
+        }
 
-        if !voting.can_execute_on_timer() {
-            return None;
+        let mut voting = self.votings.get(&id).unwrap();
+        let timelock_ns = match self
+            .get_voting_params_for(voting.kind.get_tag())
+            .timelock_ns
+        {
+            Some(it) => it,
+            None => return (Some(call), None),
+        };
+
+        let execute_at = now + timelock_ns;
+        voting.stage = VotingStage::Queued { execute_at };
+
+        self.votings.insert(id, voting);
+        self.save_event(VotingEvent::V0001(VotingEventV1::VotingQueued {
+            voting_id: id,
+            execute_at,
+        }));
+
+        let timer = VotingTimer::ExecuteQueued {
+            voting_id: id,
+            timestamp: execute_at,
+        };
+
+        (None, Some(timer))
+    }
+
+    // all votings, that share a target with the given one, including the older ones stored under the target itself
+    pub fn get_votings_for_target(&self, target: &VotingId) -> Vec<VotingId> {
+        let mut ids = self
+            .votings_by_target
+            .get(target)
+            .map(|it| it.ids)
+            .unwrap_or_default();
+
+        if self.votings.contains_key(target) {
+            ids.push(*target);
         }
 
-        match voting.resolve_on_timer() {
-            Ok(c) => Some(c),
-            Err(event) => {
-                self.save_event(event);
-                self.delete_voting(id);
+        ids
+    }
 
-                None
+    // the first voting to pass wins, returns false if the given voting has lost
+    fn resolve_conflicts(&mut self, id: VotingId, now: TimestampNs) -> bool {
+        let voting = self.votings.get(&id).unwrap();
+        let mut rivals = Vec::new();
+
+        for target in voting.kind.get_targets() {
+            for rival_id in self.get_votings_for_target(&target) {
+                if rival_id != id && !rivals.contains(&rival_id) {
+                    rivals.push(rival_id);
+                }
             }
         }
-    }
 
-    pub fn delete_voting(&mut self, id: VotingId) {
-        self.votings.remove(&id);
+        let winner = rivals
+            .iter()
+            .find(|it| self.votings.get(it).is_some_and(|v| v.has_passed()));
+
+        if let Some(winner) = winner {
+            let event = VotingEvent::V0001(VotingEventV1::VotingSuperseded {
+                voting_id: id,
+                by: *winner,
+            });
+            self.finish_voting(id, event, now);
+
+            return false;
+        }
+
+        for rival_id in rivals {
+            let event = VotingEvent::V0001(VotingEventV1::VotingSuperseded {
+                voting_id: rival_id,
+                by: id,
+            });
+            self.finish_voting(rival_id, event, now);
+        }
+
+        true
     }
 
-    pub fn get_votings(&self, req: GetVotingsRequest, caller: Principal) -> GetVotingsResponse {
-        let votings = req
-            .ids
+    pub fn execute_queued(
+        &mut self,
+        id: VotingId,
+        now: TimestampNs,
+    ) -> (Option<CallToExecute>, Option<VotingTimer>) {
+        let mut voting = match self.votings.get(&id) {
+            Some(it) => it,
+            None => return (None, None),
+        };
+
+        match voting.stage {
+            VotingStage::Queued { execute_at } if now >= execute_at => {}
+            _ => return (None, None),
+        }
+
+        // the timelock can be shorter than a veto voting, so the decision waits for open vetoes against it
+        let veto_ends_at = self
+            .get_votings_for_target(&id.veto_target())
             .iter()
-            .map(|id| self.votings.get(&id).map(|it| it.as_ext(caller)))
-            .collect();
+            .filter_map(|it| self.votings.get(it))
+            .map(|it| it.base.created_at + it.base.duration_ns)
+            .max();
 
-        GetVotingsResponse { entries: votings }
+        if let Some(ends_at) = veto_ends_at {
+            let timer = VotingTimer::ExecuteQueued {
+                voting_id: id,
+                timestamp: ends_at.max(now) + ONE_MINUTE_NS,
+            };
+
+            return (None, Some(timer));
+        }
+
+        voting.stage = VotingStage::Executing;
+        let call = voting.kind.generate_resulting_call(id, None, &voting.base);
+
+        self.votings.insert(id, voting);
+
+        (call, None)
     }
 
-    pub fn save_event(&mut self, event: VotingEvent) {
-        if self.events.len() == EVENTS_LOG_LEN {
-            self.events.pop_back();
+    // a step, that can't produce a call, is executed as a failed one
+    pub fn next_batch_step(&self, id: VotingId) -> Option<(u32, Option<CallToExecute>)> {
+        let voting = self.votings.get(&id)?;
+        let (step, action) = voting.next_batch_step()?;
+
+        Some((
+            step,
+            action.generate_resulting_call(id, Some(step), &voting.base),
+        ))
+    }
+
+    pub fn record_batch_step(
+        &mut self,
+        id: VotingId,
+        step: u32,
+        error: Option<String>,
+        now: TimestampNs,
+    ) -> Result<(), String> {
+        let mut voting = self.votings.get(&id).unwrap();
+        let result = voting.record_batch_step(step, error.clone(), now);
+
+        self.votings.insert(id, voting);
+        self.save_event(VotingEvent::V0001(VotingEventV1::BatchStepExecuted {
+            voting_id: id,
+            step,
+            error,
+        }));
+
+        result
+    }
+
+    pub fn cancel_queued_voting(
+        &mut self,
+        req: CancelQueuedVotingRequest,
+        now: TimestampNs,
+    ) -> CancelQueuedVotingResponse {
+        let is_queued = self
+            .votings
+            .get(&req.id)
+            .map(|it| it.is_queued())
+            .unwrap_or_default();
+
+        if is_queued {
+            let event = VotingEvent::V0001(VotingEventV1::VotingCancelled {
+                voting_id: req.id,
+                reason: req.reason,
+            });
+
+            self.finish_voting(req.id, event, now);
         }
 
-        self.events.push_front(event);
+        CancelQueuedVotingResponse {
+            cancelled: is_queued,
+        }
     }
 
-    pub fn get_events(&self, _req: GetVotingEventsRequest) -> GetVotingEventsResponse {
-        GetVotingEventsResponse {
-            events: self.events.iter().cloned().collect(),
+    pub fn cast_spam_vote(&mut self, req: CastSpamVoteRequest) -> CastSpamVoteResponse {
+        let rep_proof = req.proof.body.expect("The proof is not computed");
+        let mut voting = self.votings.get(&req.id).unwrap();
+
+        voting.cast_spam_vote(req.spam, rep_proof.reputation_delegation_tree);
+        self.votings.insert(req.id, voting);
+
+        CastSpamVoteResponse {}
+    }
+
+    pub fn guardian_veto(
+        &mut self,
+        req: GuardianVetoRequest,
+        caller: Principal,
+        now: TimestampNs,
+    ) -> GuardianVetoResponse {
+        let council = self.guardian_council.get().clone();
+        let mut vetoes = self.guardian_vetoes.get(&req.id).unwrap_or_default();
+
+        vetoes.reasons.insert(caller, req.reason.clone());
+        let approvals = vetoes.approvals(&council);
+
+        self.save_event(VotingEvent::V0001(VotingEventV1::GuardianVetoCast {
+            voting_id: req.id,
+            guardian: caller,
+            reason: req.reason,
+            approvals: approvals.len() as u32,
+            threshold: council.threshold,
+        }));
+
+        let vetoed = approvals.len() >= council.threshold as usize;
+
+        if vetoed {
+            let event = VotingEvent::V0001(VotingEventV1::VotingVetoedByGuardians {
+                voting_id: req.id,
+                guardians: approvals,
+            });
+
+            self.finish_voting(req.id, event, now);
+        } else {
+            self.guardian_vetoes.insert(req.id, vetoes);
         }
+
+        GuardianVetoResponse { vetoed }
     }
 
-    pub fn get_actionable_votings(
+    pub fn set_guardian_council(
+        &mut self,
+        req: SetGuardianCouncilRequest,
+    ) -> SetGuardianCouncilResponse {
+        self.guardian_council
+            .set(req.council)
+            .expect("Unable to store guardian council");
+
+        SetGuardianCouncilResponse {}
+    }
+
+    pub fn get_guardian_council(
         &self,
-        _req: GetActionableVotingsRequest,
-        caller: Principal,
-    ) -> GetActionableVotingsResponse {
-        if Principal::anonymous() == caller {
-            return GetActionableVotingsResponse {
-                entries: Vec::new(),
-            };
+        _req: GetGuardianCouncilRequest,
+    ) -> GetGuardianCouncilResponse {
+        let council = self.guardian_council.get().clone();
+        let pending_vetoes = self
+            .guardian_vetoes
+            .iter()
+            .map(|(id, vetoes)| (id, vetoes.approvals(&council)))
+            .filter(|(_, approvals)| !approvals.is_empty())
+            .collect();
+
+        GetGuardianCouncilResponse {
+            council,
+            pending_vetoes,
         }
+    }
 
+    pub fn get_queued_votings(
+        &self,
+        _req: GetQueuedVotingsRequest,
+        caller: Principal,
+    ) -> GetQueuedVotingsResponse {
         let entries = self
             .votings
             .iter()
-            .filter_map(|(id, it)| {
-                if !it.can_cast_vote() {
-                    return None;
-                }
+            .filter(|(_, it)| it.is_queued())
+            .map(|(_, it)| it.as_ext(caller))
+            .collect();
+
+        GetQueuedVotingsResponse { entries }
+    }
+
+    pub fn reveal_vote(&mut self, req: RevealVoteRequest, caller: Principal) -> RevealVoteResponse {
+        let mut voting = self.votings.get(&req.id).unwrap();
+
+        voting.reveal_vote(
+            req.option_idx,
+            req.normalized_approval_level,
+            req.ballot,
+            req.rationale,
+            caller,
+        );
+        self.votings.insert(req.id, voting);
+
+        RevealVoteResponse {}
+    }
+
+    // secret votings are resolved on timer twice - first the reveal phase starts, then the result is calculated
+    pub fn resolve_on_timer(
+        &mut self,
+        id: VotingId,
+        now: TimestampNs,
+    ) -> (Option<CallToExecute>, Option<VotingTimer>) {
+        // ignore if no voting is found or invalid state - it means that the timer is triggered for an already finished voting
+        let mut voting = match self.votings.get(&id) {
+            Some(v) => v,
+            None => return (None, None),
+        };
+
+        if !voting.can_execute_on_timer() {
+            return (None, None);
+        }
 
-                if it
-                    .base
-                    .votes_per_option
-                    .iter()
-                    .any(|v| !v.votes.contains_key(&caller))
-                {
-                    return Some(*id);
+        if voting.base.secret_ballots.is_some() && voting.can_cast_vote() {
+            let result = voting.start_reveal(now);
+            self.votings.insert(id, voting);
+
+            return match result {
+                Ok(timer) => (None, Some(timer)),
+                Err(event) => {
+                    self.finish_voting(id, *event, now);
+
+                    (None, None)
                 }
+            };
+        }
+
+        let result = voting.resolve_on_timer();
+        self.votings.insert(id, voting);
+
+        match result {
+            Ok(c) => self.queue_or_execute(id, c, now),
+            Err(event) => {
+                self.finish_voting(id, *event, now);
+
+                (None, None)
+            }
+        }
+    }
+
+    // returns the timer of the next retry, or None if the voting is finished, because the execution has expired
+    pub fn fail_execution(
+        &mut self,
+        id: VotingId,
+        error: String,
+        now: TimestampNs,
+    ) -> Option<VotingTimer> {
+        let mut voting = self.votings.get(&id)?;
+        let expiry_ns = self
+            .get_voting_params_for(voting.kind.get_tag())
+            .execution_expiry_ns
+            .unwrap_or(ONE_WEEK_NS);
+
+        match voting.fail_execution(error, expiry_ns, now) {
+            Ok((timer, event)) => {
+                self.votings.insert(id, voting);
+                self.save_event(event);
+
+                Some(timer)
+            }
+            Err(event) => {
+                self.finish_voting(id, *event, now);
 
                 None
-            })
+            }
+        }
+    }
+
+    // an interrupted call counts as a failed attempt, so it is retried with the usual backoff (or the voting expires)
+    pub fn resume_interrupted_executions(&mut self, now: TimestampNs) {
+        let interrupted: Vec<_> = self
+            .votings
+            .iter()
+            .filter(|(_, it)| it.is_execution_in_flight())
+            .map(|(id, _)| id)
             .collect();
 
-        GetActionableVotingsResponse { entries }
+        for id in interrupted {
+            let error = "Interrupted by a canister upgrade".to_string();
+
+            if let Some(timer) = self.fail_execution(id, error, now) {
+                self.save_timer(id, timer);
+            }
+        }
     }
 
-    pub fn save_timer(&mut self, id: VotingId, timer: VotingTimer) {
-        self.timers.insert(id, timer);
+    // automatic retries only happen when it is time, manual ones - at any moment
+    pub fn start_retry(
+        &mut self,
+        id: VotingId,
+        manual: bool,
+        now: TimestampNs,
+    ) -> Option<CallToExecute> {
+        let mut voting = self.votings.get(&id)?;
+
+        if let VotingStage::ExecutionFailed { next_retry_at, .. } = voting.stage {
+            if !manual && now < next_retry_at {
+                return None;
+            }
+        }
+
+        let call = voting.start_retry();
+        self.votings.insert(id, voting);
+
+        call
+    }
+
+    // moves the voting into the archive, along with the event it has finished with
+    pub fn finish_voting(&mut self, id: VotingId, event: VotingEvent, now: TimestampNs) {
+        self.guardian_vetoes.remove(&id);
+
+        if let Some(voting) = self.votings.remove(&id) {
+            for target in voting.kind.get_targets() {
+                let mut target_votings = self.votings_by_target.get(&target).unwrap_or_default();
+                target_votings.ids.retain(|it| *it != id);
+
+                if target_votings.ids.is_empty() {
+                    self.votings_by_target.remove(&target);
+                } else {
+                    self.votings_by_target.insert(target, target_votings);
+                }
+            }
+
+            let archived_id = self.votings_archive.len();
+            let archived = ArchivedVoting::V0001(ArchivedVotingV0001 {
+                id: archived_id,
+                voting_id: id,
+                kind: voting.kind.clone(),
+                topics: voting.topics.clone(),
+                base: voting.base.clone(),
+                finished_at: now,
+                result: event.clone(),
+                batch_outcomes: voting.batch_outcomes.clone(),
+            });
+
+            self.votings_archive.insert(archived_id, archived);
+            self.settle_deposit(&voting);
+
+            if let Some(snapshot_id) = voting.base.reputation_snapshot_id {
+                self.release_snapshot(snapshot_id, 1);
+            }
+
+            // nobody had a chance to take part in a superseded voting
+            if !matches!(
+                event,
+                VotingEvent::V0001(VotingEventV1::VotingSuperseded { .. })
+            ) {
+                self.count_participation(voting.base.list_direct_voters());
+            }
+
+            let accepted = matches!(
+                event,
+                VotingEvent::V0001(VotingEventV1::VotingSuccess { voting_id: _ })
+            );
+            let motion_id = self.motions_archive.len();
+
+            if let Some(motion) = voting.to_archived_motion(motion_id, accepted, now) {
+                self.motions_archive.insert(motion_id, motion);
+            }
+        }
+
+        self.save_event(event);
+    }
+
+    fn settle_deposit(&mut self, voting: &Voting) {
+        let storypoints = match &voting.base.deposit {
+            Some(it) => it.clone(),
+            None => return,
+        };
+
+        // only spam costs the deposit, honest votings get it back even if they have missed the quorum
+        // burned deposits are simply never returned to the creator
+        let event = if voting.base.is_spam() {
+            VotingEventV1::VotingDepositBurned {
+                voting_id: voting.id,
+                depositor: voting.base.creator,
+                storypoints,
+                reason: "The voting is marked as spam".to_string(),
+            }
+        } else {
+            let refund = DepositRefund {
+                voting_id: voting.id,
+                depositor: voting.base.creator,
+                storypoints: storypoints.clone(),
+            };
+            self.deposit_refunds.insert(voting.id, refund);
+
+            VotingEventV1::VotingDepositRefunded {
+                voting_id: voting.id,
+                depositor: voting.base.creator,
+                storypoints,
+            }
+        };
+
+        self.save_event(VotingEvent::V0001(event));
     }
 
-    pub fn remove_timer(&mut self, id: &VotingId) {
-        self.timers.remove(id);
+    // the first epoch starts once the canister is installed, or upgraded to track the participation
+    pub fn init_participation_epoch(&mut self, now: TimestampNs) {
+        if self.participation_epoch.get().started_at != 0 {
+            return;
+        }
+
+        let epoch = ParticipationEpoch {
+            id: 0,
+            started_at: now,
+            eligible: 0,
+        };
+
+        self.participation_epoch
+            .set(epoch)
+            .expect("Unable to store participation epoch");
+    }
+
+    pub fn set_participation_params(
+        &mut self,
+        req: SetParticipationParamsRequest,
+    ) -> SetParticipationParamsResponse {
+        self.participation_params
+            .set(req.params)
+            .expect("Unable to store participation params");
+
+        SetParticipationParamsResponse {}
+    }
+
+    pub fn get_participation(
+        &self,
+        _req: GetParticipationRequest,
+        caller: Principal,
+    ) -> GetParticipationResponse {
+        GetParticipationResponse {
+            params: self.participation_params.get().clone(),
+            epoch: self.participation_epoch.get().clone(),
+            participated: self
+                .participation
+                .get(&(self.participation_epoch.get().id, caller))
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn record_participation(
+        &mut self,
+        req: RecordParticipationRequest,
+    ) -> RecordParticipationResponse {
+        self.count_participation(req.participants);
+
+        RecordParticipationResponse {}
+    }
+
+    fn count_participation(&mut self, participants: impl IntoIterator<Item = Principal>) {
+        let mut epoch = self.participation_epoch.get().clone();
+        epoch.eligible += 1;
+
+        for participant in participants {
+            let key = (epoch.id, participant);
+            let participated = self.participation.get(&key).unwrap_or_default();
+            self.participation.insert(key, participated + 1);
+        }
+
+        self.participation_epoch
+            .set(epoch)
+            .expect("Unable to store participation epoch");
+    }
+
+    // the rewards are only calculated here, they are minted separately
+    // returns true if should reschedule another batch
+    pub fn finish_participation_epoch(&mut self, items_num: usize, now: TimestampNs) -> bool {
+        let params = self.participation_params.get().clone();
+        let mut closing = self.closing_participation_epoch.get().clone();
+
+        let epoch = match closing.epoch.clone() {
+            Some(it) => it,
+            None => {
+                let epoch = self.participation_epoch.get().clone();
+
+                if !epoch.is_over(&params, now) {
+                    return false;
+                }
+
+                // the next epoch starts right away, so the participation is not counted towards the closing one
+                let next_epoch = ParticipationEpoch {
+                    id: epoch.id + 1,
+                    started_at: now,
+                    eligible: 0,
+                };
+
+                self.participation_epoch
+                    .set(next_epoch)
+                    .expect("Unable to store participation epoch");
+
+                closing.epoch = Some(epoch.clone());
+
+                epoch
+            }
+        };
+
+        let participation: Vec<_> = self
+            .participation
+            .range((epoch.id, Principal::management_canister())..)
+            .take_while(|((epoch_id, _), _)| *epoch_id == epoch.id)
+            .take(items_num)
+            .collect();
+
+        let should_reschedule = participation.len() == items_num;
+
+        for (key, participated) in participation {
+            self.participation.remove(&key);
+            closing.participants += 1;
+
+            let reward = params.calc_reward(participated, epoch.eligible);
+            if reward == E8s::zero() {
+                continue;
+            }
+
+            closing.rewarded_storypoints += &reward;
+
+            self.add_participation_reward(key.1, reward);
+        }
+
+        if should_reschedule {
+            self.closing_participation_epoch
+                .set(closing)
+                .expect("Unable to store closing participation epoch");
+
+            return true;
+        }
+
+        self.save_event(VotingEvent::V0001(
+            VotingEventV1::ParticipationEpochFinished {
+                epoch_id: epoch.id,
+                eligible: epoch.eligible,
+                participants: closing.participants,
+                rewarded_storypoints: closing.rewarded_storypoints,
+            },
+        ));
+
+        self.closing_participation_epoch
+            .set(ClosingParticipationEpoch::default())
+            .expect("Unable to store closing participation epoch");
+
+        false
+    }
+
+    pub fn add_participation_reward(&mut self, participant: Principal, storypoints: E8s) {
+        let pending = self
+            .participation_rewards
+            .get(&participant)
+            .unwrap_or_default();
+
+        self.participation_rewards
+            .insert(participant, pending + storypoints);
+    }
+
+    pub fn take_participation_rewards(&mut self) -> Vec<(Principal, E8s)> {
+        let rewards: Vec<_> = self.participation_rewards.iter().collect();
+
+        for (participant, _) in &rewards {
+            self.participation_rewards.remove(participant);
+        }
+
+        rewards
+    }
+
+    pub fn take_deposit_refunds(&mut self) -> Vec<(VotingId, DepositRefund)> {
+        let refunds: Vec<_> = self.deposit_refunds.iter().collect();
+
+        for (id, _) in &refunds {
+            self.deposit_refunds.remove(id);
+        }
+
+        refunds
+    }
+
+    pub fn get_archived_votings(
+        &self,
+        req: GetArchivedVotingsRequest,
+    ) -> GetArchivedVotingsResponse {
+        // archived ids follow the finishing time, so the time filter narrows the range without scanning
+        let from = match req.filter.finished_after {
+            Some(after) => self.first_archived_voting_where(|at| at >= after),
+            None => 0,
+        };
+        let to = match req.filter.finished_before {
+            Some(before) => self.first_archived_voting_where(|at| at > before),
+            None => self.votings_archive.len(),
+        };
+
+        let mut skip = req.pagination.skip;
+        let take = req.pagination.take as usize;
+        let mut entries = Vec::new();
+        let mut scanned = 0;
+
+        // the cursor is the next id to look at, in both directions
+        let mut cursor = if req.pagination.reversed {
+            req.cursor.map(|it| it + 1).unwrap_or(to).min(to)
+        } else {
+            req.cursor.unwrap_or(from).max(from)
+        };
+
+        while entries.len() < take && scanned < ARCHIVE_SCAN_LIMIT {
+            let id = if req.pagination.reversed {
+                if cursor <= from {
+                    break;
+                }
+
+                cursor -= 1;
+                cursor
+            } else {
+                if cursor >= to {
+                    break;
+                }
+
+                cursor += 1;
+                cursor - 1
+            };
+
+            scanned += 1;
+
+            let voting = self.votings_archive.get(&id).unwrap();
+            if !voting.matches(&req.filter) {
+                continue;
+            }
+
+            if skip > 0 {
+                skip -= 1;
+            } else {
+                entries.push(voting);
+            }
+        }
+
+        let (left, cursor) = if req.pagination.reversed {
+            (cursor.saturating_sub(from), cursor.checked_sub(1))
+        } else {
+            (to.saturating_sub(cursor), Some(cursor))
+        };
+
+        GetArchivedVotingsResponse {
+            entries,
+            pagination: PageResponse {
+                left: left as u32,
+                next: None,
+            },
+            cursor: cursor.filter(|_| left > 0),
+        }
+    }
+
+    fn first_archived_voting_where(&self, pred: impl Fn(TimestampNs) -> bool) -> ArchivedVotingId {
+        let mut from = 0;
+        let mut to = self.votings_archive.len();
+
+        while from < to {
+            let mid = from + (to - from) / 2;
+            let ArchivedVoting::V0001(voting) = self.votings_archive.get(&mid).unwrap();
+
+            if pred(voting.finished_at) {
+                to = mid;
+            } else {
+                from = mid + 1;
+            }
+        }
+
+        from
+    }
+
+    pub fn get_archived_motions(
+        &self,
+        req: GetArchivedMotionsRequest,
+    ) -> GetArchivedMotionsResponse {
+        // motion ids are sequential, so pages can be computed without iterating over the whole archive
+        let (from, to, left) = page_range(self.motions_archive.len(), &req.pagination);
+
+        let mut entries: Vec<_> = self
+            .motions_archive
+            .range(from..to)
+            .map(|(_, it)| it)
+            .collect();
+
+        if req.pagination.reversed {
+            entries.reverse();
+        }
+
+        GetArchivedMotionsResponse {
+            entries,
+            pagination: PageResponse { left, next: None },
+        }
+    }
+
+    pub fn get_votings(&self, req: GetVotingsRequest, caller: Principal) -> GetVotingsResponse {
+        let votings = req
+            .ids
+            .iter()
+            .map(|id| self.votings.get(id).map(|it| it.as_ext(caller)))
+            .collect();
+
+        GetVotingsResponse { entries: votings }
+    }
+
+    pub fn get_votings_by_target(
+        &self,
+        req: GetVotingsByTargetRequest,
+        caller: Principal,
+    ) -> GetVotingsByTargetResponse {
+        let entries = self
+            .get_votings_for_target(&req.target)
+            .iter()
+            .filter_map(|id| self.votings.get(id))
+            .map(|it| it.as_ext(caller))
+            .collect();
+
+        GetVotingsByTargetResponse { entries }
+    }
+
+    pub fn get_voting_projection(
+        &self,
+        req: GetVotingProjectionRequest,
+    ) -> GetVotingProjectionResponse {
+        let voting = self
+            .votings
+            .get(&req.id)
+            .expect("UNREACHEABLE - voting should exist");
+
+        GetVotingProjectionResponse {
+            projection: voting.project(),
+        }
+    }
+
+    pub fn get_delegation_breakdown(
+        &self,
+        req: GetDelegationBreakdownRequest,
+    ) -> GetDelegationBreakdownResponse {
+        let voting = self
+            .votings
+            .get(&req.id)
+            .expect("UNREACHEABLE - voting should exist");
+
+        GetDelegationBreakdownResponse {
+            entries: voting.base.explain_delegation(req.principal),
+        }
+    }
+
+    pub fn save_event(&mut self, event: VotingEvent) {
+        let idx = self
+            .events
+            .last_key_value()
+            .map(|(idx, _)| idx + 1)
+            .unwrap_or_default();
+
+        self.events.insert(idx, event);
+    }
+
+    pub fn get_events(&self, req: GetVotingEventsRequest) -> GetVotingEventsResponse {
+        let pagination = req.pagination.unwrap_or(PageRequest {
+            reversed: true,
+            skip: 0,
+            take: EVENTS_DEFAULT_PAGE_LEN,
+        });
+
+        // event indices are sequential too
+        let (from, to, left) = page_range(self.events.len(), &pagination);
+
+        let mut events: Vec<_> = self
+            .events
+            .range(from..to)
+            .map(|(_, event)| event)
+            .collect();
+
+        if pagination.reversed {
+            events.reverse();
+        }
+
+        GetVotingEventsResponse {
+            events,
+            pagination: PageResponse { left, next: None },
+        }
+    }
+
+    pub fn get_actionable_votings(
+        &self,
+        _req: GetActionableVotingsRequest,
+        caller: Principal,
+    ) -> GetActionableVotingsResponse {
+        if Principal::anonymous() == caller {
+            return GetActionableVotingsResponse {
+                entries: Vec::new(),
+            };
+        }
+
+        let entries = self
+            .votings
+            .iter()
+            .filter_map(|(id, it)| {
+                if it.can_cast_vote() && !it.base.has_voted(&caller) {
+                    return Some(id);
+                }
+
+                if it.is_revealing() && it.base.has_unrevealed_commitments(&caller) {
+                    return Some(id);
+                }
+
+                None
+            })
+            .collect();
+
+        GetActionableVotingsResponse { entries }
+    }
+
+    pub fn update_generic_call_allow_list(
+        &mut self,
+        req: UpdateGenericCallAllowListRequest,
+    ) -> UpdateGenericCallAllowListResponse {
+        for target in req.remove {
+            self.generic_call_allow_list.remove(&target);
+        }
+
+        for target in req.add {
+            self.generic_call_allow_list.insert(target, ());
+        }
+
+        UpdateGenericCallAllowListResponse {}
+    }
+
+    pub fn get_generic_call_allow_list(
+        &self,
+        _req: GetGenericCallAllowListRequest,
+    ) -> GetGenericCallAllowListResponse {
+        let entries = self
+            .generic_call_allow_list
+            .iter()
+            .map(|(target, _)| target)
+            .collect();
+
+        GetGenericCallAllowListResponse { entries }
+    }
+
+    pub fn get_voting_params_for(&self, kind: VotingKindTag) -> VotingParams {
+        self.voting_params
+            .get(&kind)
+            .unwrap_or_else(|| VotingParams::default_for(kind))
+    }
+
+    pub fn set_voting_params(&mut self, req: SetVotingParamsRequest) -> SetVotingParamsResponse {
+        self.voting_params.insert(req.kind, req.params);
+
+        SetVotingParamsResponse {}
+    }
+
+    pub fn get_voting_params(&self, _req: GetVotingParamsRequest) -> GetVotingParamsResponse {
+        let entries = VotingKindTag::ALL
+            .into_iter()
+            .map(|kind| (kind, self.get_voting_params_for(kind)))
+            .collect();
+
+        GetVotingParamsResponse { entries }
+    }
+
+    pub fn create_conviction_proposal(
+        &mut self,
+        req: CreateConvictionProposalRequest,
+        caller: Principal,
+        now: TimestampNs,
+    ) -> CreateConvictionProposalResponse {
+        let rep_proof = req
+            .reputation_proof
+            .body
+            .expect("The proof is not computed");
+
+        let id = *self.conviction_proposal_id_generator.get();
+        self.conviction_proposal_id_generator
+            .set(id + 1)
+            .expect("Unable to store conviction proposal id generator");
+
+        let proposal = ConvictionProposal::new(
+            id,
+            req.title,
+            req.description,
+            req.beneficiary,
+            req.requested_storypoints,
+            rep_proof.reputation_total_supply,
+            caller,
+            now,
+        );
+
+        let event = VotingEvent::V0001(VotingEventV1::ConvictionProposalCreated {
+            proposal_id: id,
+            creator: caller,
+            beneficiary: proposal.beneficiary,
+            requested_storypoints: proposal.requested_storypoints.clone(),
+        });
+
+        self.conviction_proposals.insert(id, proposal);
+        self.save_event(event);
+
+        CreateConvictionProposalResponse { id }
+    }
+
+    pub fn set_conviction_support(
+        &mut self,
+        req: SetConvictionSupportRequest,
+        caller: Principal,
+    ) -> SetConvictionSupportResponse {
+        let rep_proof = req.proof.body.expect("The proof is not computed");
+        // delegated reputation can't be staked, only the caller's own
+        let reputation = rep_proof.reputation_delegation_tree.reputation;
+
+        let prev_supported: Vec<_> = self
+            .conviction_proposals
+            .iter()
+            .filter(|(_, it)| it.can_be_supported() && it.stakes.contains_key(&caller))
+            .map(|(id, _)| id)
+            .collect();
+
+        for id in prev_supported {
+            let mut proposal = self.conviction_proposals.get(&id).unwrap();
+            proposal.unstake(&caller);
+
+            self.conviction_proposals.insert(id, proposal);
+        }
+
+        for (id, share) in req.supports {
+            let mut proposal = self.conviction_proposals.get(&id).unwrap();
+            proposal.stake(
+                caller,
+                &reputation * share,
+                rep_proof.reputation_total_supply.clone(),
+            );
+
+            self.conviction_proposals.insert(id, proposal);
+        }
+
+        SetConvictionSupportResponse {}
+    }
+
+    // refills the budget, accumulates the conviction and returns calls of proposals that have passed the threshold
+    pub fn run_conviction_round(&mut self) -> Vec<(ConvictionProposalId, CallToExecute)> {
+        let params = self.conviction_params.get().clone();
+        let mut budget = self.conviction_budget.get().clone();
+
+        if budget < params.max_budget {
+            budget = (budget + &params.budget_refill_per_period).min(params.max_budget.clone());
+        }
+
+        let ids: Vec<_> = self.conviction_proposals.iter().map(|(id, _)| id).collect();
+        let mut calls = Vec::new();
+
+        for id in ids {
+            let mut proposal = self.conviction_proposals.get(&id).unwrap();
+
+            if !proposal.can_be_supported() {
+                continue;
+            }
+
+            proposal.accumulate(&params);
+
+            // the requested storypoints are reserved until the call is executed
+            if proposal.can_execute(&params, &budget) {
+                budget -= &proposal.requested_storypoints;
+                proposal.stage = VotingStage::Executing;
+
+                calls.push((id, proposal.generate_resulting_call()));
+            }
+
+            self.conviction_proposals.insert(id, proposal);
+        }
+
+        self.conviction_budget
+            .set(budget)
+            .expect("Unable to store conviction budget");
+
+        calls
+    }
+
+    pub fn finish_conviction_proposal(
+        &mut self,
+        id: ConvictionProposalId,
+        result: Result<(), String>,
+    ) {
+        let proposal = match self.conviction_proposals.remove(&id) {
+            Some(p) => p,
+            None => return,
+        };
+
+        let event = match result {
+            Ok(_) => VotingEventV1::ConvictionProposalSuccess {
+                proposal_id: id,
+                conviction: proposal.conviction,
+            },
+            Err(reason) => {
+                // returning the reserved storypoints back to the budget
+                let budget = self.conviction_budget.get().clone() + proposal.requested_storypoints;
+                self.conviction_budget
+                    .set(budget)
+                    .expect("Unable to store conviction budget");
+
+                VotingEventV1::ConvictionProposalFail {
+                    proposal_id: id,
+                    reason,
+                }
+            }
+        };
+
+        self.save_event(VotingEvent::V0001(event));
+    }
+
+    pub fn get_conviction_proposals(
+        &self,
+        _req: GetConvictionProposalsRequest,
+        caller: Principal,
+    ) -> GetConvictionProposalsResponse {
+        let params = self.conviction_params.get();
+        let budget = self.conviction_budget.get();
+
+        let entries = self
+            .conviction_proposals
+            .iter()
+            .map(|(_, it)| it.as_ext(caller, params, budget))
+            .collect();
+
+        GetConvictionProposalsResponse { entries }
+    }
+
+    pub fn set_conviction_params(
+        &mut self,
+        req: SetConvictionParamsRequest,
+    ) -> SetConvictionParamsResponse {
+        // the budget can't stay above the new max
+        let budget = self
+            .conviction_budget
+            .get()
+            .clone()
+            .min(req.params.max_budget.clone());
+        self.conviction_budget
+            .set(budget)
+            .expect("Unable to store conviction budget");
+
+        self.conviction_params
+            .set(req.params)
+            .expect("Unable to store conviction params");
+
+        SetConvictionParamsResponse {}
+    }
+
+    pub fn get_conviction_params(
+        &self,
+        _req: GetConvictionParamsRequest,
+    ) -> GetConvictionParamsResponse {
+        GetConvictionParamsResponse {
+            params: self.conviction_params.get().clone(),
+            budget: self.conviction_budget.get().clone(),
+        }
+    }
+
+    pub fn upload_wasm_chunk(
+        &mut self,
+        req: UploadWasmChunkRequest,
+        caller: Principal,
+        now: TimestampNs,
+    ) -> UploadWasmChunkResponse {
+        let mut staged = self.staged_wasms.get(&req.wasm_hash).unwrap_or(StagedWasm {
+            uploader: caller,
+            uploaded_at: now,
+            total_len: 0,
+            chunks: 0,
+            verified: false,
+        });
+
+        staged.total_len += req.chunk.len() as u64;
+        self.wasm_chunks
+            .insert((req.wasm_hash, staged.chunks), req.chunk);
+        staged.chunks += 1;
+        staged.uploaded_at = now;
+
+        self.staged_wasms.insert(req.wasm_hash, staged.clone());
+
+        UploadWasmChunkResponse { staged }
+    }
+
+    pub fn verify_staged_wasm(&mut self, req: VerifyStagedWasmRequest) -> VerifyStagedWasmResponse {
+        let mut staged = self
+            .staged_wasms
+            .get(&req.wasm_hash)
+            .expect("The wasm is not staged");
+
+        let mut hasher = sha2::Sha256::new();
+        for idx in 0..staged.chunks {
+            let chunk = self
+                .wasm_chunks
+                .get(&(req.wasm_hash, idx))
+                .expect("UNREACHEABLE! Wasm chunk not found");
+
+            hasher.update(chunk);
+        }
+        let hash: WasmHash = hasher.finalize().into();
+
+        let verified = hash == req.wasm_hash;
+
+        if verified {
+            staged.verified = true;
+            self.staged_wasms.insert(req.wasm_hash, staged);
+        } else {
+            self.remove_staged_wasm(&req.wasm_hash);
+        }
+
+        VerifyStagedWasmResponse { verified }
+    }
+
+    pub fn delete_staged_wasm(&mut self, req: DeleteStagedWasmRequest) -> DeleteStagedWasmResponse {
+        self.remove_staged_wasm(&req.wasm_hash);
+
+        DeleteStagedWasmResponse {}
+    }
+
+    pub fn get_staged_wasms(&self, _req: GetStagedWasmsRequest) -> GetStagedWasmsResponse {
+        GetStagedWasmsResponse {
+            entries: self.staged_wasms.iter().collect(),
+        }
+    }
+
+    pub fn is_wasm_proposed(&self, wasm_hash: &WasmHash) -> bool {
+        self.votings.iter().any(|(_, voting)| {
+            matches!(
+                voting.kind,
+                VotingKind::UpgradeCanister { wasm_hash: h, .. } if h == *wasm_hash
+            )
+        })
+    }
+
+    pub fn prepare_upgrade(&self, id: VotingId) -> Result<PreparedUpgrade, String> {
+        let voting = self.votings.get(&id).expect("The voting does not exist");
+
+        let (canister_id, wasm_hash, arg) = match voting.kind {
+            VotingKind::UpgradeCanister {
+                canister_id,
+                wasm_hash,
+                arg,
+            } => (canister_id, wasm_hash, arg),
+            _ => unreachable!("The voting is not an upgrade"),
+        };
+
+        let staged = self
+            .staged_wasms
+            .get(&wasm_hash)
+            .ok_or("The staged wasm was removed".to_string())?;
+
+        let chunks = (0..staged.chunks)
+            .map(|idx| {
+                self.wasm_chunks
+                    .get(&(wasm_hash, idx))
+                    .expect("UNREACHEABLE! Wasm chunk not found")
+            })
+            .collect();
+
+        Ok(PreparedUpgrade {
+            canister_id,
+            wasm_hash,
+            chunks,
+            arg,
+        })
+    }
+
+    pub fn finish_upgrade(&mut self, id: VotingId, canister_id: Principal, wasm_hash: WasmHash) {
+        self.save_event(VotingEvent::V0001(VotingEventV1::CanisterUpgraded {
+            voting_id: id,
+            canister_id,
+            wasm_hash,
+        }));
+
+        self.remove_staged_wasm(&wasm_hash);
+    }
+
+    fn remove_staged_wasm(&mut self, wasm_hash: &WasmHash) {
+        if let Some(staged) = self.staged_wasms.remove(wasm_hash) {
+            for idx in 0..staged.chunks {
+                self.wasm_chunks.remove(&(*wasm_hash, idx));
+            }
+        }
+    }
+
+    pub fn save_timer(&mut self, id: VotingId, timer: VotingTimer) {
+        self.timers.insert(id, timer);
+    }
+
+    // the timer may have already been replaced with a newer one for the same voting
+    pub fn remove_timer(&mut self, id: &VotingId, timer: &VotingTimer) {
+        if self.timers.get(id).as_ref() == Some(timer) {
+            self.timers.remove(id);
+        }
     }
 }
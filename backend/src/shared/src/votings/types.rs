@@ -1,27 +1,53 @@
-use std::collections::BTreeMap;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
-use candid::{encode_args, utils::ArgumentEncoder, CandidType, Principal};
+use candid::{
+    encode_args, encode_one, utils::ArgumentEncoder, CandidType, IDLArgs, Nat, Principal,
+};
 use garde::Validate;
 use ic_cdk::api::call::call_raw;
+use ic_stable_structures::{storable::Bound, Storable};
+use icrc_ledger_types::icrc1::account::{Account, Subaccount};
 
 use serde::Deserialize;
+use sha2::Digest;
 
 use crate::{
     bank::{
-        api::SetExchangeRateRequest,
-        types::{SwapFrom, SwapInto},
+        api::{SetExchangeRateRequest, SetTreasuryCapsRequest, TreasuryTransferRequest},
+        types::{SwapFrom, SwapInto, TreasuryAsset, TreasuryCaps},
     },
     e8s::E8s,
-    humans::api::{EmployRequest, UnemployRequest},
-    liquid_democracy::types::DecisionTopicId,
-    reputation::types::ReputationDelegationTreeNode,
+    humans::api::{EmployRequest, MintRewardsRequest, UnemployRequest},
+    impl_storable_for_candid, impl_storable_for_u8_enum,
+    liquid_democracy::{
+        api::{
+            CreateDecisionTopicRequest, DeprecateDecisionTopicRequest, EditDecisionTopicRequest,
+        },
+        types::DecisionTopicId,
+    },
+    reputation::{
+        api::{SetDecayParamsRequest, SlashRepRequest},
+        types::{
+            DecayParams, ReputationDelegationTreeNode, ReputationProofBody, ReputationSnapshotId,
+            SlashAmount,
+        },
+    },
     tasks::{
         api::{BackToEditTaskRequest, DeleteRequest, EvaluateRequest, StartSolveTaskRequest},
-        types::TaskId,
+        types::{RewardEntry, TaskId},
     },
     DurationNs, TimestampNs, ENV_VARS,
 };
 
+use super::api::{
+    CancelQueuedVotingRequest, ExecuteBatchRequest, ExecuteUpgradeRequest,
+    SetConvictionParamsRequest, SetGuardianCouncilRequest, SetParticipationParamsRequest,
+    SetVotingParamsRequest, UpdateGenericCallAllowListRequest,
+};
+
 pub const ONE_MINUTE_NS: u64 = 1_000_000_000 * 60;
 pub const ONE_HOUR_NS: u64 = ONE_MINUTE_NS * 60;
 pub const ONE_DAY_NS: u64 = ONE_HOUR_NS * 24;
@@ -36,6 +62,205 @@ pub enum VotingId {
     BankSetExchangeRate(#[garde(skip)] (SwapFrom, SwapInto)),
     HumansEmploy(#[garde(skip)] Principal),
     HumansUnemploy(#[garde(skip)] Principal),
+    GenericCall(#[garde(skip)] [u8; 32]),
+    UpdateGenericCallAllowList,
+    SetVotingParams(#[garde(skip)] VotingKindTag),
+    Motion(#[garde(skip)] [u8; 32]),
+    MultiChoice(#[garde(skip)] [u8; 32]),
+    SetConvictionParams,
+    Veto(#[garde(skip)] [u8; 32]),
+    SetGuardianCouncil,
+    Batch(#[garde(skip)] [u8; 32]),
+    CreateDecisionTopic(#[garde(skip)] [u8; 32]),
+    EditDecisionTopic(#[garde(skip)] DecisionTopicId),
+    DeprecateDecisionTopic(#[garde(skip)] DecisionTopicId),
+    SetParticipationParams,
+    UpgradeCanister(#[garde(skip)] Principal),
+    TreasuryTransfer(#[garde(skip)] [u8; 32]),
+    SetTreasuryCaps,
+    SlashReputation(#[garde(skip)] Principal),
+    SetDecayParams,
+    // new votings are numbered, the rest of the variants are only used as targets and by older votings
+    Proposal(#[garde(skip)] u64),
+}
+
+impl VotingId {
+    // only one veto voting per queued decision
+    pub fn veto_target(&self) -> VotingId {
+        VotingId::Veto(sha2::Sha256::digest(self.to_bytes()).into())
+    }
+}
+
+// voting ids are stored as keys: a fixed tag byte followed by a big-endian payload,
+// so the encoding never changes when new variants are added and keys of the same kind stay ordered
+impl Storable for VotingId {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut buf = Vec::with_capacity(33);
+
+        match self {
+            VotingId::StartSolveTask(task_id) => {
+                buf.push(0);
+                buf.extend_from_slice(&task_id.to_be_bytes());
+            }
+            VotingId::EvaluateTask(task_id) => {
+                buf.push(1);
+                buf.extend_from_slice(&task_id.to_be_bytes());
+            }
+            VotingId::DeleteTask(task_id) => {
+                buf.push(2);
+                buf.extend_from_slice(&task_id.to_be_bytes());
+            }
+            VotingId::BankSetExchangeRate((from, into)) => {
+                buf.push(3);
+                buf.push(from.as_u8());
+                buf.push(into.as_u8());
+            }
+            VotingId::HumansEmploy(id) => {
+                buf.push(4);
+                encode_principal(&mut buf, id);
+            }
+            VotingId::HumansUnemploy(id) => {
+                buf.push(5);
+                encode_principal(&mut buf, id);
+            }
+            VotingId::GenericCall(hash) => {
+                buf.push(6);
+                buf.extend_from_slice(hash);
+            }
+            VotingId::UpdateGenericCallAllowList => buf.push(7),
+            VotingId::SetVotingParams(tag) => {
+                buf.push(8);
+                buf.push(tag.as_u8());
+            }
+            VotingId::Motion(hash) => {
+                buf.push(9);
+                buf.extend_from_slice(hash);
+            }
+            VotingId::MultiChoice(hash) => {
+                buf.push(10);
+                buf.extend_from_slice(hash);
+            }
+            VotingId::SetConvictionParams => buf.push(11),
+            VotingId::Veto(hash) => {
+                buf.push(12);
+                buf.extend_from_slice(hash);
+            }
+            VotingId::SetGuardianCouncil => buf.push(13),
+            VotingId::Batch(hash) => {
+                buf.push(14);
+                buf.extend_from_slice(hash);
+            }
+            VotingId::CreateDecisionTopic(hash) => {
+                buf.push(15);
+                buf.extend_from_slice(hash);
+            }
+            VotingId::EditDecisionTopic(topic_id) => {
+                buf.push(16);
+                buf.extend_from_slice(&topic_id.to_be_bytes());
+            }
+            VotingId::DeprecateDecisionTopic(topic_id) => {
+                buf.push(17);
+                buf.extend_from_slice(&topic_id.to_be_bytes());
+            }
+            VotingId::SetParticipationParams => buf.push(18),
+            VotingId::UpgradeCanister(id) => {
+                buf.push(19);
+                encode_principal(&mut buf, id);
+            }
+            VotingId::TreasuryTransfer(hash) => {
+                buf.push(20);
+                buf.extend_from_slice(hash);
+            }
+            VotingId::SetTreasuryCaps => buf.push(21),
+            VotingId::SlashReputation(id) => {
+                buf.push(22);
+                encode_principal(&mut buf, id);
+            }
+            VotingId::SetDecayParams => buf.push(23),
+            VotingId::Proposal(id) => {
+                buf.push(24);
+                buf.extend_from_slice(&id.to_be_bytes());
+            }
+        }
+
+        Cow::Owned(buf)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let payload = &bytes[1..];
+
+        match bytes[0] {
+            0 => VotingId::StartSolveTask(decode_u64(payload)),
+            1 => VotingId::EvaluateTask(decode_u64(payload)),
+            2 => VotingId::DeleteTask(decode_u64(payload)),
+            3 => VotingId::BankSetExchangeRate((
+                SwapFrom::from_u8(payload[0]),
+                SwapInto::from_u8(payload[1]),
+            )),
+            4 => VotingId::HumansEmploy(decode_principal(payload)),
+            5 => VotingId::HumansUnemploy(decode_principal(payload)),
+            6 => VotingId::GenericCall(decode_hash(payload)),
+            7 => VotingId::UpdateGenericCallAllowList,
+            8 => VotingId::SetVotingParams(VotingKindTag::from_u8(payload[0])),
+            9 => VotingId::Motion(decode_hash(payload)),
+            10 => VotingId::MultiChoice(decode_hash(payload)),
+            11 => VotingId::SetConvictionParams,
+            12 => VotingId::Veto(decode_hash(payload)),
+            13 => VotingId::SetGuardianCouncil,
+            14 => VotingId::Batch(decode_hash(payload)),
+            15 => VotingId::CreateDecisionTopic(decode_hash(payload)),
+            16 => VotingId::EditDecisionTopic(decode_u32(payload)),
+            17 => VotingId::DeprecateDecisionTopic(decode_u32(payload)),
+            18 => VotingId::SetParticipationParams,
+            19 => VotingId::UpgradeCanister(decode_principal(payload)),
+            20 => VotingId::TreasuryTransfer(decode_hash(payload)),
+            21 => VotingId::SetTreasuryCaps,
+            22 => VotingId::SlashReputation(decode_principal(payload)),
+            23 => VotingId::SetDecayParams,
+            24 => VotingId::Proposal(decode_u64(payload)),
+            tag => panic!("Unknown VotingId tag {}", tag),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 33, // 1 byte tag, at most 32 bytes payload
+        is_fixed_size: false,
+    };
+}
+
+// the length goes first, so principals are ordered the same way as in memory
+fn encode_principal(buf: &mut Vec<u8>, id: &Principal) {
+    let id_buf = id.as_slice();
+
+    buf.push(id_buf.len() as u8);
+    buf.extend_from_slice(id_buf);
+}
+
+fn decode_principal(buf: &[u8]) -> Principal {
+    let len = buf[0] as usize;
+
+    Principal::from_slice(&buf[1..1 + len])
+}
+
+fn decode_u64(buf: &[u8]) -> u64 {
+    let mut u64_buf = [0u8; 8];
+    u64_buf.copy_from_slice(&buf[..8]);
+
+    u64::from_be_bytes(u64_buf)
+}
+
+fn decode_u32(buf: &[u8]) -> u32 {
+    let mut u32_buf = [0u8; 4];
+    u32_buf.copy_from_slice(&buf[..4]);
+
+    u32::from_be_bytes(u32_buf)
+}
+
+fn decode_hash(buf: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&buf[..32]);
+
+    hash
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
@@ -45,90 +270,77 @@ pub struct Voting {
     pub kind: VotingKind,
     pub stage: VotingStage,
     pub topics: Vec<DecisionTopicId>,
+    // only present in batch votings, which have started the execution
+    pub batch_outcomes: Option<Vec<BatchStepOutcome>>,
 }
 
 impl Voting {
     pub fn new(
+        id: VotingId,
         total_supply: E8s,
+        params: VotingParams,
         kind: VotingKind,
         topics: Vec<DecisionTopicId>,
         caller: Principal,
         now: TimestampNs,
     ) -> Self {
-        let (duration_ns, quorum, consensus_normalized, finish_early, num_options) = match &kind {
-            VotingKind::StartSolveTask { task_id: _ } => (
-                ONE_WEEK_NS,
-                &total_supply * E8s::f0_2(),
-                E8s::f0_5(),
-                &total_supply * E8s::f0_67(),
-                1,
-            ),
+        let num_options = match &kind {
             VotingKind::EvaluateTask {
                 task_id: _,
                 solutions,
-            } => (
-                ONE_WEEK_NS,
-                &total_supply * E8s::f0_2(),
-                E8s::f0_5(),
-                &total_supply * E8s::f0_67(),
-                solutions.len() as u32,
-            ),
-            VotingKind::DeleteTask { task_id: _ } => (
-                ONE_WEEK_NS,
-                &total_supply * E8s::f0_2(),
-                E8s::f0_5(),
-                &total_supply * E8s::f0_67(),
-                1,
-            ),
-            VotingKind::BankSetExchangeRate {
-                from: _,
-                into: _,
-                new_rate: _,
-            } => (
-                ONE_WEEK_NS,
-                &total_supply * E8s::f0_2(),
-                E8s::f0_67(),
-                &total_supply * E8s::f0_67(),
-                1,
-            ),
-            VotingKind::HumansEmploy {
-                candidate: _,
-                hours_a_week_commitment: _,
-            } => (
-                ONE_WEEK_NS,
-                &total_supply * E8s::f0_2(),
-                E8s::f0_67(),
-                &total_supply * E8s::f0_67(),
-                1,
-            ),
-            VotingKind::HumansUnemploy { team_member: _ } => (
-                ONE_WEEK_NS * 2,
-                &total_supply * E8s::f0_2(),
-                E8s::f0_67(),
-                &total_supply * E8s::f0_67(),
-                1,
-            ),
+            } => solutions.len() as u32,
+            // multi-choice votings don't use per-option tallies
+            VotingKind::MultiChoice {
+                title: _,
+                body: _,
+                topics: _,
+                options: _,
+                mode: _,
+            } => 0,
+            _ => 1,
         };
 
-        let id = kind.get_id();
+        let quorum = &total_supply * &params.quorum_normalized;
+        let finish_early = &total_supply * &params.finish_early_normalized;
 
-        let base = VotingBase::new(
-            duration_ns,
+        let mut base = VotingBase::new(
+            params.duration_ns,
             total_supply,
             quorum,
-            consensus_normalized,
+            params.consensus_normalized,
             finish_early,
             num_options,
             caller,
             now,
         );
 
+        base.weighting = params.weighting;
+
+        if let VotingKind::MultiChoice {
+            title: _,
+            body: _,
+            topics: _,
+            options,
+            mode,
+        } = &kind
+        {
+            base.choice_votes = Some(ChoiceVotes::new(*mode, options.len() as u32));
+        }
+
+        if let Some(reveal_duration_ns) = params.reveal_duration_ns {
+            // multi-choice votings have a single ballot per voter
+            let num_slots = num_options.max(1);
+
+            base.secret_ballots = Some(SecretBallots::new(reveal_duration_ns, num_slots));
+        }
+
         Self {
             id,
             base,
             kind,
             stage: VotingStage::InProgress,
             topics,
+            batch_outcomes: None,
         }
     }
 
@@ -136,21 +348,26 @@ impl Voting {
         &mut self,
         option_idx: u32,
         normalized_approval_level: Option<E8s>,
+        rationale: Option<String>,
         rep_delegation_tree: ReputationDelegationTreeNode,
-    ) -> Result<Option<CallToExecute>, VotingEvent> {
+    ) -> Result<Option<CallToExecute>, Box<VotingEvent>> {
+        let weighting = self.base.weighting.unwrap_or_default();
+        let caster = rep_delegation_tree.id;
         let option_votes = self
             .base
             .votes_per_option
             .get_mut(option_idx as usize)
             .unwrap();
 
+        option_votes.set_rationale(caster, rationale);
+
         rep_delegation_tree.traverse(
             &mut |node, depth| {
                 if !node.topicset.matches(&self.topics) {
                     return false;
                 }
 
-                let can_cast = option_votes.revert_prev_vote(&node.id, depth);
+                let can_cast = option_votes.revert_prev_vote_by(&node.id, depth, caster);
 
                 if !can_cast {
                     return false;
@@ -159,7 +376,8 @@ impl Voting {
                 let vote = Vote {
                     depth,
                     normalized_approval_level: normalized_approval_level.clone(),
-                    total_voter_reputation: node.reputation.clone(),
+                    total_voter_reputation: weighting.apply(&node.reputation),
+                    cast_by: Some(caster),
                 };
 
                 option_votes.cast_vote(node.id, vote);
@@ -173,42 +391,367 @@ impl Voting {
             return Ok(None);
         }
 
+        if self.is_motion() {
+            return Err(Box::new(self.resolve_motion()));
+        }
+
         self.stage = VotingStage::Executing;
 
-        let call_or_fail = self.kind.generate_resulting_call(&self.base);
+        let call_or_fail = self.kind.generate_resulting_call(self.id, None, &self.base);
 
         if let Some(call) = call_or_fail {
             Ok(Some(call))
         } else {
-            Err(VotingEvent::V0001(VotingEventV1::VotingFail {
+            Err(Box::new(VotingEvent::V0001(VotingEventV1::VotingFail {
+                voting_id: self.id,
+                reason: "Consensus not reached".to_string(),
+            })))
+        }
+    }
+
+    pub fn cast_spam_vote(
+        &mut self,
+        spam: bool,
+        rep_delegation_tree: ReputationDelegationTreeNode,
+    ) {
+        let weighting = self.base.weighting.unwrap_or_default();
+        let caster = rep_delegation_tree.id;
+        let spam_votes = self.base.spam_votes.as_mut().unwrap();
+        let normalized_approval_level = if spam { Some(E8s::one()) } else { None };
+
+        rep_delegation_tree.traverse(
+            &mut |node, depth| {
+                if !node.topicset.matches(&self.topics) {
+                    return false;
+                }
+
+                let can_cast = spam_votes.revert_prev_vote(&node.id, depth);
+
+                if !can_cast {
+                    return false;
+                }
+
+                let vote = Vote {
+                    depth,
+                    normalized_approval_level: normalized_approval_level.clone(),
+                    total_voter_reputation: weighting.apply(&node.reputation),
+                    cast_by: Some(caster),
+                };
+
+                spam_votes.cast_vote(node.id, vote);
+
+                true
+            },
+            0,
+        );
+    }
+
+    pub fn cast_choice_vote(
+        &mut self,
+        ballot: ChoiceBallot,
+        rationale: Option<String>,
+        rep_delegation_tree: ReputationDelegationTreeNode,
+    ) -> Result<Option<CallToExecute>, Box<VotingEvent>> {
+        let weighting = self.base.weighting.unwrap_or_default();
+        let caster = rep_delegation_tree.id;
+        let choice_votes = self
+            .base
+            .choice_votes
+            .as_mut()
+            .expect("UNREACHEABLE! Not a multi-choice voting");
+
+        choice_votes.set_rationale(caster, rationale);
+
+        // the whole ballot is passed down to the delegators
+        rep_delegation_tree.traverse(
+            &mut |node, depth| {
+                if !node.topicset.matches(&self.topics) {
+                    return false;
+                }
+
+                let can_cast = choice_votes.revert_prev_vote_by(&node.id, depth, caster);
+
+                if !can_cast {
+                    return false;
+                }
+
+                let vote = ChoiceVote {
+                    depth,
+                    ballot: ballot.clone(),
+                    total_voter_reputation: weighting.apply(&node.reputation),
+                    cast_by: Some(caster),
+                };
+
+                choice_votes.cast_vote(node.id, vote);
+
+                true
+            },
+            0,
+        );
+
+        if !self.base.is_finish_early_reached_for_all_options() {
+            return Ok(None);
+        }
+
+        Err(Box::new(self.resolve_choice()))
+    }
+
+    pub fn cast_commitment(
+        &mut self,
+        option_idx: u32,
+        hash: [u8; 32],
+        rep_delegation_tree: ReputationDelegationTreeNode,
+        caller: Principal,
+    ) {
+        let weighting = self.base.weighting.unwrap_or_default();
+        let commitments = self
+            .base
+            .secret_ballots
+            .as_mut()
+            .expect("UNREACHEABLE! Not a secret voting")
+            .per_option
+            .get_mut(option_idx as usize)
+            .unwrap();
+
+        // only the participation is counted now, the vote itself is applied to the same people on reveal
+        rep_delegation_tree.traverse(
+            &mut |node, depth| {
+                if !node.topicset.matches(&self.topics) {
+                    return false;
+                }
+
+                let can_participate = commitments.revert_prev_participation(&node.id, depth);
+
+                if !can_participate {
+                    return false;
+                }
+
+                let participant = Participant {
+                    depth,
+                    total_voter_reputation: weighting.apply(&node.reputation),
+                    committed_by: caller,
+                };
+
+                commitments.participate(node.id, participant);
+
+                true
+            },
+            0,
+        );
+
+        commitments.hashes.insert(caller, hash);
+    }
+
+    pub fn reveal_vote(
+        &mut self,
+        option_idx: u32,
+        normalized_approval_level: Option<E8s>,
+        ballot: Option<ChoiceBallot>,
+        rationale: Option<String>,
+        caller: Principal,
+    ) {
+        let commitments = self
+            .base
+            .secret_ballots
+            .as_mut()
+            .expect("UNREACHEABLE! Not a secret voting")
+            .per_option
+            .get_mut(option_idx as usize)
+            .unwrap();
+
+        commitments.hashes.remove(&caller);
+
+        if let (Some(choice_votes), Some(_)) = (&mut self.base.choice_votes, &ballot) {
+            choice_votes.set_rationale(caller, rationale);
+        } else {
+            self.base.votes_per_option[option_idx as usize].set_rationale(caller, rationale);
+        }
+
+        // everyone the caller has committed for gets the same vote
+        let participants = commitments
+            .participants
+            .iter()
+            .filter(|(_, it)| it.committed_by == caller)
+            .map(|(id, it)| (*id, it.clone()));
+
+        for (id, participant) in participants {
+            if let (Some(choice_votes), Some(ballot)) = (&mut self.base.choice_votes, &ballot) {
+                let vote = ChoiceVote {
+                    depth: participant.depth,
+                    ballot: ballot.clone(),
+                    total_voter_reputation: participant.total_voter_reputation,
+                    cast_by: Some(caller),
+                };
+
+                choice_votes.cast_vote(id, vote);
+            } else {
+                let vote = Vote {
+                    depth: participant.depth,
+                    normalized_approval_level: normalized_approval_level.clone(),
+                    total_voter_reputation: participant.total_voter_reputation,
+                    cast_by: Some(caller),
+                };
+
+                self.base.votes_per_option[option_idx as usize].cast_vote(id, vote);
+            }
+        }
+    }
+
+    // the commitment phase is over - the participation is checked against the quorum and votes can now be revealed
+    pub fn start_reveal(&mut self, now: TimestampNs) -> Result<VotingTimer, Box<VotingEvent>> {
+        let secret_ballots = self
+            .base
+            .secret_ballots
+            .as_ref()
+            .expect("UNREACHEABLE! Not a secret voting");
+
+        if !secret_ballots
+            .per_option
+            .iter()
+            .all(|it| it.total_committed >= self.base.quorum)
+        {
+            return Err(Box::new(VotingEvent::V0001(VotingEventV1::VotingFail {
                 voting_id: self.id,
-                reason: format!("Consensus not reached"),
-            }))
+                reason: "Quorum not reached for all options".to_string(),
+            })));
+        }
+
+        let until = now + secret_ballots.reveal_duration_ns;
+        self.stage = VotingStage::Revealing { until };
+
+        Ok(VotingTimer::ExecOnQuorum {
+            voting_id: self.id,
+            timestamp: until,
+        })
+    }
+
+    // unrevealed commitments count towards the participation, but not towards the approval
+    fn close_reveal(&mut self) {
+        let secret_ballots = match &self.base.secret_ballots {
+            Some(it) => it,
+            None => return,
+        };
+
+        for (idx, commitments) in secret_ballots.per_option.iter().enumerate() {
+            if let Some(choice_votes) = &mut self.base.choice_votes {
+                choice_votes.total_voted = commitments.total_committed.clone();
+            } else {
+                self.base.votes_per_option[idx].total_voted = commitments.total_committed.clone();
+            }
         }
     }
 
-    pub fn resolve_on_timer(&mut self) -> Result<CallToExecute, VotingEvent> {
+    pub fn resolve_on_timer(&mut self) -> Result<CallToExecute, Box<VotingEvent>> {
+        if self.is_revealing() {
+            self.close_reveal();
+        }
+
         if !self.base.is_quorum_reached_for_all_options() {
-            return Err(VotingEvent::V0001(VotingEventV1::VotingFail {
+            return Err(Box::new(VotingEvent::V0001(VotingEventV1::VotingFail {
                 voting_id: self.id,
-                reason: format!("Quorum not reached for all options"),
-            }));
+                reason: "Quorum not reached for all options".to_string(),
+            })));
+        }
+
+        if self.is_motion() {
+            return Err(Box::new(self.resolve_motion()));
+        }
+
+        if self.base.choice_votes.is_some() {
+            return Err(Box::new(self.resolve_choice()));
         }
 
         self.stage = VotingStage::Executing;
 
-        let call_or_fail = self.kind.generate_resulting_call(&self.base);
+        let call_or_fail = self.kind.generate_resulting_call(self.id, None, &self.base);
 
         if let Some(call) = call_or_fail {
             Ok(call)
         } else {
-            return Err(VotingEvent::V0001(VotingEventV1::VotingFail {
+            Err(Box::new(VotingEvent::V0001(VotingEventV1::VotingFail {
+                voting_id: self.id,
+                reason: "Consensus not reached".to_string(),
+            })))
+        }
+    }
+
+    // motions have nothing to execute - they are finished as soon as the decision is made
+    fn resolve_motion(&self) -> VotingEvent {
+        if self.base.calc_binary_results()[0] {
+            VotingEvent::V0001(VotingEventV1::VotingSuccess { voting_id: self.id })
+        } else {
+            VotingEvent::V0001(VotingEventV1::VotingFail {
+                voting_id: self.id,
+                reason: "Consensus not reached".to_string(),
+            })
+        }
+    }
+
+    // multi-choice votings don't execute anything either, the winner is saved in the event log
+    fn resolve_choice(&self) -> VotingEvent {
+        let (winner_opt, rounds) = self.base.calc_choice_results();
+
+        if let Some(winner) = winner_opt {
+            VotingEvent::V0001(VotingEventV1::MultiChoiceVotingResolved {
+                voting_id: self.id,
+                winner,
+                rounds,
+            })
+        } else {
+            VotingEvent::V0001(VotingEventV1::VotingFail {
                 voting_id: self.id,
-                reason: format!("Consensus not reached"),
-            }));
+                reason: "No option has won".to_string(),
+            })
         }
     }
 
+    pub fn is_motion(&self) -> bool {
+        matches!(
+            self.kind,
+            VotingKind::Motion {
+                title: _,
+                body: _,
+                topics: _,
+            }
+        )
+    }
+
+    pub fn to_archived_motion(
+        self,
+        id: MotionId,
+        accepted: bool,
+        now: TimestampNs,
+    ) -> Option<ArchivedMotion> {
+        let (title, body, topics) = match self.kind {
+            VotingKind::Motion {
+                title,
+                body,
+                topics,
+            } => (title, body, topics),
+            _ => return None,
+        };
+
+        let tally = self.base.votes_per_option.into_iter().next()?;
+
+        Some(ArchivedMotion::V0001(ArchivedMotionV0001 {
+            id,
+            title,
+            body,
+            topics,
+            creator: self.base.creator,
+            created_at: self.base.created_at,
+            finished_at: now,
+            accepted,
+            total_supply: self.base.total_supply,
+            quorum: self.base.quorum,
+            consensus_normalized: self.base.consensus_normalized,
+            total_voted: tally.total_voted,
+            approve: tally.approve,
+            reject: tally.reject,
+            rationales: tally.rationales.map(|it| it.into_iter().collect()),
+        }))
+    }
+
     pub fn as_ext(&self, caller: Principal) -> VotingExt {
         let votes_per_option = self
             .base
@@ -234,22 +777,194 @@ impl Voting {
             votes_per_option,
             kind: self.kind.clone(),
             stage: self.stage.clone(),
+            args_preview: self.kind.get_args_preview(),
+            choice_votes: self
+                .base
+                .choice_votes
+                .as_ref()
+                .map(|it| (it.calc_first_preferences(), it.votes.get(&caller).cloned())),
+            weighting: self.base.weighting.unwrap_or_default(),
+            secret_ballots: self.base.secret_ballots.as_ref().map(|it| {
+                it.per_option
+                    .iter()
+                    .map(|c| (c.total_committed.clone(), c.hashes.contains_key(&caller)))
+                    .collect()
+            }),
+            deposit: self.base.deposit.clone(),
+            spam_votes: self.base.spam_votes.as_ref().map(|it| {
+                (
+                    it.approve.clone(),
+                    it.reject.clone(),
+                    it.votes.get(&caller).cloned(),
+                )
+            }),
+            rationales: self.base.list_rationales(),
+            batch_outcomes: self.batch_outcomes.clone(),
         }
     }
 
-    pub fn can_cast_vote(&self) -> bool {
-        matches!(self.stage, VotingStage::InProgress)
-    }
-
-    pub fn can_execute_on_timer(&self) -> bool {
+    pub fn validate_vote(
+        &self,
+        option_idx: u32,
+        ballot: &Option<ChoiceBallot>,
+    ) -> Result<(), String> {
+        match (&self.base.choice_votes, ballot) {
+            (Some(choice_votes), Some(ballot)) => choice_votes.validate_ballot(ballot),
+            (None, None) => {
+                if option_idx as usize >= self.base.votes_per_option.len() {
+                    return Err(format!("Option {} does not exist", option_idx));
+                }
+
+                Ok(())
+            }
+            _ => Err("Invalid ballot type".to_string()),
+        }
+    }
+
+    pub fn can_cast_vote(&self) -> bool {
         matches!(self.stage, VotingStage::InProgress)
     }
+
+    pub fn is_revealing(&self) -> bool {
+        matches!(self.stage, VotingStage::Revealing { until: _ })
+    }
+
+    pub fn can_execute_on_timer(&self) -> bool {
+        self.can_cast_vote() || self.is_revealing()
+    }
+
+    pub fn is_queued(&self) -> bool {
+        matches!(self.stage, VotingStage::Queued { execute_at: _ })
+    }
+
+    // guardians can block a decision at any moment before it is executed
+    pub fn can_be_vetoed(&self) -> bool {
+        self.can_cast_vote()
+            || self.is_revealing()
+            || self.is_queued()
+            || self.can_retry_execution()
+    }
+
+    pub fn has_passed(&self) -> bool {
+        matches!(
+            self.stage,
+            VotingStage::Queued { .. }
+                | VotingStage::Executing
+                | VotingStage::ExecutionFailed { .. }
+        )
+    }
+
+    pub fn can_retry_execution(&self) -> bool {
+        matches!(
+            self.stage,
+            VotingStage::ExecutionFailed {
+                error: _,
+                attempts: _,
+                next_retry_at: _,
+                expires_at: _,
+                retrying: false,
+            }
+        )
+    }
+
+    // these stages are only kept while the resulting call is awaited
+    pub fn is_execution_in_flight(&self) -> bool {
+        matches!(
+            self.stage,
+            VotingStage::Executing | VotingStage::ExecutionFailed { retrying: true, .. }
+        )
+    }
+
+    // returns the call to retry, if the execution has failed before
+    pub fn start_retry(&mut self) -> Option<CallToExecute> {
+        if let VotingStage::ExecutionFailed { retrying, .. } = &mut self.stage {
+            if *retrying {
+                return None;
+            }
+
+            *retrying = true;
+
+            return self.kind.generate_resulting_call(self.id, None, &self.base);
+        }
+
+        None
+    }
+
+    // returns the timer of the next retry along with the event to log, or the event to finish the voting with, if the execution has expired
+    pub fn fail_execution(
+        &mut self,
+        error: String,
+        expiry_ns: DurationNs,
+        now: TimestampNs,
+    ) -> Result<(VotingTimer, VotingEvent), Box<VotingEvent>> {
+        let (attempts, expires_at) = match &self.stage {
+            VotingStage::ExecutionFailed {
+                error: _,
+                attempts,
+                next_retry_at: _,
+                expires_at,
+                retrying: _,
+            } => (attempts + 1, *expires_at),
+            _ => (1, now + expiry_ns),
+        };
+
+        if now >= expires_at {
+            return Err(Box::new(VotingEvent::V0001(VotingEventV1::VotingFail {
+                voting_id: self.id,
+                reason: format!("{} (gave up after {} attempts)", error, attempts),
+            })));
+        }
+
+        // one minute, doubled with each attempt, but no longer than a day
+        let backoff_ns = ONE_MINUTE_NS
+            .saturating_mul(1 << (attempts - 1).min(20))
+            .min(ONE_DAY_NS);
+        let next_retry_at = (now + backoff_ns).min(expires_at);
+
+        let event = VotingEvent::V0001(VotingEventV1::VotingExecutionFailed {
+            voting_id: self.id,
+            reason: error.clone(),
+            attempts,
+            next_retry_at,
+        });
+
+        self.stage = VotingStage::ExecutionFailed {
+            error,
+            attempts,
+            next_retry_at,
+            expires_at,
+            retrying: false,
+        };
+
+        let timer = VotingTimer::RetryExecution {
+            voting_id: self.id,
+            timestamp: next_retry_at,
+        };
+
+        Ok((timer, event))
+    }
 }
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub enum VotingStage {
     InProgress,
+    // only in secret votings, after the commitments are cast
+    Revealing {
+        until: TimestampNs,
+    },
+    // the decision is made, but it waits for the timelock to be over
+    Queued {
+        execute_at: TimestampNs,
+    },
     Executing,
+    // the decision is made, but the call has failed - it is retried until it expires
+    ExecutionFailed {
+        error: String,
+        attempts: u32,
+        next_retry_at: TimestampNs,
+        expires_at: TimestampNs,
+        retrying: bool,
+    },
 }
 
 #[derive(CandidType, Deserialize, Validate, Clone, Debug)]
@@ -287,10 +1002,168 @@ pub enum VotingKind {
         #[garde(skip)]
         team_member: Principal,
     },
+    // only works for (canister, method) pairs from the allow-list
+    GenericCall {
+        #[garde(skip)]
+        canister_id: Principal,
+        #[garde(length(min = 1, max = 128))]
+        method_name: String,
+        #[garde(length(max = 65536))]
+        args_raw: Vec<u8>,
+        #[garde(length(graphemes, min = 16, max = 4096))]
+        description: String,
+    },
+    UpdateGenericCallAllowList {
+        #[garde(dive)]
+        add: Vec<GenericCallTarget>,
+        #[garde(dive)]
+        remove: Vec<GenericCallTarget>,
+    },
+    SetVotingParams {
+        #[garde(skip)]
+        kind: VotingKindTag,
+        #[garde(skip)]
+        params: VotingParams,
+    },
+    // a signalling proposal, nothing is executed - only the final tally is archived
+    Motion {
+        #[garde(length(graphemes, min = 1, max = 256))]
+        title: String,
+        #[garde(length(graphemes, min = 16, max = 4096))]
+        body: String,
+        #[garde(length(min = 1))]
+        topics: Vec<DecisionTopicId>,
+    },
+    // a signalling proposal, where one of the options is picked
+    MultiChoice {
+        #[garde(length(graphemes, min = 1, max = 256))]
+        title: String,
+        #[garde(length(graphemes, min = 16, max = 4096))]
+        body: String,
+        #[garde(length(min = 1))]
+        topics: Vec<DecisionTopicId>,
+        #[garde(
+            length(min = 2, max = 16),
+            inner(length(graphemes, min = 1, max = 256))
+        )]
+        options: Vec<String>,
+        #[garde(skip)]
+        mode: ChoiceMode,
+    },
+    SetConvictionParams {
+        #[garde(skip)]
+        params: ConvictionParams,
+    },
+    // cancels a queued decision before its timelock is over
+    Veto {
+        #[garde(skip)]
+        voting_id: VotingId,
+        #[garde(length(graphemes, min = 16, max = 4096))]
+        reason: String,
+    },
+    SetGuardianCouncil {
+        #[garde(dive)]
+        council: GuardianCouncil,
+    },
+    // several actions voted on as one unit and executed one by one, in order
+    Batch {
+        #[garde(length(graphemes, min = 16, max = 4096))]
+        description: String,
+        #[garde(length(min = 2, max = 8), dive)]
+        actions: Vec<VotingKind>,
+        #[garde(skip)]
+        on_failure: BatchFailurePolicy,
+    },
+    CreateDecisionTopic {
+        #[garde(length(graphemes, min = 1, max = 64))]
+        name: String,
+        #[garde(length(graphemes, min = 16, max = 1024))]
+        description: String,
+    },
+    EditDecisionTopic {
+        #[garde(skip)]
+        id: DecisionTopicId,
+        #[garde(inner(length(graphemes, min = 1, max = 64)))]
+        new_name_opt: Option<String>,
+        #[garde(inner(length(graphemes, min = 16, max = 1024)))]
+        new_description_opt: Option<String>,
+    },
+    // delegations on the deprecated topic are extended to its successors, if there are any
+    DeprecateDecisionTopic {
+        #[garde(skip)]
+        id: DecisionTopicId,
+        #[garde(length(max = 8))]
+        successors: Vec<DecisionTopicId>,
+    },
+    SetParticipationParams {
+        #[garde(skip)]
+        params: ParticipationParams,
+    },
+    // the wasm should be staged and verified beforehand, since it's too big to be stored inside the voting
+    UpgradeCanister {
+        #[garde(skip)]
+        canister_id: Principal,
+        #[garde(skip)]
+        wasm_hash: WasmHash,
+        #[garde(length(max = 65536))]
+        arg: Vec<u8>,
+    },
+    // is only executed if it fits into the spending cap of the current period
+    TreasuryTransfer {
+        #[garde(dive)]
+        asset: TreasuryAsset,
+        #[garde(skip)]
+        from_subaccount: Option<Subaccount>,
+        #[garde(skip)]
+        to: Account,
+        #[garde(skip)]
+        amount: E8s,
+        #[garde(inner(length(max = 32)))]
+        memo: Option<Vec<u8>>,
+    },
+    SetTreasuryCaps {
+        #[garde(skip)]
+        caps: TreasuryCaps,
+    },
+    SlashReputation {
+        #[garde(skip)]
+        target: Principal,
+        #[garde(dive)]
+        amount_or_fraction: SlashAmount,
+        #[garde(length(graphemes, min = 16, max = 4096))]
+        reason: String,
+    },
+    SetDecayParams {
+        #[garde(skip)]
+        params: DecayParams,
+    },
 }
 
 impl VotingKind {
-    pub fn get_id(&self) -> VotingId {
+    // only actions that do nothing when rejected, since a rejected batch executes nothing at all
+    pub fn can_be_batched(&self) -> bool {
+        matches!(
+            self.get_tag(),
+            VotingKindTag::BankSetExchangeRate
+                | VotingKindTag::HumansEmploy
+                | VotingKindTag::HumansUnemploy
+                | VotingKindTag::GenericCall
+                | VotingKindTag::UpdateGenericCallAllowList
+                | VotingKindTag::SetVotingParams
+                | VotingKindTag::SetConvictionParams
+                | VotingKindTag::CreateDecisionTopic
+                | VotingKindTag::EditDecisionTopic
+                | VotingKindTag::DeprecateDecisionTopic
+                | VotingKindTag::SetParticipationParams
+                | VotingKindTag::TreasuryTransfer
+                | VotingKindTag::SetTreasuryCaps
+                | VotingKindTag::SlashReputation
+                | VotingKindTag::SetDecayParams
+        )
+    }
+
+    // votings on the same target conflict with each other
+    pub fn get_target(&self) -> VotingId {
         match self {
             VotingKind::StartSolveTask { task_id } => VotingId::StartSolveTask(*task_id),
             VotingKind::EvaluateTask {
@@ -308,10 +1181,239 @@ impl VotingKind {
                 hours_a_week_commitment: _,
             } => VotingId::HumansEmploy(*candidate),
             VotingKind::HumansUnemploy { team_member } => VotingId::HumansUnemploy(*team_member),
+            VotingKind::GenericCall {
+                canister_id,
+                method_name,
+                args_raw,
+                description: _,
+            } => {
+                // the same call can't be voted for twice at the same time
+                let mut hasher = sha2::Sha256::new();
+                hasher.update(canister_id.as_slice().len().to_le_bytes());
+                hasher.update(canister_id.as_slice());
+                hasher.update(method_name.len().to_le_bytes());
+                hasher.update(method_name.as_bytes());
+                hasher.update(args_raw);
+
+                VotingId::GenericCall(hasher.finalize().into())
+            }
+            VotingKind::UpdateGenericCallAllowList { add: _, remove: _ } => {
+                VotingId::UpdateGenericCallAllowList
+            }
+            VotingKind::SetVotingParams { kind, params: _ } => VotingId::SetVotingParams(*kind),
+            VotingKind::Motion {
+                title,
+                body,
+                topics: _,
+            } => {
+                let mut hasher = sha2::Sha256::new();
+                hasher.update(title.len().to_le_bytes());
+                hasher.update(title.as_bytes());
+                hasher.update(body.as_bytes());
+
+                VotingId::Motion(hasher.finalize().into())
+            }
+            VotingKind::MultiChoice {
+                title,
+                body,
+                topics: _,
+                options: _,
+                mode: _,
+            } => {
+                let mut hasher = sha2::Sha256::new();
+                hasher.update(title.len().to_le_bytes());
+                hasher.update(title.as_bytes());
+                hasher.update(body.as_bytes());
+
+                VotingId::MultiChoice(hasher.finalize().into())
+            }
+            VotingKind::SetConvictionParams { params: _ } => VotingId::SetConvictionParams,
+            VotingKind::Veto {
+                voting_id,
+                reason: _,
+            } => voting_id.veto_target(),
+            VotingKind::SetGuardianCouncil { council: _ } => VotingId::SetGuardianCouncil,
+            VotingKind::Batch {
+                description: _,
+                actions,
+                on_failure: _,
+            } => {
+                let encoded = encode_one(actions).expect("Unable to encode batch actions");
+
+                VotingId::Batch(sha2::Sha256::digest(encoded).into())
+            }
+            VotingKind::CreateDecisionTopic {
+                name,
+                description: _,
+            } => {
+                // topic names are unique regardless of the case
+                VotingId::CreateDecisionTopic(
+                    sha2::Sha256::digest(name.to_lowercase().as_bytes()).into(),
+                )
+            }
+            VotingKind::EditDecisionTopic {
+                id,
+                new_name_opt: _,
+                new_description_opt: _,
+            } => VotingId::EditDecisionTopic(*id),
+            VotingKind::DeprecateDecisionTopic { id, successors: _ } => {
+                VotingId::DeprecateDecisionTopic(*id)
+            }
+            VotingKind::SetParticipationParams { params: _ } => VotingId::SetParticipationParams,
+            VotingKind::UpgradeCanister {
+                canister_id,
+                wasm_hash: _,
+                arg: _,
+            } => VotingId::UpgradeCanister(*canister_id),
+            VotingKind::TreasuryTransfer { .. } => {
+                // the same transfer can't be voted for twice at the same time
+                let encoded = encode_one(self).expect("Unable to encode a treasury transfer");
+
+                VotingId::TreasuryTransfer(sha2::Sha256::digest(encoded).into())
+            }
+            VotingKind::SetTreasuryCaps { caps: _ } => VotingId::SetTreasuryCaps,
+            VotingKind::SlashReputation {
+                target,
+                amount_or_fraction: _,
+                reason: _,
+            } => VotingId::SlashReputation(*target),
+            VotingKind::SetDecayParams { params: _ } => VotingId::SetDecayParams,
+        }
+    }
+
+    pub fn get_tag(&self) -> VotingKindTag {
+        match self {
+            VotingKind::StartSolveTask { task_id: _ } => VotingKindTag::StartSolveTask,
+            VotingKind::EvaluateTask {
+                task_id: _,
+                solutions: _,
+            } => VotingKindTag::EvaluateTask,
+            VotingKind::DeleteTask { task_id: _ } => VotingKindTag::DeleteTask,
+            VotingKind::BankSetExchangeRate {
+                from: _,
+                into: _,
+                new_rate: _,
+            } => VotingKindTag::BankSetExchangeRate,
+            VotingKind::HumansEmploy {
+                candidate: _,
+                hours_a_week_commitment: _,
+            } => VotingKindTag::HumansEmploy,
+            VotingKind::HumansUnemploy { team_member: _ } => VotingKindTag::HumansUnemploy,
+            VotingKind::GenericCall {
+                canister_id: _,
+                method_name: _,
+                args_raw: _,
+                description: _,
+            } => VotingKindTag::GenericCall,
+            VotingKind::UpdateGenericCallAllowList { add: _, remove: _ } => {
+                VotingKindTag::UpdateGenericCallAllowList
+            }
+            VotingKind::SetVotingParams { kind: _, params: _ } => VotingKindTag::SetVotingParams,
+            VotingKind::Motion {
+                title: _,
+                body: _,
+                topics: _,
+            } => VotingKindTag::Motion,
+            VotingKind::MultiChoice {
+                title: _,
+                body: _,
+                topics: _,
+                options: _,
+                mode: _,
+            } => VotingKindTag::MultiChoice,
+            VotingKind::SetConvictionParams { params: _ } => VotingKindTag::SetConvictionParams,
+            VotingKind::Veto {
+                voting_id: _,
+                reason: _,
+            } => VotingKindTag::Veto,
+            VotingKind::SetGuardianCouncil { council: _ } => VotingKindTag::SetGuardianCouncil,
+            VotingKind::Batch {
+                description: _,
+                actions: _,
+                on_failure: _,
+            } => VotingKindTag::Batch,
+            VotingKind::CreateDecisionTopic {
+                name: _,
+                description: _,
+            } => VotingKindTag::CreateDecisionTopic,
+            VotingKind::EditDecisionTopic {
+                id: _,
+                new_name_opt: _,
+                new_description_opt: _,
+            } => VotingKindTag::EditDecisionTopic,
+            VotingKind::DeprecateDecisionTopic {
+                id: _,
+                successors: _,
+            } => VotingKindTag::DeprecateDecisionTopic,
+            VotingKind::SetParticipationParams { params: _ } => {
+                VotingKindTag::SetParticipationParams
+            }
+            VotingKind::UpgradeCanister {
+                canister_id: _,
+                wasm_hash: _,
+                arg: _,
+            } => VotingKindTag::UpgradeCanister,
+            VotingKind::TreasuryTransfer {
+                asset: _,
+                from_subaccount: _,
+                to: _,
+                amount: _,
+                memo: _,
+            } => VotingKindTag::TreasuryTransfer,
+            VotingKind::SetTreasuryCaps { caps: _ } => VotingKindTag::SetTreasuryCaps,
+            VotingKind::SlashReputation {
+                target: _,
+                amount_or_fraction: _,
+                reason: _,
+            } => VotingKindTag::SlashReputation,
+            VotingKind::SetDecayParams { params: _ } => VotingKindTag::SetDecayParams,
+        }
+    }
+
+    pub fn get_args_preview(&self) -> Option<String> {
+        match self {
+            VotingKind::GenericCall {
+                canister_id: _,
+                method_name: _,
+                args_raw,
+                description: _,
+            } => Some(
+                IDLArgs::from_bytes(args_raw)
+                    .map(|args| args.to_string())
+                    .unwrap_or_else(|e| format!("Unable to decode the arguments: {}", e)),
+            ),
+            VotingKind::UpgradeCanister {
+                canister_id: _,
+                wasm_hash: _,
+                arg,
+            } => Some(
+                IDLArgs::from_bytes(arg)
+                    .map(|args| args.to_string())
+                    .unwrap_or_else(|e| format!("Unable to decode the arguments: {}", e)),
+            ),
+            _ => None,
+        }
+    }
+
+    // batches are the only ones to actually have the targets different from the kind itself
+    pub fn get_targets(&self) -> Vec<VotingId> {
+        match self {
+            VotingKind::Batch {
+                description: _,
+                actions,
+                on_failure: _,
+            } => actions.iter().map(|it| it.get_target()).collect(),
+            _ => vec![self.get_target()],
         }
     }
 
-    pub fn generate_resulting_call(&self, base: &VotingBase) -> Option<CallToExecute> {
+    // batch steps share the voting id, so the step is passed along for the calls that are deduplicated by it
+    pub fn generate_resulting_call(
+        &self,
+        id: VotingId,
+        batch_step: Option<u32>,
+        base: &VotingBase,
+    ) -> Option<CallToExecute> {
         let result = match self {
             VotingKind::StartSolveTask { task_id } => {
                 let result = base.calc_binary_results()[0];
@@ -425,261 +1527,2704 @@ impl VotingKind {
                     (req,),
                 )
             }
-        };
+            VotingKind::GenericCall {
+                canister_id,
+                method_name,
+                args_raw,
+                description: _,
+            } => {
+                let result = base.calc_binary_results()[0];
 
-        let call = result.expect("UNREACHEABLE! Unable to encode args for the call");
+                if !result {
+                    return None;
+                }
 
-        Some(call)
-    }
-}
+                Ok(CallToExecute {
+                    canister_id: *canister_id,
+                    method_name: method_name.clone(),
+                    args_raw: args_raw.clone(),
+                })
+            }
+            VotingKind::UpdateGenericCallAllowList { add, remove } => {
+                let result = base.calc_binary_results()[0];
 
-#[derive(CandidType, Deserialize, Clone, Debug)]
-pub struct VotingBase {
-    pub creator: Principal,
-    pub created_at: TimestampNs,
-    pub duration_ns: DurationNs,
-    pub total_supply: E8s,
-    pub quorum: E8s,
-    pub consensus_normalized: E8s,
-    pub finish_early: E8s,
-    pub votes_per_option: Vec<OptionVotes>,
-}
+                if !result {
+                    return None;
+                }
 
-impl VotingBase {
-    pub fn new(
-        duration_ns: DurationNs,
-        total_supply: E8s,
-        quorum: E8s,
-        consensus_normalized: E8s,
-        finish_early: E8s,
-        num_options: u32,
-        caller: Principal,
-        now: TimestampNs,
-    ) -> Self {
-        Self {
-            creator: caller,
-            created_at: now,
-            duration_ns,
-            total_supply,
-            quorum,
-            consensus_normalized,
-            finish_early,
-            votes_per_option: vec![OptionVotes::default(); num_options as usize],
-        }
-    }
+                let req = UpdateGenericCallAllowListRequest {
+                    add: add.clone(),
+                    remove: remove.clone(),
+                };
 
-    pub fn is_quorum_reached_for_all_options(&self) -> bool {
-        for option_votes in &self.votes_per_option {
-            if !option_votes.total_reached_threshold(&self.quorum) {
-                return false;
+                CallToExecute::new(
+                    ENV_VARS.votings_canister_id,
+                    "votings__update_generic_call_allow_list".into(),
+                    (req,),
+                )
             }
-        }
+            VotingKind::SetVotingParams { kind, params } => {
+                let result = base.calc_binary_results()[0];
 
-        true
-    }
+                if !result {
+                    return None;
+                }
 
-    pub fn is_finish_early_reached_for_all_options(&self) -> bool {
-        for option_votes in &self.votes_per_option {
-            if !option_votes.total_reached_threshold(&self.finish_early) {
-                return false;
+                let req = SetVotingParamsRequest {
+                    kind: *kind,
+                    params: params.clone(),
+                };
+
+                CallToExecute::new(
+                    ENV_VARS.votings_canister_id,
+                    "votings__set_voting_params".into(),
+                    (req,),
+                )
             }
-        }
+            VotingKind::Motion {
+                title: _,
+                body: _,
+                topics: _,
+            } => return None,
+            VotingKind::MultiChoice {
+                title: _,
+                body: _,
+                topics: _,
+                options: _,
+                mode: _,
+            } => return None,
+            VotingKind::SetConvictionParams { params } => {
+                let result = base.calc_binary_results()[0];
 
-        true
-    }
+                if !result {
+                    return None;
+                }
 
-    pub fn calc_results_raw_normilized(&self) -> Vec<(E8s, E8s, E8s)> {
-        self.votes_per_option
-            .iter()
-            .map(OptionVotes::get_normalized_results)
-            .collect()
-    }
+                let req = SetConvictionParamsRequest {
+                    params: params.clone(),
+                };
 
-    pub fn calc_ranged_results_normalized(&self) -> Vec<Option<E8s>> {
-        let raw_results = self.calc_results_raw_normilized();
-        let one = E8s::one();
+                CallToExecute::new(
+                    ENV_VARS.votings_canister_id,
+                    "votings__set_conviction_params".into(),
+                    (req,),
+                )
+            }
+            VotingKind::Veto { voting_id, reason } => {
+                let result = base.calc_binary_results()[0];
 
-        raw_results
-            .into_iter()
-            .map(|(_, approve, reject)| {
-                if reject > (&one - &self.consensus_normalized) {
-                    None
-                } else {
-                    Some(approve)
+                if !result {
+                    return None;
                 }
-            })
-            .collect()
-    }
 
-    pub fn calc_binary_results(&self) -> Vec<bool> {
-        let raw_results = self.calc_results_raw_normilized();
+                let req = CancelQueuedVotingRequest {
+                    id: *voting_id,
+                    reason: reason.clone(),
+                };
 
-        raw_results
-            .into_iter()
-            .map(|(_, approve, _)| approve >= self.consensus_normalized)
+                CallToExecute::new(
+                    ENV_VARS.votings_canister_id,
+                    "votings__cancel_queued_voting".into(),
+                    (req,),
+                )
+            }
+            VotingKind::SetGuardianCouncil { council } => {
+                let result = base.calc_binary_results()[0];
+
+                if !result {
+                    return None;
+                }
+
+                let req = SetGuardianCouncilRequest {
+                    council: council.clone(),
+                };
+
+                CallToExecute::new(
+                    ENV_VARS.votings_canister_id,
+                    "votings__set_guardian_council".into(),
+                    (req,),
+                )
+            }
+            // the votings canister executes the actions itself, so it could record the outcome of each one
+            VotingKind::Batch {
+                description: _,
+                actions: _,
+                on_failure: _,
+            } => {
+                let result = base.calc_binary_results()[0];
+
+                if !result {
+                    return None;
+                }
+
+                let req = ExecuteBatchRequest { id };
+
+                CallToExecute::new(
+                    ENV_VARS.votings_canister_id,
+                    "votings__execute_batch".into(),
+                    (req,),
+                )
+            }
+            VotingKind::CreateDecisionTopic { name, description } => {
+                let result = base.calc_binary_results()[0];
+
+                if !result {
+                    return None;
+                }
+
+                let req = CreateDecisionTopicRequest {
+                    name: name.clone(),
+                    description: description.clone(),
+                };
+
+                CallToExecute::new(
+                    ENV_VARS.liquid_democracy_canister_id,
+                    "liquid_democracy__create_decision_topic".into(),
+                    (req,),
+                )
+            }
+            VotingKind::EditDecisionTopic {
+                id,
+                new_name_opt,
+                new_description_opt,
+            } => {
+                let result = base.calc_binary_results()[0];
+
+                if !result {
+                    return None;
+                }
+
+                let req = EditDecisionTopicRequest {
+                    id: *id,
+                    new_name_opt: new_name_opt.clone(),
+                    new_description_opt: new_description_opt.clone(),
+                };
+
+                CallToExecute::new(
+                    ENV_VARS.liquid_democracy_canister_id,
+                    "liquid_democracy__edit_decision_topic".into(),
+                    (req,),
+                )
+            }
+            VotingKind::DeprecateDecisionTopic { id, successors } => {
+                let result = base.calc_binary_results()[0];
+
+                if !result {
+                    return None;
+                }
+
+                let req = DeprecateDecisionTopicRequest {
+                    id: *id,
+                    successors: successors.clone(),
+                };
+
+                CallToExecute::new(
+                    ENV_VARS.liquid_democracy_canister_id,
+                    "liquid_democracy__deprecate_decision_topic".into(),
+                    (req,),
+                )
+            }
+            VotingKind::SetParticipationParams { params } => {
+                let result = base.calc_binary_results()[0];
+
+                if !result {
+                    return None;
+                }
+
+                let req = SetParticipationParamsRequest {
+                    params: params.clone(),
+                };
+
+                CallToExecute::new(
+                    ENV_VARS.votings_canister_id,
+                    "votings__set_participation_params".into(),
+                    (req,),
+                )
+            }
+            // the votings canister installs the staged wasm itself, since it's the controller
+            VotingKind::UpgradeCanister {
+                canister_id: _,
+                wasm_hash: _,
+                arg: _,
+            } => {
+                let result = base.calc_binary_results()[0];
+
+                if !result {
+                    return None;
+                }
+
+                let req = ExecuteUpgradeRequest { id };
+
+                CallToExecute::new(
+                    ENV_VARS.votings_canister_id,
+                    "votings__execute_upgrade".into(),
+                    (req,),
+                )
+            }
+            VotingKind::TreasuryTransfer {
+                asset,
+                from_subaccount,
+                to,
+                amount,
+                memo,
+            } => {
+                let result = base.calc_binary_results()[0];
+
+                if !result {
+                    return None;
+                }
+
+                let req = TreasuryTransferRequest {
+                    voting_id: id,
+                    batch_step,
+                    asset: *asset,
+                    from_subaccount: *from_subaccount,
+                    to: *to,
+                    amount: amount.clone(),
+                    memo: memo.clone(),
+                };
+
+                CallToExecute::new(
+                    ENV_VARS.bank_canister_id,
+                    "bank__treasury_transfer".into(),
+                    (req,),
+                )
+            }
+            VotingKind::SetTreasuryCaps { caps } => {
+                let result = base.calc_binary_results()[0];
+
+                if !result {
+                    return None;
+                }
+
+                let req = SetTreasuryCapsRequest { caps: caps.clone() };
+
+                CallToExecute::new(
+                    ENV_VARS.bank_canister_id,
+                    "bank__set_treasury_caps".into(),
+                    (req,),
+                )
+            }
+            VotingKind::SlashReputation {
+                target,
+                amount_or_fraction,
+                reason,
+            } => {
+                let result = base.calc_binary_results()[0];
+
+                if !result {
+                    return None;
+                }
+
+                let req = SlashRepRequest {
+                    target: *target,
+                    amount_or_fraction: amount_or_fraction.clone(),
+                    reason: reason.clone(),
+                    voting_id: id,
+                    batch_step,
+                };
+
+                CallToExecute::new(
+                    ENV_VARS.reputation_canister_id,
+                    "reputation__slash".into(),
+                    (req,),
+                )
+            }
+            VotingKind::SetDecayParams { params } => {
+                let result = base.calc_binary_results()[0];
+
+                if !result {
+                    return None;
+                }
+
+                let req = SetDecayParamsRequest {
+                    params: params.clone(),
+                };
+
+                CallToExecute::new(
+                    ENV_VARS.reputation_canister_id,
+                    "reputation__set_decay_params".into(),
+                    (req,),
+                )
+            }
+        };
+
+        let call = result.expect("UNREACHEABLE! Unable to encode args for the call");
+
+        Some(call)
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct VotingBase {
+    pub creator: Principal,
+    pub created_at: TimestampNs,
+    pub duration_ns: DurationNs,
+    pub total_supply: E8s,
+    pub quorum: E8s,
+    pub consensus_normalized: E8s,
+    pub finish_early: E8s,
+    pub votes_per_option: Vec<OptionVotes>,
+    // only present in multi-choice votings
+    pub choice_votes: Option<ChoiceVotes>,
+    // None means linear, total supply and all the votes are already weighted
+    pub weighting: Option<VoteWeighting>,
+    // None means the votes are public
+    pub secret_ballots: Option<SecretBallots>,
+    // storypoints locked by the creator, returned if the voting reaches quorum and is not marked as spam
+    pub deposit: Option<E8s>,
+    // only present in votings with a deposit, approving means the voting is spam
+    pub spam_votes: Option<OptionVotes>,
+    // votes are only accepted with reputation proofs made against this snapshot, None means any proof
+    pub reputation_snapshot_id: Option<ReputationSnapshotId>,
+}
+
+impl VotingBase {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        duration_ns: DurationNs,
+        total_supply: E8s,
+        quorum: E8s,
+        consensus_normalized: E8s,
+        finish_early: E8s,
+        num_options: u32,
+        caller: Principal,
+        now: TimestampNs,
+    ) -> Self {
+        Self {
+            creator: caller,
+            created_at: now,
+            duration_ns,
+            total_supply,
+            quorum,
+            consensus_normalized,
+            finish_early,
+            votes_per_option: vec![OptionVotes::default(); num_options as usize],
+            choice_votes: None,
+            weighting: None,
+            secret_ballots: None,
+            deposit: None,
+            spam_votes: None,
+            reputation_snapshot_id: None,
+        }
+    }
+
+    // the delegation tree itself is still the current one, only the balances are taken from the snapshot
+    pub fn check_reputation_proof(&self, proof: &ReputationProofBody) -> Result<(), String> {
+        if self.reputation_snapshot_id.is_some() && proof.snapshot_id != self.reputation_snapshot_id
+        {
+            return Err(
+                "The reputation proof is not made against the voting's snapshot".to_string(),
+            );
+        }
+
+        Ok(())
+    }
+
+    // multi-choice votings store everything under the first option
+    pub fn list_rationales(&self) -> Vec<(u32, Principal, String)> {
+        let per_option = match &self.choice_votes {
+            Some(choice_votes) => vec![&choice_votes.rationales],
+            None => self
+                .votes_per_option
+                .iter()
+                .map(|it| &it.rationales)
+                .collect(),
+        };
+
+        per_option
+            .into_iter()
+            .enumerate()
+            .flat_map(|(idx, rationales)| {
+                rationales
+                    .iter()
+                    .flatten()
+                    .map(move |(caster, it)| (idx as u32, *caster, it.clone()))
+            })
             .collect()
     }
 
-    pub fn get_voters(&self) -> Vec<Vec<Principal>> {
+    // only those who have voted themselves, not through a delegate
+    pub fn list_direct_voters(&self) -> BTreeSet<Principal> {
+        let mut result = BTreeSet::new();
+
+        if let Some(choice_votes) = &self.choice_votes {
+            result.extend(
+                choice_votes
+                    .votes
+                    .iter()
+                    .filter(|(_, it)| it.depth == 0)
+                    .map(|(voter, _)| *voter),
+            );
+        }
+
+        for option_votes in &self.votes_per_option {
+            result.extend(
+                option_votes
+                    .votes
+                    .iter()
+                    .filter(|(_, it)| it.depth == 0)
+                    .map(|(voter, _)| *voter),
+            );
+        }
+
+        result
+    }
+
+    // multi-choice votings only have one entry
+    pub fn explain_delegation(&self, of: Principal) -> Vec<DelegationBreakdown> {
+        if let Some(choice_votes) = &self.choice_votes {
+            let votes = choice_votes
+                .votes
+                .iter()
+                .map(|(owner, it)| (*owner, it.cast_by, it.depth));
+
+            return vec![DelegationBreakdown::new(
+                0,
+                of,
+                votes,
+                |it| choice_votes.votes.get(it).and_then(|vote| vote.cast_by),
+                &choice_votes.overrides,
+            )];
+        }
+
         self.votes_per_option
             .iter()
-            .map(|option_votes| option_votes.votes.keys().copied().collect::<Vec<_>>())
+            .enumerate()
+            .map(|(idx, option_votes)| {
+                let votes = option_votes
+                    .votes
+                    .iter()
+                    .map(|(owner, it)| (*owner, it.cast_by, it.depth));
+
+                DelegationBreakdown::new(
+                    idx as u32,
+                    of,
+                    votes,
+                    |it| option_votes.votes.get(it).and_then(|vote| vote.cast_by),
+                    &option_votes.overrides,
+                )
+            })
             .collect()
     }
-}
+
+    // the spam option should reach the quorum on its own
+    pub fn is_spam(&self) -> bool {
+        self.spam_votes
+            .as_ref()
+            .is_some_and(|it| it.total_reached_threshold(&self.quorum) && it.approve > it.reject)
+    }
+
+    pub fn is_quorum_reached_for_all_options(&self) -> bool {
+        if let Some(choice_votes) = &self.choice_votes {
+            return choice_votes.total_reached_threshold(&self.quorum);
+        }
+
+        for option_votes in &self.votes_per_option {
+            if !option_votes.total_reached_threshold(&self.quorum) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn is_finish_early_reached_for_all_options(&self) -> bool {
+        if let Some(choice_votes) = &self.choice_votes {
+            return choice_votes.total_reached_threshold(&self.finish_early);
+        }
+
+        for option_votes in &self.votes_per_option {
+            if !option_votes.total_reached_threshold(&self.finish_early) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    pub fn calc_results_raw_normilized(&self) -> Vec<(E8s, E8s, E8s)> {
+        self.votes_per_option
+            .iter()
+            .map(OptionVotes::get_normalized_results)
+            .collect()
+    }
+
+    pub fn calc_ranged_results_normalized(&self) -> Vec<Option<E8s>> {
+        let raw_results = self.calc_results_raw_normilized();
+        let one = E8s::one();
+
+        raw_results
+            .into_iter()
+            .map(|(_, approve, reject)| {
+                if reject > (&one - &self.consensus_normalized) {
+                    None
+                } else {
+                    Some(approve)
+                }
+            })
+            .collect()
+    }
+
+    pub fn calc_binary_results(&self) -> Vec<bool> {
+        let raw_results = self.calc_results_raw_normilized();
+
+        raw_results
+            .into_iter()
+            .map(|(_, approve, _)| approve >= self.consensus_normalized)
+            .collect()
+    }
+
+    // returns the winning option and the tallies of each round (there is only one round in single-choice votings)
+    pub fn calc_choice_results(&self) -> (Option<u32>, Vec<Vec<E8s>>) {
+        let choice_votes = self
+            .choice_votes
+            .as_ref()
+            .expect("UNREACHEABLE! Not a multi-choice voting");
+
+        match choice_votes.mode {
+            ChoiceMode::SingleChoice => {
+                let tally = choice_votes.calc_first_preferences();
+                let winner = ChoiceVotes::find_leader(&tally);
+
+                (winner, vec![tally])
+            }
+            ChoiceMode::RankedChoice => choice_votes.calc_instant_runoff(),
+        }
+    }
+
+    pub fn has_unrevealed_commitments(&self, voter: &Principal) -> bool {
+        match &self.secret_ballots {
+            Some(it) => it.per_option.iter().any(|c| c.hashes.contains_key(voter)),
+            None => false,
+        }
+    }
+
+    // unlike has_voted, a vote for a single option is enough
+    pub fn has_participated(&self, voter: &Principal) -> bool {
+        if let Some(secret_ballots) = &self.secret_ballots {
+            if secret_ballots
+                .per_option
+                .iter()
+                .any(|c| c.participants.contains_key(voter))
+            {
+                return true;
+            }
+        }
+
+        if let Some(choice_votes) = &self.choice_votes {
+            return choice_votes.votes.contains_key(voter);
+        }
+
+        self.votes_per_option
+            .iter()
+            .any(|v| v.votes.contains_key(voter))
+    }
+
+    pub fn has_voted(&self, voter: &Principal) -> bool {
+        if let Some(secret_ballots) = &self.secret_ballots {
+            return secret_ballots
+                .per_option
+                .iter()
+                .all(|c| c.participants.contains_key(voter));
+        }
+
+        if let Some(choice_votes) = &self.choice_votes {
+            return choice_votes.votes.contains_key(voter);
+        }
+
+        self.votes_per_option
+            .iter()
+            .all(|v| v.votes.contains_key(voter))
+    }
+
+    pub fn get_voters(&self) -> Vec<Vec<Principal>> {
+        self.votes_per_option
+            .iter()
+            .map(|option_votes| option_votes.votes.keys().copied().collect::<Vec<_>>())
+            .collect()
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Vote {
+    // defines override rules for votes - the vote can only be overriden by a caster with depth <= the previous depth
+    // the lower the depth, the closer was the delegate to the voter in the tree
+    // depth 0 means that the vote was placed by the reputation owner themself
+    pub depth: u32,
+    // None means "Reject"
+    pub normalized_approval_level: Option<E8s>,
+    // weighted according to the voting's weighting
+    pub total_voter_reputation: E8s,
+    // the delegate (or the voter themself) whose rationale this vote was cast under
+    pub cast_by: Option<Principal>,
+}
+
+impl Vote {
+    pub fn approval_level(&self) -> Option<E8s> {
+        self.normalized_approval_level
+            .as_ref()
+            .map(|a| &self.total_voter_reputation * a)
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VotingKindTag {
+    StartSolveTask,
+    EvaluateTask,
+    DeleteTask,
+    BankSetExchangeRate,
+    HumansEmploy,
+    HumansUnemploy,
+    GenericCall,
+    UpdateGenericCallAllowList,
+    SetVotingParams,
+    Motion,
+    MultiChoice,
+    SetConvictionParams,
+    Veto,
+    SetGuardianCouncil,
+    Batch,
+    CreateDecisionTopic,
+    EditDecisionTopic,
+    DeprecateDecisionTopic,
+    SetParticipationParams,
+    UpgradeCanister,
+    TreasuryTransfer,
+    SetTreasuryCaps,
+    SlashReputation,
+    SetDecayParams,
+}
+
+impl VotingKindTag {
+    // the tasks canister only lets one voting per task to be started anyway
+    pub fn allows_concurrent_votings(&self) -> bool {
+        !matches!(
+            self,
+            VotingKindTag::StartSolveTask | VotingKindTag::EvaluateTask | VotingKindTag::DeleteTask
+        )
+    }
+
+    pub const ALL: [VotingKindTag; 24] = [
+        VotingKindTag::StartSolveTask,
+        VotingKindTag::EvaluateTask,
+        VotingKindTag::DeleteTask,
+        VotingKindTag::BankSetExchangeRate,
+        VotingKindTag::HumansEmploy,
+        VotingKindTag::HumansUnemploy,
+        VotingKindTag::GenericCall,
+        VotingKindTag::UpdateGenericCallAllowList,
+        VotingKindTag::SetVotingParams,
+        VotingKindTag::Motion,
+        VotingKindTag::MultiChoice,
+        VotingKindTag::SetConvictionParams,
+        VotingKindTag::Veto,
+        VotingKindTag::SetGuardianCouncil,
+        VotingKindTag::Batch,
+        VotingKindTag::CreateDecisionTopic,
+        VotingKindTag::EditDecisionTopic,
+        VotingKindTag::DeprecateDecisionTopic,
+        VotingKindTag::SetParticipationParams,
+        VotingKindTag::UpgradeCanister,
+        VotingKindTag::TreasuryTransfer,
+        VotingKindTag::SetTreasuryCaps,
+        VotingKindTag::SlashReputation,
+        VotingKindTag::SetDecayParams,
+    ];
+}
+
+// stored params are keyed by the tag, new kinds should only be appended
+impl_storable_for_u8_enum!(VotingKindTag {
+        StartSolveTask = 0,
+        EvaluateTask = 1,
+        DeleteTask = 2,
+        BankSetExchangeRate = 3,
+        HumansEmploy = 4,
+        HumansUnemploy = 5,
+        GenericCall = 6,
+        UpdateGenericCallAllowList = 7,
+        SetVotingParams = 8,
+        Motion = 9,
+        MultiChoice = 10,
+        SetConvictionParams = 11,
+        Veto = 12,
+        SetGuardianCouncil = 13,
+        Batch = 14,
+        CreateDecisionTopic = 15,
+        EditDecisionTopic = 16,
+        DeprecateDecisionTopic = 17,
+        SetParticipationParams = 18,
+        UpgradeCanister = 19,
+        TreasuryTransfer = 20,
+        SetTreasuryCaps = 21,
+        SlashReputation = 22,
+        SetDecayParams = 23,
+});
+
+// quorum and finish early are expressed as a share of the reputation total supply
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct VotingParams {
+    pub duration_ns: DurationNs,
+    pub quorum_normalized: E8s,
+    pub consensus_normalized: E8s,
+    pub finish_early_normalized: E8s,
+    // None means linear
+    pub weighting: Option<VoteWeighting>,
+    // None means the votes are public, otherwise they are committed first and revealed after the voting is over
+    pub reveal_duration_ns: Option<DurationNs>,
+    // for how long failed calls are retried, None means a week
+    pub execution_expiry_ns: Option<DurationNs>,
+    // for how long passed decisions wait before being executed, None means they are executed right away
+    pub timelock_ns: Option<DurationNs>,
+    // storypoints locked by non-team members to start a voting, None means the default deposit
+    pub deposit_storypoints: Option<E8s>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VoteWeighting {
+    #[default]
+    Linear,
+    // each reputation owner's vote weighs as much as the square root of their reputation
+    Quadratic,
+}
+
+impl VoteWeighting {
+    pub fn apply(&self, reputation: &E8s) -> E8s {
+        match self {
+            VoteWeighting::Linear => reputation.clone(),
+            VoteWeighting::Quadratic => reputation.sqrt_precise(),
+        }
+    }
+}
+
+impl VotingParams {
+    pub fn default_for(kind: VotingKindTag) -> Self {
+        let (duration_ns, quorum_normalized, consensus_normalized, finish_early_normalized) =
+            match kind {
+                VotingKindTag::StartSolveTask
+                | VotingKindTag::EvaluateTask
+                | VotingKindTag::DeleteTask
+                | VotingKindTag::Motion
+                | VotingKindTag::MultiChoice => {
+                    (ONE_WEEK_NS, E8s::f0_2(), E8s::f0_5(), E8s::f0_67())
+                }
+                VotingKindTag::BankSetExchangeRate | VotingKindTag::HumansEmploy => {
+                    (ONE_WEEK_NS, E8s::f0_2(), E8s::f0_67(), E8s::f0_67())
+                }
+                VotingKindTag::HumansUnemploy | VotingKindTag::SlashReputation => {
+                    (ONE_WEEK_NS * 2, E8s::f0_2(), E8s::f0_67(), E8s::f0_67())
+                }
+                // arbitrary calls and the voting rules themselves require more people to agree
+                VotingKindTag::GenericCall
+                | VotingKindTag::UpdateGenericCallAllowList
+                | VotingKindTag::SetVotingParams
+                | VotingKindTag::SetConvictionParams
+                | VotingKindTag::SetGuardianCouncil
+                | VotingKindTag::Batch
+                | VotingKindTag::CreateDecisionTopic
+                | VotingKindTag::EditDecisionTopic
+                | VotingKindTag::DeprecateDecisionTopic
+                | VotingKindTag::SetParticipationParams
+                | VotingKindTag::UpgradeCanister
+                | VotingKindTag::TreasuryTransfer
+                | VotingKindTag::SetTreasuryCaps
+                | VotingKindTag::SetDecayParams => {
+                    (ONE_WEEK_NS * 2, E8s::f0_33(), E8s::f0_75(), E8s::f0_75())
+                }
+                // vetoes should be decided before the timelock is over
+                VotingKindTag::Veto => (ONE_DAY_NS * 2, E8s::f0_1(), E8s::f0_5(), E8s::f0_5()),
+            };
+
+        Self {
+            duration_ns,
+            quorum_normalized,
+            consensus_normalized,
+            finish_early_normalized,
+            weighting: None,
+            reveal_duration_ns: None,
+            execution_expiry_ns: None,
+            timelock_ns: None,
+            deposit_storypoints: None,
+        }
+    }
+
+    pub fn deposit(&self) -> E8s {
+        self.deposit_storypoints
+            .clone()
+            .unwrap_or(E8s(Nat::from(10_0000_0000u64)))
+    }
+
+    // these bounds make sure governance can't make votings impossible to pass (or too easy to)
+    pub fn check_bounds(&self) -> Result<(), String> {
+        if self.duration_ns < ONE_DAY_NS || self.duration_ns > ONE_WEEK_NS * 4 {
+            return Err("Duration should be between one day and four weeks".to_string());
+        }
+
+        if self.quorum_normalized < E8s::f0_1() || self.quorum_normalized > E8s::f0_5() {
+            return Err("Quorum should be between 10% and 50%".to_string());
+        }
+
+        if self.consensus_normalized < E8s::f0_5() || self.consensus_normalized > E8s::f0_9() {
+            return Err("Consensus should be between 50% and 90%".to_string());
+        }
+
+        if self.finish_early_normalized < E8s::f0_5()
+            || self.finish_early_normalized > E8s::one()
+            || self.finish_early_normalized < self.quorum_normalized
+        {
+            return Err(
+                "Finish early should be between 50% and 100% and not less than quorum".to_string(),
+            );
+        }
+
+        if let Some(reveal_duration_ns) = self.reveal_duration_ns {
+            if !(ONE_HOUR_NS..=ONE_WEEK_NS).contains(&reveal_duration_ns) {
+                return Err("Reveal duration should be between one hour and one week".to_string());
+            }
+        }
+
+        if let Some(execution_expiry_ns) = self.execution_expiry_ns {
+            if !(ONE_HOUR_NS..=ONE_WEEK_NS * 4).contains(&execution_expiry_ns) {
+                return Err(
+                    "Execution expiry should be between one hour and four weeks".to_string()
+                );
+            }
+        }
+
+        if let Some(timelock_ns) = self.timelock_ns {
+            if !(ONE_HOUR_NS..=ONE_WEEK_NS * 2).contains(&timelock_ns) {
+                return Err("Timelock should be between one hour and two weeks".to_string());
+            }
+        }
+
+        if let Some(deposit_storypoints) = &self.deposit_storypoints {
+            if deposit_storypoints > &E8s(Nat::from(1000_0000_0000u64)) {
+                return Err("Deposit should not exceed 1000 storypoints".to_string());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct CallToExecute {
+    pub canister_id: Principal,
+    pub method_name: String,
+    pub args_raw: Vec<u8>,
+}
+
+impl CallToExecute {
+    pub fn new<A: ArgumentEncoder>(
+        canister_id: Principal,
+        method_name: String,
+        args: A,
+    ) -> Result<Self, String> {
+        let args_raw = encode_args(args).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            canister_id,
+            method_name,
+            args_raw,
+        })
+    }
+
+    pub async fn execute(self) -> Result<(), String> {
+        call_raw(self.canister_id, &self.method_name, &self.args_raw, 0)
+            .await
+            .map_err(|(c, m)| format!("Error [{:?}]: {}", c, m))
+            .map(|_| ())
+    }
+
+    pub fn get_args_preview(&self) -> String {
+        IDLArgs::from_bytes(&self.args_raw)
+            .map(|args| args.to_string())
+            .unwrap_or_else(|e| format!("Unable to decode the arguments: {}", e))
+    }
+}
+
+// what would happen, if the voting was over right now
+#[derive(CandidType, Deserialize, Clone)]
+pub struct VotingProjection {
+    pub voting_id: VotingId,
+    pub options: Vec<OptionProjection>,
+    pub quorum_reached: bool,
+    pub finish_early_reached: bool,
+    // None until every option has some votes
+    pub binary_results: Option<Vec<bool>>,
+    pub ranged_results_normalized: Option<Vec<Option<E8s>>>,
+    pub call: Option<CallToExecute>,
+    pub call_args_preview: Option<String>,
+    // the event the voting would be finished with instead, if there is nothing to execute
+    pub event: Option<VotingEvent>,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct OptionProjection {
+    pub total_voted: E8s,
+    // goes above one, once the quorum is reached
+    pub quorum_progress_normalized: E8s,
+    pub approve_normalized: E8s,
+    pub reject_normalized: E8s,
+}
+
+impl Voting {
+    pub fn project(&self) -> VotingProjection {
+        // resolving a copy, so the projection is exactly what the timer would do
+        let mut voting = self.clone();
+
+        if voting.is_revealing() {
+            voting.close_reveal();
+        }
+
+        let base = &voting.base;
+        let totals: Vec<&E8s> = match &base.choice_votes {
+            Some(choice_votes) => vec![&choice_votes.total_voted],
+            None => base
+                .votes_per_option
+                .iter()
+                .map(|it| &it.total_voted)
+                .collect(),
+        };
+
+        let options = totals
+            .iter()
+            .enumerate()
+            .map(|(idx, total_voted)| {
+                let (approve_normalized, reject_normalized) = match base.votes_per_option.get(idx) {
+                    Some(it) if base.choice_votes.is_none() && it.total_voted > E8s::zero() => {
+                        let (_, approve, reject) = it.get_normalized_results();
+                        (approve, reject)
+                    }
+                    _ => (E8s::zero(), E8s::zero()),
+                };
+
+                let quorum_progress_normalized = if base.quorum == E8s::zero() {
+                    E8s::one()
+                } else {
+                    *total_voted / &base.quorum
+                };
+
+                OptionProjection {
+                    total_voted: (*total_voted).clone(),
+                    quorum_progress_normalized,
+                    approve_normalized,
+                    reject_normalized,
+                }
+            })
+            .collect();
+
+        let all_options_voted = base.choice_votes.is_none()
+            && base
+                .votes_per_option
+                .iter()
+                .all(|it| it.total_voted > E8s::zero());
+
+        let (binary_results, ranged_results_normalized) = if all_options_voted {
+            (
+                Some(base.calc_binary_results()),
+                Some(base.calc_ranged_results_normalized()),
+            )
+        } else {
+            (None, None)
+        };
+
+        let quorum_reached = base.is_quorum_reached_for_all_options();
+        let finish_early_reached = base.is_finish_early_reached_for_all_options();
+
+        let (call, event) = match voting.resolve_on_timer() {
+            Ok(call) => (Some(call), None),
+            Err(event) => (None, Some(*event)),
+        };
+
+        VotingProjection {
+            voting_id: self.id,
+            options,
+            quorum_reached,
+            finish_early_reached,
+            binary_results,
+            ranged_results_normalized,
+            call_args_preview: call.as_ref().map(CallToExecute::get_args_preview),
+            call,
+            event,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct VotingExt {
+    pub id: VotingId,
+    pub creator: Principal,
+    pub created_at: TimestampNs,
+    pub duration_ns: DurationNs,
+    pub total_supply: E8s,
+    pub quorum: E8s,
+    pub consensus_normalized: E8s,
+    pub finish_early: E8s,
+    pub votes_per_option: Vec<(E8s, Option<Vote>)>,
+    pub kind: VotingKind,
+    pub stage: VotingStage,
+    // candid-decoded arguments of a generic call
+    pub args_preview: Option<String>,
+    // first preferences per option and the caller's vote
+    pub choice_votes: Option<(Vec<E8s>, Option<ChoiceVote>)>,
+    pub weighting: VoteWeighting,
+    // committed participation per option and whether the caller has something to reveal
+    pub secret_ballots: Option<Vec<(E8s, bool)>>,
+    pub deposit: Option<E8s>,
+    // spam and not spam votes and the caller's vote
+    pub spam_votes: Option<(E8s, E8s, Option<Vote>)>,
+    // option index, caster and their rationale - votes reference casters via cast_by
+    pub rationales: Vec<(u32, Principal, String)>,
+    pub batch_outcomes: Option<Vec<BatchStepOutcome>>,
+}
+
+#[derive(CandidType, Deserialize, Validate, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GenericCallTarget {
+    #[garde(skip)]
+    pub canister_id: Principal,
+    #[garde(length(min = 1, max = 128))]
+    pub method_name: String,
+}
+
+impl Storable for GenericCallTarget {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut buf = Vec::new();
+
+        encode_principal(&mut buf, &self.canister_id);
+        buf.extend_from_slice(self.method_name.as_bytes());
+
+        Cow::Owned(buf)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let canister_id = decode_principal(&bytes);
+        let method_name = String::from_utf8(bytes[1 + canister_id.as_slice().len()..].to_vec())
+            .expect("Unable to decode method name");
+
+        Self {
+            canister_id,
+            method_name,
+        }
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum VotingTimer {
+    ExecOnQuorum {
+        voting_id: VotingId,
+        timestamp: TimestampNs,
+    },
+    RetryExecution {
+        voting_id: VotingId,
+        timestamp: TimestampNs,
+    },
+    ExecuteQueued {
+        voting_id: VotingId,
+        timestamp: TimestampNs,
+    },
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum VotingEvent {
+    V0001(VotingEventV1),
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum VotingEventV1 {
+    VotingCreated {
+        voting_id: VotingId,
+        creator: Principal,
+        quorum: E8s,
+        consensus_normalized: E8s,
+        finish_early: E8s,
+        num_options: u32,
+    },
+    VotingExecuting {
+        voting_id: VotingId,
+        call: CallToExecute,
+        rep_per_option: Vec<E8s>,
+        on_timer: bool,
+    },
+    VotingSuccess {
+        voting_id: VotingId,
+    },
+    VotingFail {
+        voting_id: VotingId,
+        reason: String,
+    },
+    VotingExecutionFailed {
+        voting_id: VotingId,
+        reason: String,
+        attempts: u32,
+        next_retry_at: TimestampNs,
+    },
+    VotingQueued {
+        voting_id: VotingId,
+        execute_at: TimestampNs,
+    },
+    VotingCancelled {
+        voting_id: VotingId,
+        reason: String,
+    },
+    // a conflicting voting has passed first
+    VotingSuperseded {
+        voting_id: VotingId,
+        by: VotingId,
+    },
+    GuardianVetoCast {
+        voting_id: VotingId,
+        guardian: Principal,
+        reason: String,
+        approvals: u32,
+        threshold: u32,
+    },
+    VotingVetoedByGuardians {
+        voting_id: VotingId,
+        guardians: Vec<Principal>,
+    },
+    BatchStepExecuted {
+        voting_id: VotingId,
+        step: u32,
+        // None means the step has succeeded
+        error: Option<String>,
+    },
+    VotingDepositRefunded {
+        voting_id: VotingId,
+        depositor: Principal,
+        storypoints: E8s,
+    },
+    VotingDepositBurned {
+        voting_id: VotingId,
+        depositor: Principal,
+        storypoints: E8s,
+        reason: String,
+    },
+    MultiChoiceVotingResolved {
+        voting_id: VotingId,
+        winner: u32,
+        rounds: Vec<Vec<E8s>>,
+    },
+    ConvictionProposalCreated {
+        proposal_id: ConvictionProposalId,
+        creator: Principal,
+        beneficiary: Principal,
+        requested_storypoints: E8s,
+    },
+    ConvictionProposalSuccess {
+        proposal_id: ConvictionProposalId,
+        conviction: E8s,
+    },
+    ConvictionProposalFail {
+        proposal_id: ConvictionProposalId,
+        reason: String,
+    },
+    ParticipationEpochFinished {
+        epoch_id: u64,
+        eligible: u32,
+        participants: u32,
+        rewarded_storypoints: E8s,
+    },
+    CanisterUpgraded {
+        voting_id: VotingId,
+        canister_id: Principal,
+        wasm_hash: WasmHash,
+    },
+}
+
+pub type MotionId = u64;
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ArchivedMotionV0001 {
+    pub id: MotionId,
+    pub title: String,
+    pub body: String,
+    pub topics: Vec<DecisionTopicId>,
+    pub creator: Principal,
+    pub created_at: TimestampNs,
+    pub finished_at: TimestampNs,
+    pub accepted: bool,
+
+    pub total_supply: E8s,
+    pub quorum: E8s,
+    pub consensus_normalized: E8s,
+    pub total_voted: E8s,
+    pub approve: E8s,
+    pub reject: E8s,
+    pub rationales: Option<Vec<(Principal, String)>>,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum ArchivedMotion {
+    V0001(ArchivedMotionV0001),
+}
+
+#[derive(CandidType, Deserialize, Clone, Default, Debug)]
+pub struct OptionVotes {
+    pub votes: BTreeMap<Principal, Vote>,
+    pub total_voted: E8s,
+    pub approve: E8s,
+    pub reject: E8s,
+    // keyed by the caster, shared by everyone the vote was cast for
+    pub rationales: Option<BTreeMap<Principal, String>>,
+    // delegate -> followers, whose reputation follows a closer ballot instead of the delegate's one
+    pub overrides: Option<BTreeMap<Principal, BTreeSet<Principal>>>,
+}
+
+impl OptionVotes {
+    // a new vote without a rationale removes the previous one
+    pub fn set_rationale(&mut self, caster: Principal, rationale: Option<String>) {
+        set_rationale(&mut self.rationales, caster, rationale);
+    }
+
+    // same as revert_prev_vote, but also remembers whose ballot was overridden
+    pub fn revert_prev_vote_by(
+        &mut self,
+        owner: &Principal,
+        depth: u32,
+        caster: Principal,
+    ) -> bool {
+        let prev_caster = self.votes.get(owner).and_then(|it| it.cast_by);
+        let can_cast = self.revert_prev_vote(owner, depth);
+
+        track_override(&mut self.overrides, *owner, prev_caster, caster, can_cast);
+
+        can_cast
+    }
+
+    pub fn revert_prev_vote(&mut self, owner: &Principal, depth: u32) -> bool {
+        if let Some(prev_vote) = self.votes.get(owner) {
+            if prev_vote.depth < depth {
+                return false;
+            }
+
+            self.total_voted -= &prev_vote.total_voter_reputation;
+
+            if let Some(approval) = &prev_vote.approval_level() {
+                self.approve -= approval;
+            } else {
+                self.reject -= &prev_vote.total_voter_reputation;
+            }
+        }
+
+        true
+    }
+
+    pub fn cast_vote(&mut self, vote_owner: Principal, vote: Vote) {
+        self.total_voted += &vote.total_voter_reputation;
+
+        if let Some(approval) = &vote.approval_level() {
+            self.approve += approval;
+        } else {
+            self.reject += &vote.total_voter_reputation;
+        }
+
+        self.votes.insert(vote_owner, vote);
+    }
+
+    pub fn get_normalized_results(&self) -> (E8s, E8s, E8s) {
+        (
+            self.total_voted.clone(),
+            &self.approve / &self.total_voted,
+            &self.reject / &self.total_voted,
+        )
+    }
+
+    pub fn total_reached_threshold(&self, threshold: &E8s) -> bool {
+        &self.total_voted >= threshold
+    }
+}
+
+// how someone's reputation took part in an option of a voting
+#[derive(CandidType, Deserialize, Clone)]
+pub struct DelegationBreakdown {
+    pub option_idx: u32,
+    // whose ballot the reputation follows and at what depth, None if nobody voted for them
+    pub followed: Option<(Principal, u32)>,
+    // followers the ballot is cast for, with their depth
+    pub voting_for: Vec<(Principal, u32)>,
+    // followers who have a closer ballot, with whose ballot that is
+    pub overridden_by: Vec<(Principal, Option<Principal>)>,
+}
+
+impl DelegationBreakdown {
+    fn new(
+        option_idx: u32,
+        of: Principal,
+        votes: impl Iterator<Item = (Principal, Option<Principal>, u32)>,
+        cast_by_of: impl Fn(&Principal) -> Option<Principal>,
+        overrides: &Option<BTreeMap<Principal, BTreeSet<Principal>>>,
+    ) -> Self {
+        let mut followed = None;
+        let mut voting_for = Vec::new();
+
+        for (owner, cast_by, depth) in votes {
+            if owner == of {
+                followed = cast_by.map(|it| (it, depth));
+            } else if cast_by == Some(of) {
+                voting_for.push((owner, depth));
+            }
+        }
+
+        let overridden_by = overrides
+            .as_ref()
+            .and_then(|it| it.get(&of))
+            .into_iter()
+            .flatten()
+            .map(|follower| (*follower, cast_by_of(follower)))
+            .collect();
+
+        Self {
+            option_idx,
+            followed,
+            voting_for,
+            overridden_by,
+        }
+    }
+}
+
+fn track_override(
+    overrides: &mut Option<BTreeMap<Principal, BTreeSet<Principal>>>,
+    owner: Principal,
+    prev_caster: Option<Principal>,
+    caster: Principal,
+    can_cast: bool,
+) {
+    // a closer ballot is already there, so the caster's one doesn't reach the owner
+    if !can_cast {
+        overrides
+            .get_or_insert_with(BTreeMap::new)
+            .entry(caster)
+            .or_default()
+            .insert(owner);
+
+        return;
+    }
+
+    if let Some(map) = overrides.as_mut() {
+        if let Some(followers) = map.get_mut(&caster) {
+            followers.remove(&owner);
+
+            if followers.is_empty() {
+                map.remove(&caster);
+            }
+        }
+    }
+
+    // the caster is closer to the owner than the delegate who voted for them before
+    if let Some(prev_caster) = prev_caster.filter(|it| *it != caster) {
+        overrides
+            .get_or_insert_with(BTreeMap::new)
+            .entry(prev_caster)
+            .or_default()
+            .insert(owner);
+    }
+}
+
+fn set_rationale(
+    rationales: &mut Option<BTreeMap<Principal, String>>,
+    caster: Principal,
+    rationale: Option<String>,
+) {
+    match rationale {
+        Some(it) => {
+            rationales
+                .get_or_insert_with(BTreeMap::new)
+                .insert(caster, it);
+        }
+        None => {
+            if let Some(map) = rationales {
+                map.remove(&caster);
+            }
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChoiceMode {
+    // the option with the most votes wins
+    SingleChoice,
+    // instant-runoff - options with the least votes are eliminated until one gets the majority
+    RankedChoice,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub enum ChoiceBallot {
+    Single(u32),
+    // the most preferred option goes first
+    Ranked(Vec<u32>),
+}
+
+impl ChoiceBallot {
+    pub fn ranking(&self) -> &[u32] {
+        match self {
+            ChoiceBallot::Single(option_idx) => std::slice::from_ref(option_idx),
+            ChoiceBallot::Ranked(ranking) => ranking,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ChoiceVote {
+    // same override rules as in Vote
+    pub depth: u32,
+    pub ballot: ChoiceBallot,
+    pub total_voter_reputation: E8s,
+    pub cast_by: Option<Principal>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ChoiceVotes {
+    pub mode: ChoiceMode,
+    pub num_options: u32,
+    pub votes: BTreeMap<Principal, ChoiceVote>,
+    pub total_voted: E8s,
+    pub rationales: Option<BTreeMap<Principal, String>>,
+    // same as in OptionVotes
+    pub overrides: Option<BTreeMap<Principal, BTreeSet<Principal>>>,
+}
+
+impl ChoiceVotes {
+    pub fn new(mode: ChoiceMode, num_options: u32) -> Self {
+        Self {
+            mode,
+            num_options,
+            votes: BTreeMap::new(),
+            total_voted: E8s::zero(),
+            rationales: None,
+            overrides: None,
+        }
+    }
+
+    pub fn set_rationale(&mut self, caster: Principal, rationale: Option<String>) {
+        set_rationale(&mut self.rationales, caster, rationale);
+    }
+
+    pub fn validate_ballot(&self, ballot: &ChoiceBallot) -> Result<(), String> {
+        match (self.mode, ballot) {
+            (ChoiceMode::SingleChoice, ChoiceBallot::Single(_)) => {}
+            (ChoiceMode::RankedChoice, ChoiceBallot::Ranked(_)) => {}
+            _ => return Err("The ballot does not match the voting mode".to_string()),
+        }
+
+        let ranking = ballot.ranking();
+
+        if ranking.is_empty() {
+            return Err("The ballot is empty".to_string());
+        }
+
+        let mut seen = vec![false; self.num_options as usize];
+
+        for option_idx in ranking {
+            let was_seen = seen
+                .get_mut(*option_idx as usize)
+                .ok_or(format!("Option {} does not exist", option_idx))?;
+
+            if *was_seen {
+                return Err(format!("Option {} is ranked twice", option_idx));
+            }
+
+            *was_seen = true;
+        }
+
+        Ok(())
+    }
+
+    pub fn revert_prev_vote_by(
+        &mut self,
+        owner: &Principal,
+        depth: u32,
+        caster: Principal,
+    ) -> bool {
+        let prev_caster = self.votes.get(owner).and_then(|it| it.cast_by);
+        let can_cast = self.revert_prev_vote(owner, depth);
+
+        track_override(&mut self.overrides, *owner, prev_caster, caster, can_cast);
+
+        can_cast
+    }
+
+    pub fn revert_prev_vote(&mut self, owner: &Principal, depth: u32) -> bool {
+        if let Some(prev_vote) = self.votes.get(owner) {
+            if prev_vote.depth < depth {
+                return false;
+            }
+
+            self.total_voted -= &prev_vote.total_voter_reputation;
+        }
+
+        true
+    }
+
+    pub fn cast_vote(&mut self, vote_owner: Principal, vote: ChoiceVote) {
+        self.total_voted += &vote.total_voter_reputation;
+        self.votes.insert(vote_owner, vote);
+    }
+
+    pub fn total_reached_threshold(&self, threshold: &E8s) -> bool {
+        &self.total_voted >= threshold
+    }
+
+    pub fn calc_first_preferences(&self) -> Vec<E8s> {
+        self.calc_round(&vec![false; self.num_options as usize])
+    }
+
+    // each ballot counts towards its most preferred option, that is not eliminated yet
+    fn calc_round(&self, eliminated: &[bool]) -> Vec<E8s> {
+        let mut tally = vec![E8s::zero(); self.num_options as usize];
+
+        for vote in self.votes.values() {
+            let option_idx_opt = vote
+                .ballot
+                .ranking()
+                .iter()
+                .find(|it| !eliminated[**it as usize]);
+
+            if let Some(option_idx) = option_idx_opt {
+                tally[*option_idx as usize] += &vote.total_voter_reputation;
+            }
+        }
+
+        tally
+    }
+
+    pub fn calc_instant_runoff(&self) -> (Option<u32>, Vec<Vec<E8s>>) {
+        let mut eliminated = vec![false; self.num_options as usize];
+        let mut rounds = Vec::new();
+
+        loop {
+            let tally = self.calc_round(&eliminated);
+
+            // exhausted ballots don't count towards the majority
+            let active = tally.iter().fold(E8s::zero(), |acc, it| acc + it);
+
+            rounds.push(tally);
+            let tally = rounds.last().unwrap();
+
+            if active == E8s::zero() {
+                return (None, rounds);
+            }
+
+            let winner_opt = (0..self.num_options).find(|it| {
+                let votes = &tally[*it as usize];
+
+                votes + votes > active
+            });
+
+            if let Some(winner) = winner_opt {
+                return (Some(winner), rounds);
+            }
+
+            let remaining: Vec<u32> = (0..self.num_options)
+                .filter(|it| !eliminated[*it as usize])
+                .collect();
+
+            // there is no way to decide which option to eliminate, if all of them have the same amount of votes
+            if remaining
+                .iter()
+                .all(|it| tally[*it as usize] == tally[remaining[0] as usize])
+            {
+                return (None, rounds);
+            }
+
+            // eliminating the option with the least votes, the latest one goes first on ties
+            let loser = remaining
+                .into_iter()
+                .min_by(|a, b| tally[*a as usize].cmp(&tally[*b as usize]).then(b.cmp(a)))
+                .unwrap();
+
+            eliminated[loser as usize] = true;
+        }
+    }
+
+    // returns None if there are no votes or several options share the first place
+    pub fn find_leader(tally: &[E8s]) -> Option<u32> {
+        let max = tally.iter().max()?;
+
+        if max == &E8s::zero() || tally.iter().filter(|it| *it == max).count() > 1 {
+            return None;
+        }
+
+        tally.iter().position(|it| it == max).map(|it| it as u32)
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SecretBallots {
+    pub reveal_duration_ns: DurationNs,
+    // multi-choice votings only have one entry
+    pub per_option: Vec<OptionCommitments>,
+}
+
+impl SecretBallots {
+    pub fn new(reveal_duration_ns: DurationNs, num_slots: u32) -> Self {
+        Self {
+            reveal_duration_ns,
+            per_option: vec![OptionCommitments::default(); num_slots as usize],
+        }
+    }
+
+    // the voter is hashed too, so others can't copy someone's commitment and reveal it after them
+    pub fn calc_hash(
+        voter: Principal,
+        option_idx: u32,
+        normalized_approval_level: &Option<E8s>,
+        ballot: &Option<ChoiceBallot>,
+        salt: &[u8],
+    ) -> [u8; 32] {
+        let encoded = encode_args((
+            voter,
+            option_idx,
+            normalized_approval_level,
+            ballot,
+            salt.to_vec(),
+        ))
+        .expect("Unable to encode a vote");
+
+        sha2::Sha256::digest(encoded).into()
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct OptionCommitments {
+    // commitments that are not revealed yet
+    pub hashes: BTreeMap<Principal, [u8; 32]>,
+    pub participants: BTreeMap<Principal, Participant>,
+    pub total_committed: E8s,
+}
+
+impl OptionCommitments {
+    pub fn revert_prev_participation(&mut self, owner: &Principal, depth: u32) -> bool {
+        if let Some(prev) = self.participants.get(owner) {
+            if prev.depth < depth {
+                return false;
+            }
+
+            self.total_committed -= &prev.total_voter_reputation;
+        }
+
+        true
+    }
+
+    pub fn participate(&mut self, owner: Principal, participant: Participant) {
+        self.total_committed += &participant.total_voter_reputation;
+        self.participants.insert(owner, participant);
+    }
+}
+
+// same override rules as in Vote, the vote itself is taken from the reveal of whoever has committed
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct Participant {
+    pub depth: u32,
+    pub total_voter_reputation: E8s,
+    pub committed_by: Principal,
+}
+
+// elected by governance, guardians can only block decisions - never initiate them
+#[derive(CandidType, Deserialize, Validate, Clone, Debug, Default)]
+pub struct GuardianCouncil {
+    #[garde(length(max = 16))]
+    pub members: Vec<Principal>,
+    // how many members should veto a decision to block it
+    #[garde(skip)]
+    pub threshold: u32,
+}
+
+impl GuardianCouncil {
+    pub fn check_bounds(&self) -> Result<(), String> {
+        for (idx, member) in self.members.iter().enumerate() {
+            if self.members[..idx].contains(member) {
+                return Err(format!("Guardian {} is listed twice", member));
+            }
+        }
+
+        // an empty council disables guardian vetoes
+        if self.members.is_empty() {
+            if self.threshold != 0 {
+                return Err("The threshold of an empty council should be zero".to_string());
+            }
+        } else if self.threshold == 0 || self.threshold as usize > self.members.len() {
+            return Err(
+                "The threshold should be between one and the number of guardians".to_string(),
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn is_member(&self, principal: &Principal) -> bool {
+        self.members.contains(principal)
+    }
+}
+
+// vetoes of a single voting, guardians that have left the council are not counted
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct GuardianVetoes {
+    pub reasons: BTreeMap<Principal, String>,
+}
+
+impl GuardianVetoes {
+    pub fn approvals(&self, council: &GuardianCouncil) -> Vec<Principal> {
+        self.reasons
+            .keys()
+            .filter(|it| council.is_member(it))
+            .copied()
+            .collect()
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatchFailurePolicy {
+    // the remaining actions wait, until the failed one is successfully retried
+    Halt,
+    // failed actions are recorded and skipped
+    Continue,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct BatchStepOutcome {
+    pub step: u32,
+    pub executed_at: TimestampNs,
+    // None means the step has succeeded
+    pub error: Option<String>,
+}
+
+impl Voting {
+    // steps are executed in order, so the next one goes right after the last finished one
+    pub fn next_batch_step(&self) -> Option<(u32, &VotingKind)> {
+        let (actions, on_failure) = match &self.kind {
+            VotingKind::Batch {
+                description: _,
+                actions,
+                on_failure,
+            } => (actions, on_failure),
+            _ => return None,
+        };
+
+        let finished = self
+            .batch_outcomes
+            .iter()
+            .flatten()
+            .filter(|it| it.error.is_none() || *on_failure == BatchFailurePolicy::Continue)
+            .count();
+
+        actions
+            .get(finished)
+            .map(|action| (finished as u32, action))
+    }
+
+    // returns the error, if the batch should stop
+    pub fn record_batch_step(
+        &mut self,
+        step: u32,
+        error: Option<String>,
+        now: TimestampNs,
+    ) -> Result<(), String> {
+        let halt = matches!(
+            self.kind,
+            VotingKind::Batch {
+                on_failure: BatchFailurePolicy::Halt,
+                ..
+            }
+        );
+
+        self.batch_outcomes
+            .get_or_insert_with(Vec::new)
+            .push(BatchStepOutcome {
+                step,
+                executed_at: now,
+                error: error.clone(),
+            });
+
+        match error {
+            Some(e) if halt => Err(format!("Step {} has failed: {}", step, e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct TargetVotings {
+    pub ids: Vec<VotingId>,
+}
+
+// refunds are retried until the humans canister accepts them
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct DepositRefund {
+    pub voting_id: VotingId,
+    pub depositor: Principal,
+    pub storypoints: E8s,
+}
+
+pub type ArchivedVotingId = u64;
+
+// a finished voting with all its votes and the final event (the execution result)
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ArchivedVotingV0001 {
+    pub id: ArchivedVotingId,
+    pub voting_id: VotingId,
+    pub kind: VotingKind,
+    pub topics: Vec<DecisionTopicId>,
+    pub base: VotingBase,
+    pub finished_at: TimestampNs,
+    pub result: VotingEvent,
+    pub batch_outcomes: Option<Vec<BatchStepOutcome>>,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum ArchivedVoting {
+    V0001(ArchivedVotingV0001),
+}
+
+impl ArchivedVoting {
+    pub fn matches(&self, filter: &ArchivedVotingsFilter) -> bool {
+        let ArchivedVoting::V0001(v) = self;
+
+        if let Some(kind) = filter.kind {
+            if v.kind.get_tag() != kind {
+                return false;
+            }
+        }
+
+        if let Some(topic) = &filter.topic {
+            if !v.topics.contains(topic) {
+                return false;
+            }
+        }
+
+        if let Some(creator) = &filter.creator {
+            if &v.base.creator != creator {
+                return false;
+            }
+        }
+
+        if let Some(voter) = &filter.voter {
+            if !v.base.has_participated(voter) {
+                return false;
+            }
+        }
+
+        if let Some(from) = filter.finished_after {
+            if v.finished_at < from {
+                return false;
+            }
+        }
+
+        if let Some(to) = filter.finished_before {
+            if v.finished_at > to {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+// all the fields are optional, the entry should match all of the provided ones
+#[derive(CandidType, Deserialize, Validate, Clone, Default)]
+pub struct ArchivedVotingsFilter {
+    #[garde(skip)]
+    pub kind: Option<VotingKindTag>,
+    #[garde(skip)]
+    pub topic: Option<DecisionTopicId>,
+    #[garde(skip)]
+    pub creator: Option<Principal>,
+    #[garde(skip)]
+    pub voter: Option<Principal>,
+    #[garde(skip)]
+    pub finished_after: Option<TimestampNs>,
+    #[garde(skip)]
+    pub finished_before: Option<TimestampNs>,
+}
+
+pub type ConvictionProposalId = u64;
+
+// A funding proposal of the conviction voting engine. Instead of being counted once, support accumulates
+// the longer reputation stays staked on the proposal, until the conviction passes the threshold.
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ConvictionProposal {
+    pub id: ConvictionProposalId,
+    pub creator: Principal,
+    pub created_at: TimestampNs,
+    pub title: String,
+    pub description: String,
+    pub beneficiary: Principal,
+    pub requested_storypoints: E8s,
+    // the latest known reputation total supply, refreshed with every stake
+    pub total_supply: E8s,
+    pub stakes: BTreeMap<Principal, E8s>,
+    pub total_staked: E8s,
+    pub conviction: E8s,
+    pub stage: VotingStage,
+}
+
+impl ConvictionProposal {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        id: ConvictionProposalId,
+        title: String,
+        description: String,
+        beneficiary: Principal,
+        requested_storypoints: E8s,
+        total_supply: E8s,
+        caller: Principal,
+        now: TimestampNs,
+    ) -> Self {
+        Self {
+            id,
+            creator: caller,
+            created_at: now,
+            title,
+            description,
+            beneficiary,
+            requested_storypoints,
+            total_supply,
+            stakes: BTreeMap::new(),
+            total_staked: E8s::zero(),
+            conviction: E8s::zero(),
+            stage: VotingStage::InProgress,
+        }
+    }
+
+    pub fn unstake(&mut self, staker: &Principal) {
+        if let Some(prev_stake) = self.stakes.remove(staker) {
+            self.total_staked -= prev_stake;
+        }
+    }
+
+    pub fn stake(&mut self, staker: Principal, amount: E8s, total_supply: E8s) {
+        self.unstake(&staker);
+
+        self.total_staked += &amount;
+        self.stakes.insert(staker, amount);
+        self.total_supply = total_supply;
+    }
+
+    // y(t+1) = α * y(t) + staked(t)
+    pub fn accumulate(&mut self, params: &ConvictionParams) {
+        self.conviction = &self.conviction * &params.decay + &self.total_staked;
+    }
+
+    pub fn can_be_supported(&self) -> bool {
+        matches!(self.stage, VotingStage::InProgress)
+    }
+
+    pub fn can_execute(&self, params: &ConvictionParams, budget: &E8s) -> bool {
+        if !self.can_be_supported() {
+            return false;
+        }
+
+        match params.threshold(&self.requested_storypoints, budget, &self.total_supply) {
+            Some(threshold) => self.conviction >= threshold,
+            None => false,
+        }
+    }
+
+    pub fn generate_resulting_call(&self) -> CallToExecute {
+        let req = MintRewardsRequest {
+            rewards: vec![RewardEntry {
+                solver: self.beneficiary,
+                reward_hours: E8s::zero(),
+                reward_storypoints: self.requested_storypoints.clone(),
+                want_rep: false,
+            }],
+        };
+
+        CallToExecute::new(
+            ENV_VARS.humans_canister_id,
+            "humans__mint_rewards".into(),
+            (req,),
+        )
+        .expect("UNREACHEABLE! Unable to encode args for the call")
+    }
+
+    pub fn as_ext(
+        &self,
+        caller: Principal,
+        params: &ConvictionParams,
+        budget: &E8s,
+    ) -> ConvictionProposalExt {
+        ConvictionProposalExt {
+            id: self.id,
+            creator: self.creator,
+            created_at: self.created_at,
+            title: self.title.clone(),
+            description: self.description.clone(),
+            beneficiary: self.beneficiary,
+            requested_storypoints: self.requested_storypoints.clone(),
+            total_staked: self.total_staked.clone(),
+            conviction: self.conviction.clone(),
+            threshold: params.threshold(&self.requested_storypoints, budget, &self.total_supply),
+            my_stake: self.stakes.get(&caller).cloned(),
+            stage: self.stage.clone(),
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ConvictionProposalExt {
+    pub id: ConvictionProposalId,
+    pub creator: Principal,
+    pub created_at: TimestampNs,
+    pub title: String,
+    pub description: String,
+    pub beneficiary: Principal,
+    pub requested_storypoints: E8s,
+    pub total_staked: E8s,
+    pub conviction: E8s,
+    // None means the proposal requests too big of a share of the current budget
+    pub threshold: Option<E8s>,
+    pub my_stake: Option<E8s>,
+    pub stage: VotingStage,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ConvictionParams {
+    // how often the conviction is recalculated
+    pub period_ns: DurationNs,
+    // α - the share of the conviction that is left after each period
+    pub decay: E8s,
+    // β - the max share of the budget a single proposal can request
+    pub max_ratio: E8s,
+    // ρ - scales the threshold
+    pub weight: E8s,
+    // storypoints added to the budget each period, until it reaches the max budget
+    pub budget_refill_per_period: E8s,
+    pub max_budget: E8s,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct ParticipationParams {
+    pub epoch_duration_ns: DurationNs,
+    // minted to those who took part in every eligible voting of an epoch, the rest get a proportional share
+    pub max_reward_storypoints: E8s,
+}
+
+impl Default for ParticipationParams {
+    // nothing is rewarded by default, until governance decides so
+    fn default() -> Self {
+        Self {
+            epoch_duration_ns: ONE_WEEK_NS,
+            max_reward_storypoints: E8s::zero(),
+        }
+    }
+}
+
+impl ParticipationParams {
+    pub fn check_bounds(&self) -> Result<(), String> {
+        if self.epoch_duration_ns < ONE_DAY_NS || self.epoch_duration_ns > ONE_WEEK_NS * 4 {
+            return Err("Epoch duration should be between one day and four weeks".to_string());
+        }
+
+        if self.max_reward_storypoints > E8s(Nat::from(100_0000_0000u64)) {
+            return Err("Max possible reward is 100 storypoints".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn calc_reward(&self, participated: u32, eligible: u32) -> E8s {
+        if eligible == 0 {
+            return E8s::zero();
+        }
+
+        E8s(
+            self.max_reward_storypoints.0.clone() * Nat::from(participated.min(eligible))
+                / Nat::from(eligible),
+        )
+    }
+}
+
+// votings and work report evaluations, which have finished during the epoch, are eligible
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct ParticipationEpoch {
+    pub id: u64,
+    pub started_at: TimestampNs,
+    pub eligible: u32,
+}
+
+impl ParticipationEpoch {
+    pub fn is_over(&self, params: &ParticipationParams, now: TimestampNs) -> bool {
+        self.started_at + params.epoch_duration_ns <= now
+    }
+}
+
+// an epoch, which is over, but its participants are not rewarded yet - they are processed in batches
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct ClosingParticipationEpoch {
+    pub epoch: Option<ParticipationEpoch>,
+    pub participants: u32,
+    pub rewarded_storypoints: E8s,
+}
+
+pub type WasmHash = [u8; 32];
+
+// the management canister does not accept chunks bigger than 1 MiB
+pub const MAX_WASM_CHUNK_SIZE: usize = 1024 * 1024;
+pub const MAX_WASM_SIZE: u64 = 100 * 1024 * 1024;
+pub const MAX_STAGED_WASMS: u64 = 8;
 
 #[derive(CandidType, Deserialize, Clone, Debug)]
-pub struct Vote {
-    // defines override rules for votes - the vote can only be overriden by a caster with depth <= the previous depth
-    // the lower the depth, the closer was the delegate to the voter in the tree
-    // depth 0 means that the vote was placed by the reputation owner themself
-    pub depth: u32,
-    // None means "Reject"
-    pub normalized_approval_level: Option<E8s>,
-    pub total_voter_reputation: E8s,
+pub struct StagedWasm {
+    pub uploader: Principal,
+    pub uploaded_at: TimestampNs,
+    pub total_len: u64,
+    pub chunks: u32,
+    // only verified wasms can be proposed for an upgrade, no more chunks can be uploaded after that
+    pub verified: bool,
 }
 
-impl Vote {
-    pub fn approval_level(&self) -> Option<E8s> {
-        if let Some(a) = &self.normalized_approval_level {
-            Some(&self.total_voter_reputation * a)
-        } else {
-            None
+// everything needed to install a staged wasm, once its voting has passed
+pub struct PreparedUpgrade {
+    pub canister_id: Principal,
+    pub wasm_hash: WasmHash,
+    pub chunks: Vec<Vec<u8>>,
+    pub arg: Vec<u8>,
+}
+
+impl Default for ConvictionParams {
+    // the budget is empty by default, so nothing gets funded until governance decides so
+    fn default() -> Self {
+        Self {
+            period_ns: ONE_DAY_NS,
+            decay: E8s::f0_9(),
+            max_ratio: E8s::f0_2(),
+            weight: E8s(Nat::from(10_0000u64)),
+            budget_refill_per_period: E8s::zero(),
+            max_budget: E8s::zero(),
         }
     }
 }
 
-#[derive(CandidType, Deserialize, Clone)]
-pub struct CallToExecute {
-    pub canister_id: Principal,
-    pub method_name: String,
-    pub args_raw: Vec<u8>,
-}
+impl ConvictionParams {
+    pub fn check_bounds(&self) -> Result<(), String> {
+        if self.period_ns < ONE_HOUR_NS || self.period_ns > ONE_WEEK_NS {
+            return Err("Period should be between one hour and one week".to_string());
+        }
 
-impl CallToExecute {
-    pub fn new<A: ArgumentEncoder>(
-        canister_id: Principal,
-        method_name: String,
-        args: A,
-    ) -> Result<Self, String> {
-        let args_raw = encode_args(args).map_err(|e| e.to_string())?;
+        if self.decay < E8s::f0_5() || self.decay > E8s(Nat::from(9900_0000u64)) {
+            return Err("Decay should be between 50% and 99%".to_string());
+        }
 
-        Ok(Self {
-            canister_id,
-            method_name,
-            args_raw,
-        })
+        if self.max_ratio < E8s(Nat::from(100_0000u64)) || self.max_ratio > E8s::one() {
+            return Err("Max ratio should be between 1% and 100%".to_string());
+        }
+
+        if self.weight == E8s::zero() || self.weight > E8s::f0_1() {
+            return Err("Weight should be between 0% and 10%".to_string());
+        }
+
+        if self.budget_refill_per_period > self.max_budget {
+            return Err("Budget refill should not be greater than the max budget".to_string());
+        }
+
+        Ok(())
     }
 
-    pub async fn execute(self) -> Result<(), String> {
-        call_raw(self.canister_id, &self.method_name, &self.args_raw, 0)
-            .await
-            .map_err(|(c, m)| format!("Error [{:?}]: {}", c, m))
-            .map(|_| ())
+    // τ = ρ * S / ((1 - α) * (β - r / R)^2)
+    // returns None, if the proposal requests too big of a share of the budget to ever pass
+    pub fn threshold(&self, requested: &E8s, budget: &E8s, total_supply: &E8s) -> Option<E8s> {
+        if budget == &E8s::zero() {
+            return None;
+        }
+
+        let share = requested / budget;
+        if share >= self.max_ratio {
+            return None;
+        }
+
+        let distance = &self.max_ratio - share;
+        let denominator = (E8s::one() - &self.decay) * &distance * distance;
+
+        if denominator == E8s::zero() {
+            return None;
+        }
+
+        Some(&self.weight * total_supply / denominator)
     }
 }
 
-#[derive(CandidType, Deserialize, Clone)]
-pub struct VotingExt {
-    pub id: VotingId,
-    pub creator: Principal,
-    pub created_at: TimestampNs,
-    pub duration_ns: DurationNs,
-    pub total_supply: E8s,
-    pub quorum: E8s,
-    pub consensus_normalized: E8s,
-    pub finish_early: E8s,
-    pub votes_per_option: Vec<(E8s, Option<Vote>)>,
-    pub kind: VotingKind,
-    pub stage: VotingStage,
-}
+impl_storable_for_candid!(
+    StagedWasm,
+    ParticipationParams,
+    ParticipationEpoch,
+    ClosingParticipationEpoch,
+    GuardianCouncil,
+    GuardianVetoes,
+    DepositRefund,
+    TargetVotings,
+    ConvictionProposal,
+    ConvictionParams,
+    Voting,
+    VotingTimer,
+    VotingEvent,
+    VotingParams,
+    ArchivedMotion,
+    ArchivedVoting
+);
 
-#[derive(CandidType, Deserialize, Clone, Copy)]
-pub enum VotingTimer {
-    ExecOnQuorum {
-        voting_id: VotingId,
-        timestamp: TimestampNs,
-    },
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[derive(CandidType, Deserialize, Clone)]
-pub enum VotingEvent {
-    V0001(VotingEventV1),
-}
+    fn principal(n: u8) -> Principal {
+        Principal::from_slice(&[n; 29])
+    }
 
-#[derive(CandidType, Deserialize, Clone)]
-pub enum VotingEventV1 {
-    VotingCreated {
-        voting_id: VotingId,
-        creator: Principal,
-        quorum: E8s,
-        consensus_normalized: E8s,
-        finish_early: E8s,
-        num_options: u32,
-    },
-    VotingExecuting {
-        voting_id: VotingId,
-        call: CallToExecute,
-        rep_per_option: Vec<E8s>,
-        on_timer: bool,
-    },
-    VotingSuccess {
-        voting_id: VotingId,
-    },
-    VotingFail {
-        voting_id: VotingId,
-        reason: String,
-    },
-}
+    #[test]
+    fn voting_id_round_trip() {
+        let ids = [
+            VotingId::StartSolveTask(1),
+            VotingId::EvaluateTask(u64::MAX),
+            VotingId::DeleteTask(0),
+            VotingId::BankSetExchangeRate((SwapFrom::Hour, SwapInto::ICP)),
+            VotingId::HumansEmploy(principal(1)),
+            VotingId::HumansUnemploy(Principal::anonymous()),
+            VotingId::GenericCall([1; 32]),
+            VotingId::UpdateGenericCallAllowList,
+            VotingId::SetVotingParams(VotingKindTag::SetDecayParams),
+            VotingId::Motion([2; 32]),
+            VotingId::MultiChoice([3; 32]),
+            VotingId::SetConvictionParams,
+            VotingId::Veto([4; 32]),
+            VotingId::SetGuardianCouncil,
+            VotingId::Batch([5; 32]),
+            VotingId::CreateDecisionTopic([6; 32]),
+            VotingId::EditDecisionTopic(7),
+            VotingId::DeprecateDecisionTopic(u32::MAX),
+            VotingId::SetParticipationParams,
+            VotingId::UpgradeCanister(Principal::management_canister()),
+            VotingId::TreasuryTransfer([8; 32]),
+            VotingId::SetTreasuryCaps,
+            VotingId::SlashReputation(principal(2)),
+            VotingId::SetDecayParams,
+            VotingId::Proposal(9),
+        ];
 
-#[derive(CandidType, Deserialize, Clone, Default, Debug)]
-pub struct OptionVotes {
-    pub votes: BTreeMap<Principal, Vote>,
-    pub total_voted: E8s,
-    pub approve: E8s,
-    pub reject: E8s,
-}
+        for (idx, id) in ids.iter().enumerate() {
+            let buf = id.to_bytes();
 
-impl OptionVotes {
-    pub fn revert_prev_vote(&mut self, owner: &Principal, depth: u32) -> bool {
-        if let Some(prev_vote) = self.votes.get(&owner) {
-            if prev_vote.depth < depth {
-                return false;
-            }
+            assert_eq!(buf[0] as usize, idx);
+            assert!(buf.len() <= 33);
+            assert_eq!(VotingId::from_bytes(buf), *id);
+        }
+    }
 
-            self.total_voted -= &prev_vote.total_voter_reputation;
+    #[test]
+    fn voting_id_keys_are_ordered() {
+        assert!(VotingId::Proposal(255).to_bytes() < VotingId::Proposal(256).to_bytes());
+        assert!(
+            VotingId::EditDecisionTopic(1).to_bytes() < VotingId::EditDecisionTopic(300).to_bytes()
+        );
+        assert!(
+            VotingId::StartSolveTask(u64::MAX).to_bytes() < VotingId::EvaluateTask(0).to_bytes()
+        );
+        assert!(
+            VotingId::HumansEmploy(Principal::anonymous()).to_bytes()
+                < VotingId::HumansEmploy(principal(0)).to_bytes()
+        );
+    }
 
-            if let Some(approval) = &prev_vote.approval_level() {
-                self.approve -= approval;
-            } else {
-                self.reject -= &prev_vote.total_voter_reputation;
+    #[test]
+    fn voting_kind_tag_round_trip() {
+        for (idx, tag) in VotingKindTag::ALL.iter().enumerate() {
+            assert_eq!(tag.to_bytes().as_ref(), &[idx as u8]);
+            assert_eq!(VotingKindTag::from_bytes(tag.to_bytes()), *tag);
+        }
+    }
+
+    fn e8s(whole: u64) -> E8s {
+        E8s(Nat::from(whole * 1_0000_0000))
+    }
+
+    fn choice_votes(mode: ChoiceMode, num_options: u32, ballots: &[(u64, &[u32])]) -> ChoiceVotes {
+        let mut votes = ChoiceVotes::new(mode, num_options);
+
+        for (idx, (reputation, ranking)) in ballots.iter().enumerate() {
+            let vote = ChoiceVote {
+                depth: 0,
+                ballot: ChoiceBallot::Ranked(ranking.to_vec()),
+                total_voter_reputation: e8s(*reputation),
+                cast_by: None,
+            };
+
+            votes.cast_vote(principal(idx as u8), vote);
+        }
+
+        votes
+    }
+
+    fn tally(whole: &[u64]) -> Vec<E8s> {
+        whole.iter().map(|it| e8s(*it)).collect()
+    }
+
+    #[test]
+    fn instant_runoff_moves_eliminated_votes_to_next_preferences() {
+        let votes = choice_votes(
+            ChoiceMode::RankedChoice,
+            3,
+            &[(40, &[0, 1]), (35, &[1, 0]), (25, &[2, 1])],
+        );
+
+        let (winner, rounds) = votes.calc_instant_runoff();
+
+        assert_eq!(winner, Some(1));
+        assert_eq!(rounds, vec![tally(&[40, 35, 25]), tally(&[40, 60, 0])]);
+    }
+
+    #[test]
+    fn instant_runoff_wins_in_the_first_round_with_majority() {
+        let votes = choice_votes(ChoiceMode::RankedChoice, 2, &[(60, &[0]), (40, &[1])]);
+
+        assert_eq!(
+            votes.calc_instant_runoff(),
+            (Some(0), vec![tally(&[60, 40])])
+        );
+    }
+
+    #[test]
+    fn instant_runoff_ignores_exhausted_ballots() {
+        let votes = choice_votes(
+            ChoiceMode::RankedChoice,
+            3,
+            &[(30, &[0]), (25, &[1]), (20, &[2])],
+        );
+
+        // the last option is eliminated and its only ballot is exhausted, then 30 of 55 is a majority
+        let (winner, rounds) = votes.calc_instant_runoff();
+
+        assert_eq!(winner, Some(0));
+        assert_eq!(rounds.len(), 2);
+    }
+
+    #[test]
+    fn instant_runoff_has_no_winner_on_a_full_tie() {
+        let votes = choice_votes(ChoiceMode::RankedChoice, 2, &[(50, &[0]), (50, &[1])]);
+        assert_eq!(votes.calc_instant_runoff().0, None);
+
+        let empty = choice_votes(ChoiceMode::RankedChoice, 2, &[]);
+        assert_eq!(empty.calc_instant_runoff(), (None, vec![tally(&[0, 0])]));
+    }
+
+    #[test]
+    fn choice_results_per_mode() {
+        let mut base = VotingBase::new(
+            ONE_DAY_NS,
+            e8s(100),
+            e8s(10),
+            E8s::f0_5(),
+            E8s::one(),
+            1,
+            principal(0),
+            0,
+        );
+
+        // a plurality is enough without the ranking
+        base.choice_votes = Some(choice_votes(
+            ChoiceMode::SingleChoice,
+            3,
+            &[(40, &[0]), (35, &[1]), (25, &[2])],
+        ));
+        assert_eq!(
+            base.calc_choice_results(),
+            (Some(0), vec![tally(&[40, 35, 25])])
+        );
+
+        base.choice_votes = Some(choice_votes(
+            ChoiceMode::SingleChoice,
+            2,
+            &[(50, &[0]), (50, &[1])],
+        ));
+        assert_eq!(base.calc_choice_results().0, None);
+
+        base.choice_votes = Some(choice_votes(
+            ChoiceMode::RankedChoice,
+            3,
+            &[(40, &[0, 1]), (35, &[1, 0]), (25, &[2, 1])],
+        ));
+        assert_eq!(base.calc_choice_results().0, Some(1));
+    }
+
+    fn voting(kind: VotingKind) -> Voting {
+        Voting {
+            id: VotingId::Motion([0; 32]),
+            base: VotingBase::new(
+                ONE_DAY_NS,
+                e8s(100),
+                e8s(10),
+                E8s::f0_5(),
+                E8s::one(),
+                1,
+                principal(0),
+                0,
+            ),
+            kind,
+            stage: VotingStage::Executing,
+            topics: Vec::new(),
+            batch_outcomes: None,
+        }
+    }
+
+    fn next_retry_at(result: Result<(VotingTimer, VotingEvent), Box<VotingEvent>>) -> TimestampNs {
+        match result {
+            Ok((VotingTimer::RetryExecution { timestamp, .. }, _)) => timestamp,
+            _ => panic!("Not a retry"),
+        }
+    }
+
+    #[test]
+    fn failed_execution_backs_off_exponentially() {
+        let kind = VotingKind::Motion {
+            title: "Title".to_string(),
+            body: "Body".to_string(),
+            topics: vec![0],
+        };
+        let mut v = voting(kind);
+        let expiry_ns = ONE_DAY_NS * 30;
+
+        assert_eq!(
+            next_retry_at(v.fail_execution("err".to_string(), expiry_ns, 0)),
+            ONE_MINUTE_NS
+        );
+        assert_eq!(
+            next_retry_at(v.fail_execution("err".to_string(), expiry_ns, 0)),
+            2 * ONE_MINUTE_NS
+        );
+        assert_eq!(
+            next_retry_at(v.fail_execution("err".to_string(), expiry_ns, 0)),
+            4 * ONE_MINUTE_NS
+        );
+
+        // the backoff is capped at a day
+        for _ in 0..30 {
+            let _ = v.fail_execution("err".to_string(), expiry_ns, 0);
+        }
+        assert_eq!(
+            next_retry_at(v.fail_execution("err".to_string(), expiry_ns, 0)),
+            ONE_DAY_NS
+        );
+
+        match &v.stage {
+            VotingStage::ExecutionFailed {
+                attempts,
+                expires_at,
+                ..
+            } => {
+                assert_eq!(*attempts, 34);
+                assert_eq!(*expires_at, expiry_ns);
             }
+            _ => panic!("Not failed"),
         }
+    }
+
+    #[test]
+    fn interrupted_retry_can_be_retried_again() {
+        let kind = VotingKind::Motion {
+            title: "Title".to_string(),
+            body: "Body".to_string(),
+            topics: vec![0],
+        };
+        let mut v = voting(kind);
+        assert!(v.is_execution_in_flight());
 
-        return true;
+        assert!(v.fail_execution("err".to_string(), ONE_HOUR_NS, 0).is_ok());
+        assert!(!v.is_execution_in_flight());
+        assert!(v.can_retry_execution());
+
+        // motions have no call, but the retry is still marked as started
+        v.start_retry();
+        assert!(v.is_execution_in_flight());
+        assert!(!v.can_retry_execution());
+
+        // this is what an upgrade does to the dropped call
+        assert!(v
+            .fail_execution("Interrupted".to_string(), ONE_HOUR_NS, 1)
+            .is_ok());
+        assert!(v.can_retry_execution());
     }
 
-    pub fn cast_vote(&mut self, vote_owner: Principal, vote: Vote) {
-        self.total_voted += &vote.total_voter_reputation;
+    #[test]
+    fn failed_execution_gives_up_after_expiry() {
+        let kind = VotingKind::Motion {
+            title: "Title".to_string(),
+            body: "Body".to_string(),
+            topics: vec![0],
+        };
+        let mut v = voting(kind);
 
-        if let Some(approval) = &vote.approval_level() {
-            self.approve += approval;
-        } else {
-            self.reject += &vote.total_voter_reputation;
+        assert_eq!(
+            next_retry_at(v.fail_execution("err".to_string(), ONE_HOUR_NS, 0)),
+            ONE_MINUTE_NS
+        );
+
+        // the next retry never goes past the expiry
+        let now = ONE_HOUR_NS - ONE_MINUTE_NS;
+        assert_eq!(
+            next_retry_at(v.fail_execution("err".to_string(), ONE_HOUR_NS, now)),
+            ONE_HOUR_NS
+        );
+
+        match v.fail_execution("err".to_string(), ONE_HOUR_NS, ONE_HOUR_NS) {
+            Err(event) => match *event {
+                VotingEvent::V0001(VotingEventV1::VotingFail { reason, .. }) => {
+                    assert_eq!(reason, "err (gave up after 3 attempts)")
+                }
+                _ => panic!("Not a fail"),
+            },
+            Ok(_) => panic!("Not expired"),
         }
+    }
 
-        self.votes.insert(vote_owner, vote);
+    fn batch(on_failure: BatchFailurePolicy) -> Voting {
+        let actions = vec![
+            VotingKind::SetGuardianCouncil {
+                council: GuardianCouncil::default(),
+            },
+            VotingKind::DeleteTask { task_id: 1 },
+            VotingKind::DeleteTask { task_id: 2 },
+        ];
+
+        voting(VotingKind::Batch {
+            description: "Description".to_string(),
+            actions,
+            on_failure,
+        })
     }
 
-    pub fn get_normalized_results(&self) -> (E8s, E8s, E8s) {
-        (
-            self.total_voted.clone(),
-            &self.approve / &self.total_voted,
-            &self.reject / &self.total_voted,
-        )
+    fn next_step(v: &Voting) -> Option<u32> {
+        v.next_batch_step().map(|(step, _)| step)
     }
 
-    pub fn total_reached_threshold(&self, threshold: &E8s) -> bool {
-        &self.total_voted >= threshold
+    #[test]
+    fn halted_batch_retries_the_failed_step() {
+        let mut v = batch(BatchFailurePolicy::Halt);
+        assert_eq!(next_step(&v), Some(0));
+
+        v.record_batch_step(0, None, 1).unwrap();
+        assert_eq!(next_step(&v), Some(1));
+
+        assert_eq!(
+            v.record_batch_step(1, Some("boom".to_string()), 2),
+            Err("Step 1 has failed: boom".to_string())
+        );
+        assert_eq!(next_step(&v), Some(1));
+
+        v.record_batch_step(1, None, 3).unwrap();
+        assert_eq!(next_step(&v), Some(2));
+
+        v.record_batch_step(2, None, 4).unwrap();
+        assert_eq!(next_step(&v), None);
+    }
+
+    #[test]
+    fn continued_batch_skips_the_failed_step() {
+        let mut v = batch(BatchFailurePolicy::Continue);
+
+        v.record_batch_step(0, Some("boom".to_string()), 1).unwrap();
+        assert_eq!(next_step(&v), Some(1));
+
+        v.record_batch_step(1, None, 2).unwrap();
+        v.record_batch_step(2, Some("boom".to_string()), 3).unwrap();
+        assert_eq!(next_step(&v), None);
+
+        let failed: Vec<_> = v
+            .batch_outcomes
+            .iter()
+            .flatten()
+            .filter(|it| it.error.is_some())
+            .map(|it| it.step)
+            .collect();
+        assert_eq!(failed, vec![0, 2]);
+    }
+
+    #[test]
+    fn non_batch_votings_have_no_steps() {
+        assert_eq!(
+            next_step(&voting(VotingKind::DeleteTask { task_id: 1 })),
+            None
+        );
+    }
+
+    fn cast_spam_vote(v: &mut Voting, voter: u8, spam: bool, reputation: u64) {
+        let vote = Vote {
+            depth: 0,
+            normalized_approval_level: if spam { Some(E8s::one()) } else { None },
+            total_voter_reputation: e8s(reputation),
+            cast_by: None,
+        };
+
+        v.base
+            .spam_votes
+            .get_or_insert_with(OptionVotes::default)
+            .cast_vote(principal(voter), vote);
+    }
+
+    #[test]
+    fn missed_quorum_is_not_spam() {
+        let mut v = voting(VotingKind::DeleteTask { task_id: 1 });
+        v.base.spam_votes = Some(OptionVotes::default());
+
+        // nobody has voted, so the deposit is refunded
+        assert!(!v.base.is_quorum_reached_for_all_options());
+        assert!(!v.base.is_spam());
+
+        // a spam majority below the quorum is not enough either
+        cast_spam_vote(&mut v, 1, true, 5);
+        assert!(!v.base.is_spam());
+
+        cast_spam_vote(&mut v, 2, false, 2);
+        cast_spam_vote(&mut v, 3, true, 4);
+        assert!(v.base.is_spam());
+
+        cast_spam_vote(&mut v, 4, false, 10);
+        assert!(!v.base.is_spam());
+    }
+
+    #[test]
+    fn secret_ballot_hash_binds_the_vote() {
+        let salt = [7u8; 16];
+        let approve = Some(E8s::one());
+        let hash = SecretBallots::calc_hash(principal(1), 0, &approve, &None, &salt);
+
+        assert_eq!(
+            hash,
+            SecretBallots::calc_hash(principal(1), 0, &approve, &None, &salt)
+        );
+        assert_ne!(
+            hash,
+            SecretBallots::calc_hash(principal(2), 0, &approve, &None, &salt)
+        );
+        assert_ne!(
+            hash,
+            SecretBallots::calc_hash(principal(1), 1, &approve, &None, &salt)
+        );
+        assert_ne!(
+            hash,
+            SecretBallots::calc_hash(principal(1), 0, &Some(E8s::zero()), &None, &salt)
+        );
+        assert_ne!(
+            hash,
+            SecretBallots::calc_hash(principal(1), 0, &approve, &None, &[8u8; 16])
+        );
+    }
+
+    #[test]
+    fn generic_call_target_round_trip() {
+        let targets = [
+            GenericCallTarget {
+                canister_id: Principal::anonymous(),
+                method_name: String::from("a"),
+            },
+            GenericCallTarget {
+                canister_id: principal(3),
+                method_name: "x".repeat(128),
+            },
+        ];
+
+        for target in targets {
+            assert_eq!(GenericCallTarget::from_bytes(target.to_bytes()), target);
+        }
     }
 }
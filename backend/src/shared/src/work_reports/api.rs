@@ -61,7 +61,7 @@ impl CreateWorkReportRequest {
         hasher.update(POW_DELIMITER);
         hasher.update(self.result.as_bytes());
         hasher.update(POW_DELIMITER);
-        hasher.update(&bool_to_bytes(self.want_rep));
+        hasher.update(bool_to_bytes(self.want_rep));
         hasher.update(POW_DELIMITER);
 
         hasher.update(self.nonce.0.to_bytes_le());
@@ -79,7 +79,7 @@ impl Guard<WorkReportState> for CreateWorkReportRequest {
         now: crate::TimestampNs,
     ) -> Result<(), String> {
         if !bufs_le(&self.pow[0..POW_COMPLEXITY.len()], POW_COMPLEXITY) {
-            return Err(format!("The Proof Of Work is of invalid complexity"));
+            return Err("The Proof Of Work is of invalid complexity".to_string());
         }
 
         let expected = self.hash(&caller, &ENV_VARS.work_reports_canister_id);
@@ -135,7 +135,7 @@ impl Guard<WorkReportState> for EvaluateWorkReportRequest {
         }
 
         if self.score > E8s(Nat::from(100_0000_0000u64)) {
-            return Err(format!("Max score is 100.0"));
+            return Err("Max score is 100.0".to_string());
         }
 
         Ok(())
@@ -1,9 +1,10 @@
 use std::collections::BTreeMap;
 
 use candid::{CandidType, Principal};
+use ic_stable_structures::{Cell, StableBTreeMap};
 use serde::Deserialize;
 
-use crate::{e8s::E8s, pagination::PageResponse, TimestampNs};
+use crate::{e8s::E8s, pagination::PageResponse, stable_memory::Memory, TimestampNs};
 
 use super::{
     api::{
@@ -14,14 +15,35 @@ use super::{
     types::{ArchivedWorkReport, ReportEval, WorkReport, WorkReportId},
 };
 
-#[derive(CandidType, Deserialize, Default)]
 pub struct WorkReportState {
+    pub work_report_id_generator: Cell<WorkReportId, Memory>,
+    pub work_reports: StableBTreeMap<WorkReportId, WorkReport, Memory>,
+    pub work_reports_local_archive: StableBTreeMap<WorkReportId, ArchivedWorkReport, Memory>,
+}
+
+// the heap layout this state had before it was moved to stable memory
+#[derive(CandidType, Deserialize)]
+pub struct LegacyWorkReportState {
     pub work_report_id_generator: WorkReportId,
     pub work_reports: BTreeMap<WorkReportId, WorkReport>,
     pub work_reports_local_archive: BTreeMap<WorkReportId, ArchivedWorkReport>,
 }
 
 impl WorkReportState {
+    pub fn migrate_legacy(&mut self, legacy: LegacyWorkReportState) {
+        self.work_report_id_generator
+            .set(legacy.work_report_id_generator)
+            .expect("Unable to store work report id generator");
+
+        for (id, report) in legacy.work_reports {
+            self.work_reports.insert(id, report);
+        }
+
+        for (id, report) in legacy.work_reports_local_archive {
+            self.work_reports_local_archive.insert(id, report);
+        }
+    }
+
     pub fn create_work_report(
         &mut self,
         req: CreateWorkReportRequest,
@@ -49,12 +71,13 @@ impl WorkReportState {
         CreateWorkReportResponse { id }
     }
 
+    // once the evaluation is over, returns the report (None if it was spam) and its direct evaluators
     pub fn evalute_work_report(
         &mut self,
         req: EvaluateWorkReportRequest,
         _now: TimestampNs,
-    ) -> Option<Option<WorkReport>> {
-        let report = self.work_reports.get_mut(&req.id).unwrap();
+    ) -> Option<(Option<WorkReport>, Vec<Principal>)> {
+        let mut report = self.work_reports.get(&req.id).unwrap();
         let rep_delegation_tree = req
             .reputation_proof
             .body
@@ -96,11 +119,19 @@ impl WorkReportState {
             None
         };
 
+        self.work_reports.insert(req.id, report);
+
         match result {
-            Some(true) => Some(Some(self.delete_work_report(req.id))),
+            Some(true) => {
+                let report = self.delete_work_report(req.id);
+                let evaluators = report.list_direct_evaluators();
+
+                Some((Some(report), evaluators))
+            }
             Some(false) => {
-                self.delete_work_report(req.id);
-                Some(None)
+                let report = self.delete_work_report(req.id);
+
+                Some((None, report.list_direct_evaluators()))
             }
             None => None,
         }
@@ -119,71 +150,34 @@ impl WorkReportState {
     }
 
     pub fn get_work_report_ids(&self, req: GetWorkReportIdsRequest) -> GetWorkReportIdsResponse {
-        let (entries, left) = if req.filter.archived {
-            let _iter = self.work_reports_local_archive.iter();
-
-            let mut iter: Box<dyn Iterator<Item = (&u64, &ArchivedWorkReport)>> =
-                if req.pagination.reversed {
-                    if let Some(topic) = req.filter.decision_topic_id {
-                        Box::new(_iter.rev().filter(move |(_, it)| match it {
-                            ArchivedWorkReport::V0001(r) => r.decision_topic == topic,
-                        }))
-                    } else {
-                        Box::new(_iter.rev())
-                    }
-                } else {
-                    if let Some(topic) = req.filter.decision_topic_id {
-                        Box::new(_iter.filter(move |(_, it)| match it {
-                            ArchivedWorkReport::V0001(r) => r.decision_topic == topic,
-                        }))
-                    } else {
-                        Box::new(_iter)
-                    }
-                };
-
-            let entries = iter
-                .by_ref()
-                .take(req.pagination.take as usize)
+        let topic = req.filter.decision_topic_id;
+
+        // stable btreemap can't be iterated backwards, so the matching ids are collected first
+        let mut ids: Vec<WorkReportId> = if req.filter.archived {
+            self.work_reports_local_archive
+                .iter()
+                .filter(|(_, it)| match it {
+                    ArchivedWorkReport::V0001(r) => topic.is_none_or(|t| r.decision_topic == t),
+                })
                 .map(|(id, _)| id)
-                .cloned()
-                .collect();
-
-            let left = iter.count() as u32;
-
-            (entries, left)
+                .collect()
         } else {
-            let _iter = self.work_reports.iter();
-
-            let mut iter: Box<dyn Iterator<Item = (&u64, &WorkReport)>> = if req.pagination.reversed
-            {
-                if let Some(topic) = req.filter.decision_topic_id {
-                    Box::new(
-                        _iter
-                            .rev()
-                            .filter(move |(_, it)| it.decision_topic == topic),
-                    )
-                } else {
-                    Box::new(_iter.rev())
-                }
-            } else {
-                if let Some(topic) = req.filter.decision_topic_id {
-                    Box::new(_iter.filter(move |(_, it)| it.decision_topic == topic))
-                } else {
-                    Box::new(_iter)
-                }
-            };
-
-            let entries = iter
-                .by_ref()
-                .take(req.pagination.take as usize)
+            self.work_reports
+                .iter()
+                .filter(|(_, it)| topic.is_none_or(|t| it.decision_topic == t))
                 .map(|(id, _)| id)
-                .cloned()
-                .collect();
+                .collect()
+        };
+
+        if req.pagination.reversed {
+            ids.reverse();
+        }
 
-            let left = iter.count() as u32;
+        let mut iter = ids.into_iter();
 
-            (entries, left)
-        };
+        let entries = iter.by_ref().take(req.pagination.take as usize).collect();
+
+        let left = iter.count() as u32;
 
         GetWorkReportIdsResponse {
             entries,
@@ -202,18 +196,20 @@ impl WorkReportState {
             if let Some(report) = self.work_reports.get(&id) {
                 entries.push(Some(WorkReportKind::Common(report.as_pub(&caller))));
             } else if let Some(report) = self.work_reports_local_archive.get(&id) {
-                entries.push(Some(WorkReportKind::Archive(report.clone())));
+                entries.push(Some(WorkReportKind::Archive(report)));
             } else {
                 entries.push(None);
             }
         }
 
-        return GetWorkReportsByIdResponse { entries };
+        GetWorkReportsByIdResponse { entries }
     }
 
     fn generate_work_report_id(&mut self) -> WorkReportId {
-        let id = self.work_report_id_generator;
-        self.work_report_id_generator += 1;
+        let id = *self.work_report_id_generator.get();
+        self.work_report_id_generator
+            .set(id + 1)
+            .expect("Unable to store work report id generator");
 
         id
     }
@@ -4,7 +4,9 @@ use candid::{CandidType, Principal};
 use garde::Validate;
 use serde::Deserialize;
 
-use crate::{e8s::E8s, liquid_democracy::types::DecisionTopicId, TimestampNs};
+use crate::{
+    e8s::E8s, impl_storable_for_candid, liquid_democracy::types::DecisionTopicId, TimestampNs,
+};
 
 pub type WorkReportId = u64;
 
@@ -43,6 +45,7 @@ pub struct WorkReport {
 }
 
 impl WorkReport {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: WorkReportId,
         decision_topic: DecisionTopicId,
@@ -76,7 +79,7 @@ impl WorkReport {
     }
 
     pub fn revert_prev_eval(&mut self, evaluator: &Principal, depth: u32) -> bool {
-        if let Some(prev_eval) = self.evaluation.remove(&evaluator) {
+        if let Some(prev_eval) = self.evaluation.remove(evaluator) {
             if prev_eval.depth < depth {
                 return false;
             }
@@ -123,6 +126,15 @@ impl WorkReport {
         &self.total_rep_said_is_spam / &self.total_rep_supply >= Self::threshold()
     }
 
+    // only those who have evaluated the report themselves, not through a delegate
+    pub fn list_direct_evaluators(&self) -> Vec<Principal> {
+        self.evaluation
+            .iter()
+            .filter(|(_, it)| it.depth == 0)
+            .map(|(evaluator, _)| *evaluator)
+            .collect()
+    }
+
     pub fn already_evaluated(&self, caller: &Principal) -> bool {
         self.evaluation.contains_key(caller)
     }
@@ -232,3 +244,5 @@ pub struct ArchivedWorkReportV0001 {
 pub enum ArchivedWorkReport {
     V0001(ArchivedWorkReportV0001),
 }
+
+impl_storable_for_candid!(WorkReport, ArchivedWorkReport);